@@ -0,0 +1,132 @@
+use crate::error::CropperError;
+use crate::settings::Settings;
+use crate::{IMAGE_EXTENSIONS, ImageCropper, compute_crop, export, filename};
+use eframe::egui;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Suffix the default filename template adds to the stem. Files ending in
+/// it are taken for earlier outputs and skipped.
+pub const OUTPUT_SUFFIX: &str = "_cropped";
+
+/// Outcome of running a crop template over a folder.
+#[derive(Default)]
+pub struct BatchSummary {
+    pub saved: usize,
+    pub failed: Vec<(PathBuf, CropperError)>,
+}
+
+/// Returns the supported images in `folder`, sorted by name, skipping
+/// outputs of a previous batch run.
+pub fn folder_images(folder: &Path) -> Result<Vec<PathBuf>, CropperError> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+        })
+        .filter(|path| {
+            !path
+                .file_stem()
+                .is_some_and(|s| s.to_string_lossy().ends_with(OUTPUT_SUFFIX))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Crops one file the way `save_cropped` would: turned upright if
+/// `auto_orient` is set, at the JPEG quality and with the source's colour
+/// profile when the settings keep it. The output goes into `out_dir` under
+/// the filename template, numbered if `used` already holds that name.
+fn crop_file(
+    path: &Path,
+    out_dir: &Path,
+    template: egui::Rect,
+    settings: &Settings,
+    used: &mut HashSet<OsString>,
+) -> Result<(), CropperError> {
+    let loaded = ImageCropper::decode_file(path, settings.auto_orient)?;
+    let cropped = compute_crop(&loaded.image, template);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or("png".to_owned(), |e| e.to_string_lossy().to_lowercase());
+    let name = filename::output_name(
+        &settings.filename_template,
+        &stem,
+        (cropped.width(), cropped.height()),
+        &extension,
+        settings.ratio_epsilon,
+    )?;
+    let output = out_dir.join(unique_name(Path::new(&name), used));
+    if settings.protect_source && output == path {
+        return Err(CropperError::OverwriteSource(output));
+    }
+    let icc_profile = loaded
+        .icc_profile
+        .as_deref()
+        .filter(|_| settings.preserve_icc_profile);
+    export::save_image_with_profile(&cropped, &output, icc_profile, settings.jpeg_quality)
+}
+
+/// `name` if it is not in `used` yet, else the first free `stem-1.ext`,
+/// `stem-2.ext`, ... The returned name is added to `used`.
+pub fn unique_name(name: &Path, used: &mut HashSet<OsString>) -> OsString {
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = name.as_os_str().to_owned();
+    let mut n = 0;
+    while !used.insert(candidate.clone()) {
+        n += 1;
+        candidate = format!("{}-{}{}", stem, n, extension).into();
+    }
+    candidate
+}
+
+/// Crops every image in `folder` with the normalized `template` rect and
+/// writes the results next to the originals, named by the filename template.
+pub fn apply_template(
+    folder: &Path,
+    template: egui::Rect,
+    settings: &Settings,
+) -> Result<BatchSummary, CropperError> {
+    let mut summary = BatchSummary::default();
+    let mut used = HashSet::new();
+    for path in folder_images(folder)? {
+        match crop_file(&path, folder, template, settings, &mut used) {
+            Ok(()) => summary.saved += 1,
+            Err(e) => summary.failed.push((path, e)),
+        }
+    }
+    Ok(summary)
+}
+
+/// Crops each of `paths` with the normalized `template` rect into
+/// `out_dir`, named by the filename template, counting finished files in
+/// `progress`. Files from different folders that share a name get numbered
+/// outputs instead of overwriting each other.
+pub fn crop_files(
+    paths: &[PathBuf],
+    out_dir: &Path,
+    template: egui::Rect,
+    settings: &Settings,
+    progress: &AtomicUsize,
+) -> BatchSummary {
+    let mut summary = BatchSummary::default();
+    let mut used = HashSet::new();
+    for path in paths {
+        match crop_file(path, out_dir, template, settings, &mut used) {
+            Ok(()) => summary.saved += 1,
+            Err(e) => summary.failed.push((path.clone(), e)),
+        }
+        progress.fetch_add(1, Ordering::Relaxed);
+    }
+    summary
+}
@@ -0,0 +1,144 @@
+use crate::error::CropperError;
+use crate::{AspectRatioMode, crop_pixel_bounds};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// A crop and the ratio settings behind it, written as a JSON sidecar so
+/// other tools can re-apply it:
+///
+/// ```json
+/// {
+///   "image": { "width": 4000, "height": 3000 },
+///   "normalized": { "min_x": 0.1, "min_y": 0.2, "max_x": 0.9, "max_y": 0.8 },
+///   "pixels": { "x": 400, "y": 600, "width": 3200, "height": 1800 },
+///   "aspect_ratio": "16:9",
+///   "portrait": false,
+///   "custom_ratio": [4, 3]
+/// }
+/// ```
+///
+/// Only the normalized rect is needed to read one back; the pixel rect is
+/// for tools that work on the same source image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "CropFile", try_from = "CropFile")]
+pub struct CropData {
+    pub rect: egui::Rect,
+    pub image_size: (u32, u32),
+    pub aspect_ratio: AspectRatioMode,
+    pub is_portrait: bool,
+    pub custom_ratio: (u32, u32),
+}
+
+/// The sidecar's layout on disk.
+#[derive(Serialize, Deserialize)]
+struct CropFile {
+    #[serde(default)]
+    image: ImageSize,
+    normalized: Normalized,
+    #[serde(default, skip_deserializing)]
+    pixels: Option<Pixels>,
+    #[serde(default)]
+    aspect_ratio: Option<String>,
+    #[serde(default)]
+    portrait: bool,
+    #[serde(default)]
+    custom_ratio: Option<(u32, u32)>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ImageSize {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Normalized {
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+}
+
+#[derive(Serialize)]
+struct Pixels {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl From<CropData> for CropFile {
+    fn from(data: CropData) -> Self {
+        let (width, height) = data.image_size;
+        let (min, max) = (data.rect.min, data.rect.max);
+        let pixels = (width > 0 && height > 0).then(|| {
+            let (x, y, width, height) = crop_pixel_bounds(width, height, data.rect);
+            Pixels {
+                x,
+                y,
+                width,
+                height,
+            }
+        });
+        Self {
+            image: ImageSize { width, height },
+            normalized: Normalized {
+                min_x: min.x,
+                min_y: min.y,
+                max_x: max.x,
+                max_y: max.y,
+            },
+            pixels,
+            aspect_ratio: Some(data.aspect_ratio.key()),
+            portrait: data.is_portrait,
+            custom_ratio: Some(data.custom_ratio),
+        }
+    }
+}
+
+/// Missing or unknown ratio fields fall back to freehand; the normalized
+/// rect must be finite and non-empty.
+impl TryFrom<CropFile> for CropData {
+    type Error = String;
+
+    fn try_from(file: CropFile) -> Result<Self, String> {
+        let Normalized {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } = file.normalized;
+        if ![min_x, min_y, max_x, max_y].iter().all(|v| v.is_finite()) {
+            return Err("the normalized rect is not finite".to_owned());
+        }
+        let rect = egui::Rect::from_min_max(egui::pos2(min_x, min_y), egui::pos2(max_x, max_y));
+        if !rect.is_positive() {
+            return Err("the normalized rect is empty".to_owned());
+        }
+        Ok(Self {
+            rect,
+            image_size: (file.image.width, file.image.height),
+            aspect_ratio: file
+                .aspect_ratio
+                .as_deref()
+                .and_then(AspectRatioMode::from_key)
+                .unwrap_or(AspectRatioMode::Free),
+            is_portrait: file.portrait,
+            custom_ratio: file
+                .custom_ratio
+                .filter(|&(w, h)| w >= 1 && h >= 1)
+                .unwrap_or((4, 3)),
+        })
+    }
+}
+
+impl CropData {
+    pub fn to_json(&self) -> String {
+        // Plain numbers, strings and bools always serialize
+        serde_json::to_string_pretty(self).expect("crop data serializes")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CropperError> {
+        serde_json::from_str(text).map_err(|e| CropperError::InvalidCropData(e.to_string()))
+    }
+}
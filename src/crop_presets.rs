@@ -0,0 +1,61 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Named crops in normalized coordinates, so one saved on a photo applies
+/// to any other. Stored presets are read back through `set`, so ones that
+/// aren't usable crops are dropped.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(into = "Vec<(String, egui::Rect)>", from = "Vec<(String, egui::Rect)>")]
+pub struct CropPresets {
+    presets: Vec<(String, egui::Rect)>,
+}
+
+impl CropPresets {
+    /// Key the presets are kept under in eframe's storage.
+    pub const STORAGE_KEY: &str = "crop_presets";
+
+    /// Saves `rect` under `name`, replacing a preset of that name. The name
+    /// is trimmed to its first line; an empty one or a rect that isn't a
+    /// usable crop is ignored.
+    pub fn set(&mut self, name: &str, rect: egui::Rect) {
+        let name = name.lines().next().unwrap_or_default().trim();
+        if !rect.is_finite() {
+            return;
+        }
+        let unit = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        let rect = rect.intersect(unit);
+        if name.is_empty() || !rect.is_positive() {
+            return;
+        }
+        match self.presets.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = rect,
+            None => self.presets.push((name.to_owned(), rect)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.presets.retain(|(n, _)| n != name);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, egui::Rect)> {
+        self.presets
+            .iter()
+            .map(|(name, rect)| (name.as_str(), *rect))
+    }
+}
+
+impl From<Vec<(String, egui::Rect)>> for CropPresets {
+    fn from(stored: Vec<(String, egui::Rect)>) -> Self {
+        let mut presets = Self::default();
+        for (name, rect) in stored {
+            presets.set(&name, rect);
+        }
+        presets
+    }
+}
+
+impl From<CropPresets> for Vec<(String, egui::Rect)> {
+    fn from(presets: CropPresets) -> Self {
+        presets.presets
+    }
+}
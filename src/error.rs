@@ -0,0 +1,136 @@
+use std::fmt;
+
+/// Errors produced by the core load, crop and export operations.
+#[derive(Debug)]
+pub enum CropperError {
+    /// The source file could not be read or decoded.
+    Load(image::ImageError),
+    /// An operation needed an image but none is loaded.
+    NoImage,
+    /// The perspective quad is degenerate and cannot be straightened.
+    DegenerateQuad,
+    /// The cropped image could not be encoded in the requested format.
+    Encode(image::ImageError),
+    /// Writing the output file failed.
+    Io(std::io::Error),
+    /// The system clipboard could not be accessed.
+    Clipboard(arboard::Error),
+    /// The format was left out of this build (see the Cargo features).
+    #[cfg(not(feature = "avif"))]
+    FormatDisabled(&'static str),
+    /// A multi-crop region file is malformed or does not fit the image.
+    InvalidRegions(String),
+    /// A crop data file is malformed.
+    InvalidCropData(String),
+    /// A typed path does not name an existing file.
+    NotAFile(std::path::PathBuf),
+    /// The output filename template cannot be expanded.
+    InvalidTemplate(String),
+    /// A save would replace the source image while it is protected.
+    OverwriteSource(std::path::PathBuf),
+    /// A paste was requested but the clipboard holds no image.
+    NoClipboardImage,
+    /// The face detection model could not be loaded.
+    #[cfg(feature = "face-detect")]
+    FaceModel(std::io::Error),
+    /// Face detection found no face in the image.
+    #[cfg(feature = "face-detect")]
+    NoFace,
+    /// Some files of a batch could not be cropped.
+    BatchFailed(Vec<(std::path::PathBuf, CropperError)>),
+}
+
+impl fmt::Display for CropperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CropperError::Load(e) => write!(f, "Failed to open image: {}", e),
+            CropperError::NoImage => write!(f, "No image loaded"),
+            CropperError::NoClipboardImage => write!(f, "The clipboard does not contain an image"),
+            CropperError::DegenerateQuad => {
+                write!(f, "The perspective corners do not form a valid shape")
+            }
+            CropperError::Encode(e) => write!(f, "Failed to encode image: {}", e),
+            CropperError::Io(e) => write!(f, "Failed to write file: {}", e),
+            CropperError::Clipboard(e) => write!(f, "Clipboard unavailable: {}", e),
+            #[cfg(not(feature = "avif"))]
+            CropperError::FormatDisabled(format) => write!(
+                f,
+                "{} support is not included in this build (enable the \"{}\" feature)",
+                format,
+                format.to_lowercase()
+            ),
+            CropperError::InvalidRegions(message) => write!(f, "Invalid regions: {}", message),
+            CropperError::InvalidCropData(message) => {
+                write!(f, "Invalid crop data: {}", message)
+            }
+            CropperError::NotAFile(path) => write!(f, "No such file: {}", path.display()),
+            CropperError::InvalidTemplate(message) => {
+                write!(f, "Invalid filename template: {}", message)
+            }
+            CropperError::OverwriteSource(path) => write!(
+                f,
+                "Not saved: {} is the source image (turn off \"Protect source\" to overwrite it)",
+                path.display()
+            ),
+            #[cfg(feature = "face-detect")]
+            CropperError::FaceModel(e) => write!(f, "Failed to load the face model: {}", e),
+            #[cfg(feature = "face-detect")]
+            CropperError::NoFace => write!(f, "No face was found in the image"),
+            CropperError::BatchFailed(failed) => {
+                write!(f, "{} file(s) could not be cropped:", failed.len())?;
+                for (path, e) in failed {
+                    write!(f, "\n{}: {}", path.display(), e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CropperError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CropperError::Load(e) | CropperError::Encode(e) => Some(e),
+            CropperError::Io(e) => Some(e),
+            CropperError::Clipboard(e) => Some(e),
+            CropperError::NoImage
+            | CropperError::NoClipboardImage
+            | CropperError::DegenerateQuad
+            | CropperError::InvalidRegions(_)
+            | CropperError::InvalidCropData(_)
+            | CropperError::NotAFile(_)
+            | CropperError::InvalidTemplate(_)
+            | CropperError::OverwriteSource(_)
+            | CropperError::BatchFailed(_) => None,
+            #[cfg(not(feature = "avif"))]
+            CropperError::FormatDisabled(_) => None,
+            #[cfg(feature = "face-detect")]
+            CropperError::FaceModel(e) => Some(e),
+            #[cfg(feature = "face-detect")]
+            CropperError::NoFace => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CropperError {
+    fn from(e: std::io::Error) -> Self {
+        CropperError::Io(e)
+    }
+}
+
+impl From<arboard::Error> for CropperError {
+    fn from(e: arboard::Error) -> Self {
+        CropperError::Clipboard(e)
+    }
+}
+
+impl From<image::ImageError> for CropperError {
+    /// Errors from encoding; I/O failures are kept distinct so the user can
+    /// tell a bad format from a bad destination.
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => CropperError::Io(e),
+            e => CropperError::Encode(e),
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use crate::error::CropperError;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat, Rgba};
+use imageproc::drawing::draw_line_segment_mut;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Sizes bundled into a favicon `.ico`.
+pub const FAVICON_SIZES: [u32; 3] = [16, 32, 48];
+
+/// Sizes bundled into an "Export as ICO" app icon.
+pub const ICO_SIZES: [u32; 4] = [16, 32, 48, 256];
+
+/// Encoder speed for AVIF exports, 1 (slowest, smallest) to 10.
+#[cfg(feature = "avif")]
+pub const AVIF_SPEED: u8 = 6;
+
+/// Quality used for the JPEG candidate of a "Smallest file" export.
+pub const SMALLEST_JPEG_QUALITY: u8 = 85;
+
+/// Colour of the crop outline traced on the original in a before/after image.
+pub const OUTLINE_COLOR: Rgba<u8> = Rgba([255, 48, 48, 255]);
+
+/// Format picked beside the Save button. `Auto` goes by the typed
+/// extension, the others choose the encoder whatever the name says.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum SaveFormat {
+    #[default]
+    Auto,
+    Png,
+    Jpeg,
+    Bmp,
+    WebP,
+}
+
+impl SaveFormat {
+    pub const ALL: [SaveFormat; 5] = [
+        SaveFormat::Auto,
+        SaveFormat::Png,
+        SaveFormat::Jpeg,
+        SaveFormat::Bmp,
+        SaveFormat::WebP,
+    ];
+
+    /// Extensions the format is recognised by, the preferred one first.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            SaveFormat::Auto => &[],
+            SaveFormat::Png => &["png"],
+            SaveFormat::Jpeg => &["jpg", "jpeg"],
+            SaveFormat::Bmp => &["bmp"],
+            SaveFormat::WebP => &["webp"],
+        }
+    }
+
+    /// The encoder this format picks; `None` for `Auto`.
+    pub fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            SaveFormat::Auto => None,
+            SaveFormat::Png => Some(ImageFormat::Png),
+            SaveFormat::Jpeg => Some(ImageFormat::Jpeg),
+            SaveFormat::Bmp => Some(ImageFormat::Bmp),
+            SaveFormat::WebP => Some(ImageFormat::WebP),
+        }
+    }
+
+    /// `path` with this format's extension appended unless it already has
+    /// one. With `Auto`, a name without a known image extension gets `.png`.
+    pub fn apply_to(self, path: &Path) -> PathBuf {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let append = match self {
+            SaveFormat::Auto if ImageFormat::from_path(path).is_ok() || is_avif(path) => None,
+            SaveFormat::Auto => Some("png"),
+            format if format.extensions().contains(&extension.as_str()) => None,
+            format => Some(format.extensions()[0]),
+        };
+        match append {
+            Some(append) => {
+                let mut name = path.file_name().unwrap_or_default().to_os_string();
+                name.push(".");
+                name.push(append);
+                path.with_file_name(name)
+            }
+            None => path.to_path_buf(),
+        }
+    }
+}
+
+impl std::fmt::Display for SaveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SaveFormat::Auto => "By Name",
+            SaveFormat::Png => "PNG",
+            SaveFormat::Jpeg => "JPEG",
+            SaveFormat::Bmp => "BMP",
+            SaveFormat::WebP => "WebP",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Result of a finished "Smallest file" export.
+pub struct SmallestExport {
+    pub path: PathBuf,
+    pub format: ImageFormat,
+    pub size: usize,
+}
+
+/// Encodes `image` into every candidate format in memory and returns the
+/// smallest encoding along with its format.
+pub fn encode_smallest(
+    image: &DynamicImage,
+    icc_profile: Option<&[u8]>,
+) -> Result<(ImageFormat, Vec<u8>), image::ImageError> {
+    let mut best: Option<(ImageFormat, Vec<u8>)> = None;
+    let mut last_err = None;
+
+    let mut candidates = vec![ImageFormat::Png, ImageFormat::WebP];
+    // JPEG would silently drop transparency, so only offer it for opaque images
+    if !image.color().has_alpha() {
+        candidates.push(ImageFormat::Jpeg);
+    }
+
+    for format in candidates {
+        match encode(image, format, icc_profile) {
+            Ok(bytes) => {
+                if best.as_ref().is_none_or(|(_, b)| bytes.len() < b.len()) {
+                    best = Some((format, bytes));
+                }
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    match (best, last_err) {
+        (Some(best), _) => Ok(best),
+        (None, Some(e)) => Err(e),
+        (None, None) => unreachable!("at least one candidate format is always tried"),
+    }
+}
+
+/// Saves `image` to `path`, picking the format from the extension.
+pub fn save_image(image: &DynamicImage, path: &Path) -> Result<(), CropperError> {
+    image.save(path)?;
+    Ok(())
+}
+
+/// Whether saving to `path` would lose transparency.
+pub fn drops_alpha(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format == ImageFormat::Jpeg)
+}
+
+/// Like `save_image`, but embeds `icc_profile` in formats that carry one
+/// (PNG, JPEG and WebP) and writes JPEGs at `jpeg_quality` (1-100). WebP and
+/// GIF are converted to 8 bits first; other formats are saved as
+/// `save_image` would.
+pub fn save_image_with_profile(
+    image: &DynamicImage,
+    path: &Path,
+    icc_profile: Option<&[u8]>,
+    jpeg_quality: u8,
+) -> Result<(), CropperError> {
+    let format = ImageFormat::from_path(path);
+    match (icc_profile, format) {
+        (_, Ok(ImageFormat::Jpeg)) => {
+            std::fs::write(path, encode_jpeg(image, jpeg_quality, icc_profile)?)?;
+            Ok(())
+        }
+        (_, Ok(format @ (ImageFormat::WebP | ImageFormat::Gif)))
+        | (Some(_), Ok(format @ ImageFormat::Png)) => {
+            std::fs::write(path, encode(image, format, icc_profile)?)?;
+            Ok(())
+        }
+        _ => save_image(image, path),
+    }
+}
+
+/// Crops the largest rect of each of `ratios` centered on the normalized
+/// `center` and hands it to `save` along with the stem `<stem>_<W>x<H>`,
+/// e.g. `photo_16x9`, to name it by. Returns how many were saved.
+pub fn export_ratios(
+    image: &DynamicImage,
+    center: eframe::egui::Pos2,
+    ratios: &[crate::AspectRatioMode],
+    stem: &str,
+    mut save: impl FnMut(&str, DynamicImage) -> Result<(), CropperError>,
+) -> Result<usize, CropperError> {
+    let image_size = eframe::egui::vec2(image.width() as f32, image.height() as f32);
+    let mut count = 0;
+    for mode in ratios {
+        let Some(ratio) = mode.ratio() else {
+            continue;
+        };
+        let rect = crate::ImageCropper::max_rect_around(center, image_size, ratio);
+        let stem = format!("{}_{}", stem, mode.key().replace(':', "x"));
+        save(&stem, crate::compute_crop(image, rect))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+pub fn is_avif(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("avif"))
+}
+
+/// Writes `image` as AVIF with `quality` from 1 to 100. Fails with
+/// `FormatDisabled` when built without the `avif` feature.
+pub fn save_avif(image: &DynamicImage, path: &Path, quality: u8) -> Result<(), CropperError> {
+    #[cfg(feature = "avif")]
+    {
+        let mut bytes = Vec::new();
+        let encoder = image::codecs::avif::AvifEncoder::new_with_speed_quality(
+            &mut bytes,
+            AVIF_SPEED,
+            quality.clamp(1, 100),
+        );
+        image.to_rgba8().write_with_encoder(encoder)?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+    #[cfg(not(feature = "avif"))]
+    {
+        let _ = (image, path, quality);
+        Err(CropperError::FormatDisabled("AVIF"))
+    }
+}
+
+/// Centers `image` on a transparent square canvas; square images are
+/// returned unchanged.
+pub fn pad_to_square(image: &DynamicImage) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    if width == height {
+        return image.clone();
+    }
+    let side = width.max(height);
+    let mut canvas = image::RgbaImage::new(side, side);
+    image::imageops::overlay(
+        &mut canvas,
+        &image.to_rgba8(),
+        ((side - width) / 2) as i64,
+        ((side - height) / 2) as i64,
+    );
+    DynamicImage::ImageRgba8(canvas)
+}
+
+/// Writes a multi-resolution `.ico` to `path`, resampling each of `sizes`
+/// from the full-resolution `image`. Non-square images are padded to square
+/// first so icons are never stretched.
+pub fn save_ico(image: &DynamicImage, sizes: &[u32], path: &Path) -> Result<(), CropperError> {
+    let image = pad_to_square(image);
+    let frames = sizes
+        .iter()
+        .map(|&size| {
+            let resized = image
+                .resize_exact(size, size, image::imageops::FilterType::Lanczos3)
+                .to_rgba8();
+            IcoFrame::as_png(resized.as_raw(), size, size, ExtendedColorType::Rgba8)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let file = std::fs::File::create(path)?;
+    IcoEncoder::new(std::io::BufWriter::new(file)).encode_images(&frames)?;
+    Ok(())
+}
+
+/// Places `original` and `cropped` side by side on a `background` canvas,
+/// `gutter` pixels apart and from the edges, both vertically centered. If
+/// `outline` is given, those pixel corners (clockwise from top-left) are traced
+/// on the original to show where the crop came from.
+pub fn compose_side_by_side(
+    original: &DynamicImage,
+    cropped: &DynamicImage,
+    outline: Option<[(f32, f32); 4]>,
+    gutter: u32,
+    background: Rgba<u8>,
+) -> DynamicImage {
+    let width = original.width() + cropped.width() + gutter * 3;
+    let height = original.height().max(cropped.height()) + gutter * 2;
+    let mut canvas = image::RgbaImage::from_pixel(width, height, background);
+
+    let mut before = original.to_rgba8();
+    if let Some(corners) = outline {
+        // Thick enough to stay visible once the comparison is scaled down
+        let thickness = (before.width().min(before.height()) / 200).max(2) as i32;
+        for i in 0..4 {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            for dx in 0..thickness {
+                for dy in 0..thickness {
+                    let offset = |(x, y): (f32, f32)| {
+                        let half = thickness as f32 / 2.0;
+                        (x + dx as f32 - half, y + dy as f32 - half)
+                    };
+                    draw_line_segment_mut(&mut before, offset(a), offset(b), OUTLINE_COLOR);
+                }
+            }
+        }
+    }
+
+    let top = |h: u32| ((height - h) / 2) as i64;
+    image::imageops::overlay(&mut canvas, &before, gutter as i64, top(original.height()));
+    image::imageops::overlay(
+        &mut canvas,
+        &cropped.to_rgba8(),
+        (original.width() + gutter * 2) as i64,
+        top(cropped.height()),
+    );
+    DynamicImage::ImageRgba8(canvas)
+}
+
+/// Encodes `image` as the smallest candidate format and writes it next to
+/// `path`, replacing the extension with the one of the chosen format.
+pub fn save_smallest(
+    image: &DynamicImage,
+    path: &Path,
+    icc_profile: Option<&[u8]>,
+) -> Result<SmallestExport, CropperError> {
+    let (format, bytes) = encode_smallest(image, icc_profile)?;
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    let path = path.with_extension(extension);
+    std::fs::write(&path, &bytes)?;
+
+    Ok(SmallestExport {
+        path,
+        format,
+        size: bytes.len(),
+    })
+}
+
+fn encode(
+    image: &DynamicImage,
+    format: ImageFormat,
+    icc_profile: Option<&[u8]>,
+) -> Result<Vec<u8>, image::ImageError> {
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Jpeg => return encode_jpeg(image, SMALLEST_JPEG_QUALITY, icc_profile),
+        // The WebP encoder only accepts 8-bit buffers
+        ImageFormat::WebP => {
+            let mut encoder = WebPEncoder::new_lossless(&mut bytes);
+            if let Some(profile) = icc_profile {
+                let _ = encoder.set_icc_profile(profile.to_vec());
+            }
+            image.to_rgba8().write_with_encoder(encoder)?;
+        }
+        ImageFormat::Png => {
+            let mut encoder = PngEncoder::new(&mut bytes);
+            if let Some(profile) = icc_profile {
+                let _ = encoder.set_icc_profile(profile.to_vec());
+            }
+            image.write_with_encoder(encoder)?;
+        }
+        // The GIF encoder only accepts 8-bit buffers
+        ImageFormat::Gif => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Gif)?,
+        _ => image.write_to(&mut Cursor::new(&mut bytes), format)?,
+    }
+    Ok(bytes)
+}
+
+/// JPEG has no alpha, so the image is flattened to RGB first.
+fn encode_jpeg(
+    image: &DynamicImage,
+    quality: u8,
+    icc_profile: Option<&[u8]>,
+) -> Result<Vec<u8>, image::ImageError> {
+    let mut bytes = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100));
+    if let Some(profile) = icc_profile {
+        let _ = encoder.set_icc_profile(profile.to_vec());
+    }
+    image.to_rgb8().write_with_encoder(encoder)?;
+    Ok(bytes)
+}
+
+/// Encodes `image` as a `data:` URI, e.g. for pasting into HTML or CSS.
+pub fn data_uri(image: &DynamicImage, format: ImageFormat) -> Result<String, CropperError> {
+    let bytes = encode(image, format, None)?;
+    Ok(format!(
+        "data:{};base64,{}",
+        format.to_mime_type(),
+        base64_encode(&bytes)
+    ))
+}
+
+/// Standard, padded base64.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Bytes `image` takes encoded as `format`, JPEGs at `jpeg_quality`,
+/// without a colour profile.
+pub fn encoded_size(
+    image: &DynamicImage,
+    format: ImageFormat,
+    jpeg_quality: u8,
+) -> Result<usize, CropperError> {
+    let bytes = match format {
+        ImageFormat::Jpeg => encode_jpeg(image, jpeg_quality, None)?,
+        format => encode(image, format, None)?,
+    };
+    Ok(bytes.len())
+}
+
+/// Formats a byte count for display, e.g. `12.3 KB`.
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -0,0 +1,61 @@
+use crate::error::CropperError;
+use eframe::egui;
+use image::DynamicImage;
+use image::imageops::FilterType;
+use std::path::Path;
+
+/// Longest side the image is scaled down to before detection; larger
+/// photos only make the sliding window slower.
+const DETECT_SIDE: u32 = 1024;
+
+/// Smallest face, in pixels of the scaled image, the detector looks for.
+const MIN_FACE_PX: u32 = 20;
+
+/// Finds the largest face in `image` with the SeetaFace frontal model at
+/// `model` and returns its box as a normalized rect, or `None` when there
+/// is no face.
+pub fn detect_largest(
+    image: &DynamicImage,
+    model: &Path,
+) -> Result<Option<egui::Rect>, CropperError> {
+    let model = rustface::load_model(&model.to_string_lossy()).map_err(CropperError::FaceModel)?;
+    let mut detector = rustface::create_detector_with_model(model);
+    detector.set_min_face_size(MIN_FACE_PX);
+    detector.set_score_thresh(2.0);
+    detector.set_pyramid_scale_factor(0.8);
+    detector.set_slide_window_step(4, 4);
+
+    let gray = if image.width().max(image.height()) > DETECT_SIDE {
+        image.resize(DETECT_SIDE, DETECT_SIDE, FilterType::Triangle)
+    } else {
+        image.clone()
+    }
+    .to_luma8();
+    let (width, height) = gray.dimensions();
+    if width.min(height) < MIN_FACE_PX {
+        return Ok(None);
+    }
+
+    let faces = detector.detect(&rustface::ImageData::new(&gray, width, height));
+    let size = egui::vec2(width as f32, height as f32);
+    Ok(faces
+        .iter()
+        .map(|face| {
+            let bbox = face.bbox();
+            egui::Rect::from_min_size(
+                egui::pos2(bbox.x() as f32, bbox.y() as f32),
+                egui::vec2(bbox.width() as f32, bbox.height() as f32),
+            )
+        })
+        .max_by(|a, b| a.area().total_cmp(&b.area()))
+        .map(|face| {
+            let face = egui::Rect::from_min_max(
+                (face.min.to_vec2() / size).to_pos2(),
+                (face.max.to_vec2() / size).to_pos2(),
+            );
+            face.intersect(egui::Rect::from_min_max(
+                egui::Pos2::ZERO,
+                egui::pos2(1.0, 1.0),
+            ))
+        }))
+}
@@ -0,0 +1,103 @@
+use crate::error::CropperError;
+
+/// Placeholders accepted in output filename templates.
+pub const TOKENS: [&str; 5] = ["stem", "w", "h", "ratio", "ext"];
+
+/// Values substituted into a filename template.
+pub struct NameParts<'a> {
+    pub stem: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub ratio: &'a str,
+    pub extension: &'a str,
+}
+
+/// Expands a template such as `{stem}_{w}x{h}_{ratio}.{ext}`. Only the
+/// tokens in `TOKENS` are recognised; `{{` and `}}` produce literal braces.
+pub fn expand(template: &str, parts: &NameParts) -> Result<String, CropperError> {
+    let invalid = |message: String| CropperError::InvalidTemplate(message);
+    let mut name = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                name.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                name.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| invalid("unclosed '{'".to_owned()))?;
+                match &rest[..end] {
+                    "stem" => name.push_str(parts.stem),
+                    "w" => name.push_str(&parts.width.to_string()),
+                    "h" => name.push_str(&parts.height.to_string()),
+                    "ratio" => name.push_str(parts.ratio),
+                    "ext" => name.push_str(parts.extension),
+                    token => return Err(invalid(format!("unknown token {{{}}}", token))),
+                }
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err(invalid("unmatched '}'".to_owned())),
+            c if std::path::is_separator(c) => {
+                return Err(invalid("the name cannot contain folders".to_owned()));
+            }
+            c => name.push(c),
+        }
+    }
+    if name.trim().is_empty() {
+        return Err(invalid("the name is empty".to_owned()));
+    }
+    Ok(name)
+}
+
+/// `{ratio}` for a `width`×`height` output: the preset it is within
+/// `epsilon` of, e.g. `16x9`, else the ratio to two decimals.
+pub fn ratio_token(width: u32, height: u32, epsilon: f32) -> String {
+    let ratio = width as f32 / height as f32;
+    match crate::nearest_preset_ratio(ratio, epsilon) {
+        Some(mode) => mode.key().replace(':', "x"),
+        None => format!("{:.2}", ratio),
+    }
+}
+
+/// Expands `template` for a `width`×`height` output of the image `stem`.
+pub fn output_name(
+    template: &str,
+    stem: &str,
+    (width, height): (u32, u32),
+    extension: &str,
+    epsilon: f32,
+) -> Result<String, CropperError> {
+    let ratio = ratio_token(width, height, epsilon);
+    expand(
+        template,
+        &NameParts {
+            stem,
+            width,
+            height,
+            ratio: &ratio,
+            extension,
+        },
+    )
+}
+
+/// Checks `template` by expanding it with sample values.
+pub fn validate(template: &str) -> Result<(), CropperError> {
+    expand(
+        template,
+        &NameParts {
+            stem: "image",
+            width: 1,
+            height: 1,
+            ratio: "1x1",
+            extension: "png",
+        },
+    )
+    .map(|_| ())
+}
@@ -0,0 +1,62 @@
+use crate::AspectRatioMode;
+use eframe::egui;
+
+/// Number of crop edits kept for undo.
+pub const CROP_HISTORY: usize = 50;
+
+/// A crop as it was after one edit, with the ratio it was made under.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CropState {
+    pub rect: egui::Rect,
+    pub aspect_ratio: AspectRatioMode,
+    pub is_portrait: bool,
+}
+
+/// Undo/redo stack of crop edits. `index` points at the current state;
+/// states after it are the ones that can be redone.
+#[derive(Default)]
+pub struct CropHistory {
+    states: Vec<CropState>,
+    index: usize,
+}
+
+impl CropHistory {
+    /// Starts over with `state` as the only entry.
+    pub fn reset(&mut self, state: CropState) {
+        self.states = vec![state];
+        self.index = 0;
+    }
+
+    /// Records a finished edit, dropping any redo states. Repeats of the
+    /// current state are ignored.
+    pub fn record(&mut self, state: CropState) {
+        if self.states.get(self.index) == Some(&state) {
+            return;
+        }
+        self.states.truncate(self.index + 1);
+        self.states.push(state);
+        if self.states.len() > CROP_HISTORY {
+            self.states.remove(0);
+        }
+        self.index = self.states.len() - 1;
+    }
+
+    pub fn undo(&mut self) -> Option<CropState> {
+        self.index = self.index.checked_sub(1)?;
+        self.states.get(self.index).copied()
+    }
+
+    pub fn redo(&mut self) -> Option<CropState> {
+        let state = self.states.get(self.index + 1).copied()?;
+        self.index += 1;
+        Some(state)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.index > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.index + 1 < self.states.len()
+    }
+}
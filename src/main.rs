@@ -1,7 +1,36 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+mod batch;
+mod crop_data;
+mod crop_presets;
+mod error;
+mod export;
+#[cfg(feature = "face-detect")]
+mod face;
+mod filename;
+mod history;
+mod perspective;
+mod recent;
+mod redact;
+mod regions;
+mod session;
+mod settings;
+mod straighten;
+
+use crop_presets::CropPresets;
 use eframe::egui;
-use image::DynamicImage;
+use error::CropperError;
+use history::{CropHistory, CropState};
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder};
+use recent::RecentFiles;
+use session::Session;
+use settings::Settings;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum ResizeHandle {
@@ -16,8 +45,23 @@ enum ResizeHandle {
     Center, // Moving
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+impl ResizeHandle {
+    /// Cursor hinting which way the handle moves the crop.
+    fn cursor_icon(self, dragging: bool) -> egui::CursorIcon {
+        match self {
+            ResizeHandle::TopLeft | ResizeHandle::BottomRight => egui::CursorIcon::ResizeNwSe,
+            ResizeHandle::TopRight | ResizeHandle::BottomLeft => egui::CursorIcon::ResizeNeSw,
+            ResizeHandle::Left | ResizeHandle::Right => egui::CursorIcon::ResizeHorizontal,
+            ResizeHandle::Top | ResizeHandle::Bottom => egui::CursorIcon::ResizeVertical,
+            ResizeHandle::Center if dragging => egui::CursorIcon::Grabbing,
+            ResizeHandle::Center => egui::CursorIcon::Grab,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, serde::Serialize, serde::Deserialize)]
 enum AspectRatioMode {
+    #[default]
     Free,
     Original,
     Square,
@@ -26,21 +70,100 @@ enum AspectRatioMode {
     R4_3,
     R16_9,
     R16_10,
+    Golden, // φ:1, about 1.618:1
     // Portrait
     R2_3,
     R3_4,
     R9_16,
     R10_16,
+    GoldenPortrait,
+    // Print, in landscape and portrait
+    R7_5,
+    R5_7,
+    R10_8,
+    R8_10,
+    IsoA, // A-series paper, √2:1
+    IsoAPortrait,
     Custom,
+    FixedArea, // Holds the pixel area while the ratio follows the drag
 }
 
-impl Default for AspectRatioMode {
-    fn default() -> Self {
-        Self::Free
+impl AspectRatioMode {
+    const ALL: [AspectRatioMode; 21] = [
+        AspectRatioMode::Free,
+        AspectRatioMode::Original,
+        AspectRatioMode::Square,
+        AspectRatioMode::R3_2,
+        AspectRatioMode::R4_3,
+        AspectRatioMode::R16_9,
+        AspectRatioMode::R16_10,
+        AspectRatioMode::Golden,
+        AspectRatioMode::R2_3,
+        AspectRatioMode::R3_4,
+        AspectRatioMode::R9_16,
+        AspectRatioMode::R10_16,
+        AspectRatioMode::GoldenPortrait,
+        AspectRatioMode::R7_5,
+        AspectRatioMode::R5_7,
+        AspectRatioMode::R10_8,
+        AspectRatioMode::R8_10,
+        AspectRatioMode::IsoA,
+        AspectRatioMode::IsoAPortrait,
+        AspectRatioMode::Custom,
+        AspectRatioMode::FixedArea,
+    ];
+
+    /// Stable name used in crop files and output names, e.g. `16:9` or `fixed_area`.
+    fn key(&self) -> String {
+        self.to_string().to_lowercase().replace(' ', "_")
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+
+    fn is_portrait(&self) -> bool {
+        matches!(
+            self,
+            AspectRatioMode::R2_3
+                | AspectRatioMode::R3_4
+                | AspectRatioMode::R9_16
+                | AspectRatioMode::R10_16
+                | AspectRatioMode::GoldenPortrait
+                | AspectRatioMode::R5_7
+                | AspectRatioMode::R8_10
+                | AspectRatioMode::IsoAPortrait
+        )
+    }
+
+    /// The ratio of a fixed preset; `None` for modes that depend on the image
+    /// or on user input.
+    fn ratio(&self) -> Option<f32> {
+        match self {
+            AspectRatioMode::Square => Some(1.0),
+            AspectRatioMode::R3_2 => Some(3.0 / 2.0),
+            AspectRatioMode::R4_3 => Some(4.0 / 3.0),
+            AspectRatioMode::R16_9 => Some(16.0 / 9.0),
+            AspectRatioMode::R16_10 => Some(16.0 / 10.0),
+            AspectRatioMode::R2_3 => Some(2.0 / 3.0),
+            AspectRatioMode::R3_4 => Some(3.0 / 4.0),
+            AspectRatioMode::R9_16 => Some(9.0 / 16.0),
+            AspectRatioMode::R10_16 => Some(10.0 / 16.0),
+            AspectRatioMode::Golden => Some(GOLDEN_RATIO),
+            AspectRatioMode::GoldenPortrait => Some(1.0 / GOLDEN_RATIO),
+            AspectRatioMode::R7_5 => Some(7.0 / 5.0),
+            AspectRatioMode::R5_7 => Some(5.0 / 7.0),
+            AspectRatioMode::R10_8 => Some(10.0 / 8.0),
+            AspectRatioMode::R8_10 => Some(8.0 / 10.0),
+            AspectRatioMode::IsoA => Some(std::f32::consts::SQRT_2),
+            AspectRatioMode::IsoAPortrait => Some(std::f32::consts::FRAC_1_SQRT_2),
+            AspectRatioMode::Free
+            | AspectRatioMode::Original
+            | AspectRatioMode::Custom
+            | AspectRatioMode::FixedArea => None,
+        }
     }
-}
 
-impl AspectRatioMode {
     fn counterpart(&self) -> Self {
         match self {
             AspectRatioMode::R3_2 => AspectRatioMode::R2_3,
@@ -51,7 +174,313 @@ impl AspectRatioMode {
             AspectRatioMode::R3_4 => AspectRatioMode::R4_3,
             AspectRatioMode::R9_16 => AspectRatioMode::R16_9,
             AspectRatioMode::R10_16 => AspectRatioMode::R16_10,
-            _ => self.clone(),
+            AspectRatioMode::Golden => AspectRatioMode::GoldenPortrait,
+            AspectRatioMode::GoldenPortrait => AspectRatioMode::Golden,
+            AspectRatioMode::R7_5 => AspectRatioMode::R5_7,
+            AspectRatioMode::R5_7 => AspectRatioMode::R7_5,
+            AspectRatioMode::R10_8 => AspectRatioMode::R8_10,
+            AspectRatioMode::R8_10 => AspectRatioMode::R10_8,
+            AspectRatioMode::IsoA => AspectRatioMode::IsoAPortrait,
+            AspectRatioMode::IsoAPortrait => AspectRatioMode::IsoA,
+            _ => *self,
+        }
+    }
+}
+
+/// File extensions offered in the open dialog and picked up by folder batches.
+#[cfg(not(feature = "avif-decode"))]
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "bmp", "webp", "tiff", "tif", "gif"];
+#[cfg(feature = "avif-decode")]
+const IMAGE_EXTENSIONS: [&str; 9] = [
+    "png", "jpg", "jpeg", "bmp", "webp", "tiff", "tif", "gif", "avif",
+];
+
+/// Step, in image pixels, used by the on-screen +/- adjust buttons.
+const ADJUST_STEP_PX: f32 = 10.0;
+
+/// Number of recent crop rects offered for quick re-apply.
+const RECENT_CROPS: usize = 5;
+
+/// The golden ratio φ, (1 + √5) / 2.
+const GOLDEN_RATIO: f32 = 1.618_034;
+
+/// Smallest crop side, in image pixels, a resize shrinks to. The dragged
+/// edge stops there instead of crossing the opposite one.
+const MIN_CROP_PX: f32 = 8.0;
+
+/// Number of baked crops that can be stepped back through.
+const TRANSFORM_HISTORY: usize = 10;
+
+/// Zoom multiplier applied per zoom in/out step.
+const ZOOM_STEP: f32 = 1.25;
+const MAX_ZOOM: f32 = 32.0;
+
+/// Room left below a framed face, in face heights, for the neck and shoulders.
+const FACE_CHIN_ROOM: f32 = 1.0;
+
+/// How far past each image edge, in image sizes, the crop may go when
+/// "Allow crop outside image" is on.
+const OUTSIDE_MARGIN: f32 = 1.0;
+
+/// Dash and gap length, in screen points, of the marching-ants border.
+const MARCHING_ANTS_DASH: f32 = 4.0;
+/// How fast the marching ants crawl, in screen points per second.
+const MARCHING_ANTS_SPEED: f32 = 16.0;
+
+/// Longest side of the result preview thumbnail, in pixels.
+const PREVIEW_SIZE: u32 = 200;
+/// Least time between preview rebuilds while the crop is changing.
+const PREVIEW_INTERVAL_SECS: f64 = 0.15;
+
+/// Longest side of the texture the canvas draws, unless the GPU allows
+/// less. Bigger images are shown downscaled; cropping and saving still use
+/// every source pixel.
+const MAX_TEXTURE_SIDE: u32 = 4096;
+
+/// How long the crop must stay put before its file size is estimated.
+const ESTIMATE_DELAY_SECS: f64 = 0.4;
+
+/// Distance, in screen points, at which a dragged crop edge snaps to the
+/// image border.
+const SNAP_DISTANCE: f32 = 8.0;
+
+/// Pointer travel, in screen points, before a press starts moving the crop.
+const DRAG_THRESHOLD: f32 = 3.0;
+
+/// How long, in seconds, a press must be held still to open the canvas menu.
+const LONG_PRESS_SECS: f64 = 0.6;
+/// Pointer travel, in screen points, tolerated during a long press.
+const LONG_PRESS_SLOP: f32 = 6.0;
+
+/// Composition guides drawn inside the crop. They are only shown on screen,
+/// never saved into the output.
+#[derive(Clone, Copy, Debug, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+enum GridMode {
+    #[default]
+    None,
+    Thirds,
+    Golden,    // Lines at the golden section, about 0.382 and 0.618
+    Diagonals, // Corner to corner
+    Fine,      // Even 8×8 grid for checking lines are level
+}
+
+impl GridMode {
+    const ALL: [GridMode; 5] = [
+        GridMode::None,
+        GridMode::Thirds,
+        GridMode::Golden,
+        GridMode::Diagonals,
+        GridMode::Fine,
+    ];
+
+    /// The guide lines for a crop shown at `rect`, as screen segments.
+    fn lines(&self, rect: egui::Rect) -> Vec<[egui::Pos2; 2]> {
+        let stops: Vec<f32> = match self {
+            GridMode::None => return Vec::new(),
+            GridMode::Diagonals => {
+                return vec![
+                    [rect.left_top(), rect.right_bottom()],
+                    [rect.right_top(), rect.left_bottom()],
+                ];
+            }
+            GridMode::Thirds => vec![1.0 / 3.0, 2.0 / 3.0],
+            GridMode::Golden => vec![1.0 - 1.0 / GOLDEN_RATIO, 1.0 / GOLDEN_RATIO],
+            GridMode::Fine => (1..8).map(|i| i as f32 / 8.0).collect(),
+        };
+        stops
+            .into_iter()
+            .flat_map(|t| {
+                let x = egui::lerp(rect.x_range(), t);
+                let y = egui::lerp(rect.y_range(), t);
+                [
+                    [egui::pos2(x, rect.min.y), egui::pos2(x, rect.max.y)],
+                    [egui::pos2(rect.min.x, y), egui::pos2(rect.max.x, y)],
+                ]
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for GridMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            GridMode::None => "No Guides",
+            GridMode::Thirds => "Thirds",
+            GridMode::Golden => "Golden Section",
+            GridMode::Diagonals => "Diagonals",
+            GridMode::Fine => "Fine Grid",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// What the preview thumbnail was built from: the image texture, crop,
+/// perspective corners, tool, mask-out, round mask, output size and filter.
+type PreviewKey = (
+    egui::TextureId,
+    egui::Rect,
+    [egui::Pos2; 4],
+    Tool,
+    bool,
+    bool,
+    SizePreset,
+    (u32, u32),
+    ResizeFilter,
+);
+
+/// Everything the output file size depends on: the image texture, crop,
+/// perspective corners, tool, output size, filter, round mask, mask-out,
+/// format and JPEG quality.
+type EstimateKey = (
+    egui::TextureId,
+    egui::Rect,
+    [egui::Pos2; 4],
+    Tool,
+    SizePreset,
+    (u32, u32),
+    ResizeFilter,
+    bool,
+    bool,
+    image::ImageFormat,
+    u8,
+);
+
+/// Editing tool that drives how the canvas responds to drags.
+#[derive(Clone, Copy, Debug, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+enum Tool {
+    #[default]
+    Crop,
+    Perspective, // Four independently draggable corners, straightened on export
+}
+
+impl Tool {
+    const ALL: [Tool; 2] = [Tool::Crop, Tool::Perspective];
+
+    fn description(&self) -> &'static str {
+        match self {
+            Tool::Crop => "Drag the edges and corners of a rectangle",
+            Tool::Perspective => "Drag each corner independently and straighten on export",
+        }
+    }
+}
+
+impl std::fmt::Display for Tool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Tool::Crop => "✂ Crop",
+            Tool::Perspective => "⬚ Perspective",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Fixed output pixel size applied on export.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+enum SizePreset {
+    #[default]
+    Original,
+    Square(u32),
+    Favicon, // Multi-size .ico
+    Custom,  // Width and height typed in; 0 follows the crop's aspect
+}
+
+impl SizePreset {
+    const SQUARE_SIZES: [u32; 7] = [16, 32, 64, 128, 256, 512, 1024];
+}
+
+impl std::fmt::Display for SizePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizePreset::Original => write!(f, "Original"),
+            SizePreset::Square(size) => write!(f, "{}×{}", size, size),
+            SizePreset::Favicon => write!(f, "Favicon (.ico)"),
+            SizePreset::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+/// Resampling filter used when scaling to a custom output size.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+enum ResizeFilter {
+    Nearest,
+    Triangle,
+    #[default]
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    const ALL: [ResizeFilter; 3] = [
+        ResizeFilter::Nearest,
+        ResizeFilter::Triangle,
+        ResizeFilter::Lanczos3,
+    ];
+
+    fn filter_type(self) -> image::imageops::FilterType {
+        match self {
+            ResizeFilter::Nearest => image::imageops::FilterType::Nearest,
+            ResizeFilter::Triangle => image::imageops::FilterType::Triangle,
+            ResizeFilter::Lanczos3 => image::imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+impl std::fmt::Display for ResizeFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResizeFilter::Nearest => write!(f, "Nearest"),
+            ResizeFilter::Triangle => write!(f, "Bilinear"),
+            ResizeFilter::Lanczos3 => write!(f, "Lanczos"),
+        }
+    }
+}
+
+/// An image decoded off the UI thread, ready to be made the current one.
+struct LoadedImage {
+    image: DynamicImage,
+    icc_profile: Option<Vec<u8>>,
+    auto_rotated: Option<Orientation>,
+    path: Option<PathBuf>,
+    folder_images: Vec<PathBuf>, // Listing of the file's folder, for ◀ and ▶
+    keep_crop: Option<egui::Rect>, // Crop to carry over instead of a fresh one
+}
+
+/// The ratio in use when the app last saved, kept in eframe's storage so
+/// the next launch can start with it.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct LastRatio {
+    mode: AspectRatioMode,
+    custom_w: u32,
+    custom_h: u32,
+    is_portrait: bool,
+}
+
+impl LastRatio {
+    const STORAGE_KEY: &str = "last_ratio";
+
+    /// The ratio to start with: the stored one when the settings ask to
+    /// remember it, else the default ratio. The stored custom size is kept
+    /// either way.
+    fn at_startup(stored: Option<Self>, settings: &Settings) -> Self {
+        let (custom_w, custom_h) =
+            stored.map_or((4, 3), |last| (last.custom_w.max(1), last.custom_h.max(1)));
+        match stored {
+            Some(last) if settings.remember_ratio => Self {
+                custom_w,
+                custom_h,
+                ..last
+            },
+            _ => {
+                let mode = settings.default_aspect_ratio;
+                Self {
+                    mode,
+                    custom_w,
+                    custom_h,
+                    is_portrait: match mode {
+                        AspectRatioMode::Custom => custom_h > custom_w,
+                        mode => mode.is_portrait(),
+                    },
+                }
+            }
         }
     }
 }
@@ -62,841 +491,8134 @@ struct ImageCropper {
     texture: Option<egui::TextureHandle>,
     crop_rect: Option<egui::Rect>, // Normalized coordinates (0.0-1.0)
     selected_handle: Option<ResizeHandle>,
+    draw_start: Option<(egui::Pos2, egui::Rect)>, // Anchor of a crop being drawn, and the crop it replaces
+    unsnapped_crop: Option<egui::Rect>, // Where the dragged crop would be without edge snapping
     aspect_ratio_mode: AspectRatioMode,
+    last_constrained: AspectRatioMode, // Restored when toggling back from Free
     custom_w: u32,
     custom_h: u32,
+    target_megapixels: f32,
     is_portrait: bool,
+    aspect_lock: Option<f32>, // Pixel width / height captured by "Aspect lock", overriding the mode
+    smallest_export: Option<mpsc::Receiver<Result<export::SmallestExport, CropperError>>>,
+    export_status: Option<String>,
+    error: Option<CropperError>, // Shown in a dialog until dismissed
+    size_preset: SizePreset,
+    output_size: (u32, u32), // Custom output width and height; 0 keeps the crop's value or aspect
+    resize_filter: ResizeFilter,
+    image_path: Option<PathBuf>,
+    icc_profile: Option<Vec<u8>>, // Embedded colour profile of the source image
+    crop_template: Option<egui::Rect>, // Normalized crop reused across a folder
+    regions: Vec<regions::Region>, // Named crops for multi-crop export
+    batch_job: Option<mpsc::Receiver<Result<batch::BatchSummary, CropperError>>>,
+    batch_status: Option<(String, String)>, // Summary and failure details
+    batch_progress: Option<(Arc<AtomicUsize>, usize)>, // Files done and total of a picked-files batch
+    zoom: f32,                                         // 1.0 fits the whole image in the canvas
+    actual_pixels: bool,
+    save_format: export::SaveFormat, // Encoder for Save Cropped Image, or by extension
+    size_estimate: Option<(EstimateKey, Option<usize>)>, // Encoded size of the output, if it could be encoded
+    estimate_pending: Option<(EstimateKey, f64)>, // Settings waiting to be estimated, and since when
+    estimate_job: Option<(EstimateKey, mpsc::Receiver<Result<usize, CropperError>>)>,
+    preview: Option<(PreviewKey, f64, egui::TextureHandle)>, // Thumbnail of the result, what it shows and when it was made
+    // Hold the zoom at one image pixel per point as the canvas resizes
+    view_center: egui::Pos2, // Normalized image point shown at the canvas center
+    fit_scale: f32,          // Screen points per image pixel at zoom 1.0, as of the last frame
+    image_screen_rect: Option<egui::Rect>, // Where the image was drawn, as of the last frame
+    tool: Tool,
+    settings: Settings,
+    show_settings: bool,
+    quad: [egui::Pos2; 4], // Normalized corners: TL, TR, BR, BL
+    selected_corner: Option<usize>,
+    recent_crops: std::collections::VecDeque<egui::Rect>, // Newest first, cleared per image
+    canvas_menu: Option<egui::Pos2>, // Screen position of the open canvas context menu
+    press_moved: bool,               // Current canvas press has moved too far to be a long press
+    drag_engaged: bool,              // Current canvas press has passed the drag threshold
+    closing: bool,                   // The window is closing, so the session is cleared
+    path_input: String,              // Path typed or pasted into the Load field
+    folder_images: Vec<PathBuf>,     // Sorted images next to the open one, for ◀ and ▶
+    v_key_seen: bool,                // A V press or paste event came since V was last released
+    transform_history: Vec<(DynamicImage, egui::Rect, bool)>, // Images, crops and `redacted` before each Crop
+    crop_history: CropHistory,                                // Undo/redo of crop edits
+    panning: bool, // Current canvas drag moves the view, not the crop
+    recent_files: RecentFiles,
+    crop_presets: CropPresets, // Named crops kept across sessions
+    preset_name: String,       // Name to save the current crop under
+    circular: bool,            // Mask the output to the ellipse inscribed in the crop
+    mask_out: bool, // Save the whole image with the crop cleared instead of just the crop
+    redact_strength: u32, // Blur sigma or pixelation block size, in pixels
+    redacted: bool, // Part of the image was blurred or pixelated, so Save keeps all of it
+    crop_locked: bool, // Ignore drags and nudges on the crop, leaving only pan and zoom
+    straighten_deg: f32, // Angle `straighten_base` is turned by to give `image`
+    straighten_base: Option<DynamicImage>, // The image before straightening, while an angle is set
+    auto_rotated: Option<Orientation>, // EXIF orientation applied to the open image
+    loading: Option<(String, mpsc::Receiver<Result<LoadedImage, CropperError>>)>, // Name and result of an image being decoded
 }
 
 impl ImageCropper {
-    fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self {
-            custom_w: 4,
-            custom_h: 3,
-            is_portrait: false,
+    /// Sets up the app, opening `startup_path` if one was given on the
+    /// command line, else the crashed session or a path on the clipboard.
+    fn new(cc: &eframe::CreationContext<'_>, startup_path: Option<PathBuf>) -> Self {
+        let settings: Settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, Settings::STORAGE_KEY))
+            .unwrap_or_default();
+        cc.egui_ctx.set_theme(settings.theme);
+        let last_ratio = LastRatio::at_startup(
+            cc.storage
+                .and_then(|storage| eframe::get_value(storage, LastRatio::STORAGE_KEY)),
+            &settings,
+        );
+        let mut cropper = Self {
+            tool: settings.default_tool,
+            aspect_ratio_mode: last_ratio.mode,
+            is_portrait: last_ratio.is_portrait,
+            last_constrained: AspectRatioMode::Original,
+            settings,
+            custom_w: last_ratio.custom_w,
+            custom_h: last_ratio.custom_h,
+            target_megapixels: 2.0,
+            redact_strength: 12,
+            zoom: 1.0,
+            view_center: egui::pos2(0.5, 0.5),
+            recent_files: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, RecentFiles::STORAGE_KEY))
+                .unwrap_or_default(),
+            crop_presets: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, CropPresets::STORAGE_KEY))
+                .unwrap_or_default(),
             ..Default::default()
+        };
+        // A file named on the command line comes first (a bad one leaves the
+        // app empty), then a session left behind by a run that did not exit
+        // cleanly, then an image path on the clipboard
+        if let Some(path) = startup_path {
+            if path.is_file() {
+                cropper.start_open(&cc.egui_ctx, path);
+            } else {
+                cropper.error = Some(CropperError::NotAFile(path));
+            }
+        } else if let Some(session) = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<Option<Session>>(storage, Session::STORAGE_KEY))
+            .flatten()
+            && session.has_usable_crop()
+            && session.image_path.exists()
+        {
+            cropper.restore_session(&cc.egui_ctx, session);
+        } else if cropper.settings.open_clipboard_path
+            && let Some(path) = arboard::Clipboard::new()
+                .and_then(|mut clipboard| clipboard.get_text())
+                .ok()
+                .and_then(|text| image_path_from_text(&text))
+            && let Err(e) = cropper.open_image(&cc.egui_ctx, &path)
+        {
+            cropper.error = Some(e);
         }
+        cropper
     }
 
-    fn load_texture(&mut self, ctx: &egui::Context) {
-        if let Some(image) = &self.image {
-            let size = [image.width() as _, image.height() as _];
-            let image_buffer = image.to_rgba8();
-            let pixels = image_buffer.as_flat_samples();
-            let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-            self.texture =
-                Some(ctx.load_texture("image", color_image, egui::TextureOptions::LINEAR));
-            // Initialize crop rect to full image
-            self.crop_rect = Some(egui::Rect::from_min_max(
-                egui::Pos2::new(0.0, 0.0),
-                egui::Pos2::new(1.0, 1.0),
-            ));
+    fn restore_session(&mut self, ctx: &egui::Context, session: Session) {
+        if let Err(e) = self.open_image(ctx, &session.image_path) {
+            self.error = Some(e);
+            return;
         }
+        self.aspect_ratio_mode = session.aspect_ratio;
+        self.is_portrait = session.is_portrait;
+        self.crop_rect = Some(session.crop_rect);
+        self.export_status = Some("Restored the crop from the last session".to_owned());
     }
 
-    fn apply_aspect_ratio(&mut self) {
-        if let (Some(image), Some(crop_rect)) = (&self.image, &mut self.crop_rect) {
-            let image_size = egui::vec2(image.width() as f32, image.height() as f32);
-            let target_ratio = match self.aspect_ratio_mode {
-                AspectRatioMode::Free => None,
-                AspectRatioMode::Original => Some(image_size.x / image_size.y),
-                AspectRatioMode::Square => Some(1.0),
-                AspectRatioMode::R3_2 => Some(3.0 / 2.0),
-                AspectRatioMode::R4_3 => Some(4.0 / 3.0),
-                AspectRatioMode::R16_9 => Some(16.0 / 9.0),
-                AspectRatioMode::R16_10 => Some(16.0 / 10.0),
-                AspectRatioMode::R2_3 => Some(2.0 / 3.0),
-                AspectRatioMode::R3_4 => Some(3.0 / 4.0),
-                AspectRatioMode::R9_16 => Some(9.0 / 16.0),
-                AspectRatioMode::R10_16 => Some(10.0 / 16.0),
-                AspectRatioMode::Custom => Some(self.custom_w as f32 / self.custom_h as f32),
-            };
+    /// The image and crop to store for crash recovery: none when auto-save
+    /// is off, nothing is open, or the window is closing cleanly.
+    fn session(&self) -> Option<Session> {
+        if self.settings.autosave_secs == 0 || self.closing {
+            return None;
+        }
+        Some(Session {
+            image_path: self.image_path.clone()?,
+            crop_rect: self.crop_rect?,
+            aspect_ratio: self.aspect_ratio_mode,
+            is_portrait: self.is_portrait,
+        })
+    }
 
-            if let Some(ratio) = target_ratio {
-                // Calculate normalized target aspect ratio
-                let norm_aspect = ratio * (image_size.y / image_size.x);
-                let current_center = crop_rect.center();
-                let current_w = crop_rect.width();
-                let current_h = crop_rect.height();
+    /// Decodes the image at `path` along with its embedded ICC profile, if
+    /// any, and its EXIF orientation. The orientation is not applied.
+    fn load_image(
+        path: &Path,
+    ) -> Result<(DynamicImage, Option<Vec<u8>>, Orientation), CropperError> {
+        let load = || -> image::ImageResult<_> {
+            let mut decoder = image::ImageReader::open(path)?
+                .with_guessed_format()?
+                .into_decoder()?;
+            // A broken profile or EXIF block is not worth failing the load over
+            let icc_profile = decoder.icc_profile().ok().flatten();
+            let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+            Ok((
+                DynamicImage::from_decoder(decoder)?,
+                icc_profile,
+                orientation,
+            ))
+        };
+        load().map_err(CropperError::Load)
+    }
 
-                // Preserve major dimension logic
-                let max_dim = current_w.max(current_h);
+    /// Decodes the image at `path`, turned upright if `auto_orient` is set.
+    fn decode_file(path: &Path, auto_orient: bool) -> Result<LoadedImage, CropperError> {
+        let (mut image, icc_profile, orientation) = Self::load_image(path)?;
+        let auto_rotated =
+            (auto_orient && orientation != Orientation::NoTransforms).then_some(orientation);
+        if let Some(orientation) = auto_rotated {
+            image.apply_orientation(orientation);
+        }
+        Ok(LoadedImage {
+            image,
+            icc_profile,
+            auto_rotated,
+            path: Some(path.to_path_buf()),
+            folder_images: Self::list_folder(path),
+            keep_crop: None,
+        })
+    }
 
-                let (mut new_w, mut new_h) = if norm_aspect >= 1.0 {
-                    (max_dim, max_dim / norm_aspect)
-                } else {
-                    (max_dim * norm_aspect, max_dim)
-                };
+    /// Asks for an image file and opens it.
+    fn open_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Image", &IMAGE_EXTENSIONS)
+            .pick_file()
+        {
+            self.start_open(ctx, path);
+        }
+    }
 
-                // Fit to bounds if necessary
-                if new_w > 1.0 {
-                    new_w = 1.0;
-                    new_h = new_w / norm_aspect;
-                }
-                if new_h > 1.0 {
-                    new_h = 1.0;
-                    new_w = new_h * norm_aspect;
-                }
+    /// Loads the image at `path` and resets the crop to the full frame.
+    /// Photos stored sideways are turned upright unless that is turned off.
+    fn open_image(&mut self, ctx: &egui::Context, path: &Path) -> Result<(), CropperError> {
+        let loaded = Self::decode_file(path, self.settings.auto_orient)?;
+        self.finish_loading(ctx, loaded);
+        Ok(())
+    }
 
-                *crop_rect = egui::Rect::from_center_size(current_center, egui::vec2(new_w, new_h));
+    /// Like `open_image`, but decodes on a worker thread so a huge file
+    /// doesn't freeze the window; `poll_loading` picks up the result.
+    fn start_open(&mut self, ctx: &egui::Context, path: PathBuf) {
+        self.start_open_with_crop(ctx, path, None);
+    }
 
-                // Ensure it stays within 0.0-1.0 bounds logic
-                if crop_rect.min.x < 0.0 {
-                    *crop_rect = crop_rect.translate(egui::vec2(-crop_rect.min.x, 0.0));
-                }
-                if crop_rect.min.y < 0.0 {
-                    *crop_rect = crop_rect.translate(egui::vec2(0.0, -crop_rect.min.y));
+    /// Like `start_open`, but gives the new image `keep_crop` once loaded.
+    fn start_open_with_crop(
+        &mut self,
+        ctx: &egui::Context,
+        path: PathBuf,
+        keep_crop: Option<egui::Rect>,
+    ) {
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let auto_orient = self.settings.auto_orient;
+        self.start_loading(ctx, name, move || {
+            let mut loaded = Self::decode_file(&path, auto_orient)?;
+            loaded.keep_crop = keep_crop;
+            Ok(loaded)
+        });
+    }
+
+    /// Runs `decode` on a worker thread. A load already in progress is
+    /// abandoned, so the last image asked for is the one that opens.
+    fn start_loading(
+        &mut self,
+        ctx: &egui::Context,
+        name: String,
+        decode: impl FnOnce() -> Result<LoadedImage, CropperError> + Send + 'static,
+    ) {
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(decode());
+            ctx.request_repaint();
+        });
+        self.loading = Some((name, rx));
+    }
+
+    fn poll_loading(&mut self, ctx: &egui::Context) {
+        let Some((_, rx)) = &self.loading else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(result) => {
+                self.loading = None;
+                match result {
+                    Ok(loaded) => self.finish_loading(ctx, loaded),
+                    Err(e) => self.error = Some(e),
                 }
-                if crop_rect.max.x > 1.0 {
-                    *crop_rect = crop_rect.translate(egui::vec2(1.0 - crop_rect.max.x, 0.0));
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.loading = None,
+        }
+    }
+
+    /// Makes a decoded image the current one; the texture upload has to
+    /// happen here on the UI thread.
+    fn finish_loading(&mut self, ctx: &egui::Context, loaded: LoadedImage) {
+        self.set_image(ctx, loaded.image, loaded.icc_profile, loaded.path);
+        self.auto_rotated = loaded.auto_rotated;
+        self.folder_images = loaded.folder_images;
+        if let Some(crop_rect) = loaded.keep_crop {
+            self.crop_rect = Some(crop_rect);
+            // Keeps the ratio exact if it depends on the image (e.g. Original)
+            self.apply_aspect_ratio();
+        }
+    }
+
+    /// Opens the image on the clipboard, e.g. a screenshot. It has no file,
+    /// so saving suggests a generic name. Only the clipboard read happens
+    /// here; converting the pixels is left to a worker thread.
+    fn paste_image(&mut self, ctx: &egui::Context) -> Result<(), CropperError> {
+        let data = arboard::Clipboard::new()?
+            .get_image()
+            .map_err(|e| match e {
+                arboard::Error::ContentNotAvailable => CropperError::NoClipboardImage,
+                e => e.into(),
+            })?;
+        self.start_loading(ctx, "clipboard image".to_owned(), move || {
+            let image = image_from_clipboard(data).ok_or(CropperError::NoClipboardImage)?;
+            Ok(LoadedImage {
+                image,
+                icc_profile: None,
+                auto_rotated: None,
+                path: None,
+                folder_images: Vec::new(),
+                keep_crop: None,
+            })
+        });
+        Ok(())
+    }
+
+    /// Whether `events` hold the paste shortcut. egui turns the press into a
+    /// text paste event, or into nothing at all when the clipboard only
+    /// holds pixels, so a Ctrl/Cmd+V release without a V press before it
+    /// counts too. Letting go of Ctrl/Cmd before V loses that paste.
+    /// `v_key_seen` carries the state between frames.
+    fn paste_shortcut(events: &[egui::Event], v_key_seen: &mut bool) -> bool {
+        let mut paste = false;
+        for event in events {
+            match event {
+                egui::Event::Paste(_) => {
+                    paste = true;
+                    *v_key_seen = true;
                 }
-                if crop_rect.max.y > 1.0 {
-                    *crop_rect = crop_rect.translate(egui::vec2(0.0, 1.0 - crop_rect.max.y));
+                egui::Event::Key {
+                    key: egui::Key::V,
+                    pressed: true,
+                    ..
+                } => *v_key_seen = true,
+                egui::Event::Key {
+                    key: egui::Key::V,
+                    pressed: false,
+                    modifiers,
+                    ..
+                } => {
+                    paste |= !*v_key_seen && modifiers.command;
+                    *v_key_seen = false;
                 }
-
-                // Hard clamp if still out (e.g. too big)
-                crop_rect.min = crop_rect
-                    .min
-                    .clamp(egui::Pos2::ZERO, egui::Pos2::new(1.0, 1.0));
-                crop_rect.max = crop_rect
-                    .max
-                    .clamp(egui::Pos2::ZERO, egui::Pos2::new(1.0, 1.0));
+                _ => {}
             }
         }
+        paste
     }
 
-    fn hit_test(pos: egui::Pos2, rect: egui::Rect) -> Option<ResizeHandle> {
-        let tolerance = 10.0;
+    /// Makes `image` the one being cropped, starting a fresh crop unless the
+    /// last one is carried over.
+    fn set_image(
+        &mut self,
+        ctx: &egui::Context,
+        image: DynamicImage,
+        icc_profile: Option<Vec<u8>>,
+        path: Option<PathBuf>,
+    ) {
+        let previous_crop = self.crop_rect.filter(|_| self.settings.apply_last_crop);
+        self.transform_history.clear();
+        self.redacted = false;
+        self.auto_rotated = None;
+        self.straighten_base = None;
+        self.straighten_deg = 0.0;
+        self.image = Some(image);
+        self.icc_profile = icc_profile;
+        if let Some(path) = &path {
+            self.recent_files.push(path.clone());
+        }
+        self.image_path = path;
+        self.load_texture(ctx);
+        if let Some(previous) = previous_crop {
+            self.carry_over_crop(previous);
+        }
+        self.apply_aspect_ratio();
+        self.selected_handle = None;
+    }
 
-        let min = rect.min;
-        let max = rect.max;
+    /// Rebuilds the result thumbnail when the crop changed, at most every
+    /// `PREVIEW_INTERVAL_SECS` so a drag doesn't upload a texture per frame.
+    fn update_preview(&mut self, ctx: &egui::Context) {
+        let (Some(texture), Some(crop_rect)) = (&self.texture, self.crop_rect) else {
+            self.preview = None;
+            return;
+        };
+        let key = (
+            texture.id(),
+            crop_rect,
+            self.quad,
+            self.tool,
+            self.mask_out,
+            self.circular,
+            self.size_preset,
+            self.output_size,
+            self.resize_filter,
+        );
+        let now = ctx.input(|i| i.time);
+        if let Some((shown, made, _)) = &self.preview {
+            if *shown == key {
+                return;
+            }
+            let wait = PREVIEW_INTERVAL_SECS - (now - made);
+            if wait > 0.0 {
+                ctx.request_repaint_after_secs(wait as f32);
+                return;
+            }
+        }
+        // Built from what Save writes, so a round or resized output shows as such
+        self.preview = self.output_image().ok().map(|output| {
+            let thumbnail = output.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE).to_rgba8();
+            let color_image = egui::ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width() as _, thumbnail.height() as _],
+                thumbnail.as_flat_samples().as_slice(),
+            );
+            let texture = ctx.load_texture("preview", color_image, egui::TextureOptions::LINEAR);
+            (key, now, texture)
+        });
+    }
 
-        if pos.distance(min) < tolerance {
-            return Some(ResizeHandle::TopLeft);
+    fn load_texture(&mut self, ctx: &egui::Context) {
+        if let Some(image) = &self.image {
+            let max_side = MAX_TEXTURE_SIDE.min(ctx.input(|i| i.max_texture_side) as u32);
+            let image_buffer = if image.width().max(image.height()) > max_side {
+                image.thumbnail(max_side, max_side).to_rgba8()
+            } else {
+                image.to_rgba8()
+            };
+            let size = [image_buffer.width() as _, image_buffer.height() as _];
+            let pixels = image_buffer.as_flat_samples();
+            let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+            self.texture =
+                Some(ctx.load_texture("image", color_image, egui::TextureOptions::LINEAR));
+            // Initialize crop rect to full image
+            self.crop_rect = Some(egui::Rect::from_min_max(
+                egui::Pos2::new(0.0, 0.0),
+                egui::Pos2::new(1.0, 1.0),
+            ));
+            self.quad = Self::rect_corners(egui::Rect::from_min_max(
+                egui::Pos2::ZERO,
+                egui::pos2(1.0, 1.0),
+            ));
+            self.zoom = 1.0;
+            self.view_center = egui::pos2(0.5, 0.5);
+            self.recent_crops.clear();
+            self.recent_crops.extend(self.crop_rect);
+            if let Some(state) = self.crop_state() {
+                self.crop_history.reset(state);
+            }
         }
-        if pos.distance(egui::pos2(max.x, min.y)) < tolerance {
-            return Some(ResizeHandle::TopRight);
+    }
+
+    /// Replaces the working image with the current crop so it can be cropped
+    /// again; the previous image is kept in the transform history.
+    fn bake_crop(&mut self, ctx: &egui::Context) -> Result<(), CropperError> {
+        let cropped = self.cropped_image()?;
+        if let (Some(image), Some(crop_rect)) = (self.image.take(), self.crop_rect) {
+            self.push_transform(image, crop_rect);
         }
-        if pos.distance(egui::pos2(min.x, max.y)) < tolerance {
-            return Some(ResizeHandle::BottomLeft);
+        // What is left is the crop, so Save goes back to keeping just that
+        self.redacted = false;
+        self.image = Some(cropped);
+        self.load_texture(ctx);
+        Ok(())
+    }
+
+    /// Turns the working image a quarter turn and resets the crop to it. The
+    /// ratio turns with the image, so a 3:2 crop of a sideways photo becomes
+    /// 2:3 once it is upright.
+    fn rotate_image(&mut self, ctx: &egui::Context, clockwise: bool) {
+        let Some(image) = self.image.take() else {
+            return;
+        };
+        let rotated = if clockwise {
+            image.rotate90()
+        } else {
+            image.rotate270()
+        };
+        if let Some(crop_rect) = self.crop_rect {
+            self.push_transform(image, crop_rect);
         }
-        if pos.distance(max) < tolerance {
-            return Some(ResizeHandle::BottomRight);
+        self.image = Some(rotated);
+        self.load_texture(ctx);
+        self.swap_ratio_orientation();
+        self.apply_aspect_ratio();
+    }
+
+    /// Mirrors the image, and the crop with it so it stays over the same
+    /// content. The ratio is unaffected since the dimensions don't change.
+    fn flip_image(&mut self, ctx: &egui::Context, horizontal: bool) {
+        let (Some(image), Some(crop_rect)) = (self.image.take(), self.crop_rect) else {
+            return;
+        };
+        let flipped = if horizontal {
+            image.fliph()
+        } else {
+            image.flipv()
+        };
+        self.push_transform(image, crop_rect);
+        self.image = Some(flipped);
+        self.load_texture(ctx);
+        self.crop_rect = Some(mirror_rect(crop_rect, horizontal));
+        if let Some(state) = self.crop_state() {
+            self.crop_history.reset(state);
         }
+    }
 
-        if (pos.x - min.x).abs() < tolerance && pos.y > min.y && pos.y < max.y {
-            return Some(ResizeHandle::Left);
+    /// Blurs or pixelates the pixels under the crop in the working image,
+    /// keeping the crop where it is so the effect can be stacked. From then
+    /// on Save writes the whole image; Revert undoes the effect.
+    fn redact_crop(&mut self, ctx: &egui::Context, pixelate: bool) {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return;
+        };
+        let region = crop_pixel_bounds(image.width(), image.height(), crop_rect);
+        let mut redacted = image.clone();
+        let strength = self.redact_strength.max(1);
+        if pixelate {
+            redact::pixelate(&mut redacted, region, strength);
+        } else {
+            redact::blur(&mut redacted, region, strength as f32);
+        }
+        if let Some(image) = self.image.replace(redacted) {
+            self.push_transform(image, crop_rect);
         }
-        if (pos.x - max.x).abs() < tolerance && pos.y > min.y && pos.y < max.y {
-            return Some(ResizeHandle::Right);
+        self.redacted = true;
+        self.load_texture(ctx);
+        self.crop_rect = Some(crop_rect);
+        if let Some(state) = self.crop_state() {
+            self.crop_history.reset(state);
         }
-        if (pos.y - min.y).abs() < tolerance && pos.x > min.x && pos.x < max.x {
-            return Some(ResizeHandle::Top);
+    }
+
+    /// Turns the image by `straighten_deg` from its unstraightened state and
+    /// fits the crop inside the turned image so no blank corner is included.
+    fn apply_straighten(&mut self, ctx: &egui::Context) {
+        let Some(base) = self.straighten_base.take().or_else(|| self.image.take()) else {
+            return;
+        };
+        let (width, height) = (base.width(), base.height());
+        if self.straighten_deg == 0.0 {
+            self.image = Some(base);
+        } else {
+            self.image = Some(straighten::rotate(&base, self.straighten_deg));
+            self.straighten_base = Some(base);
         }
-        if (pos.y - max.y).abs() < tolerance && pos.x > min.x && pos.x < max.x {
-            return Some(ResizeHandle::Bottom);
+        self.load_texture(ctx);
+        self.crop_rect = Some(straighten::inscribed_crop(
+            width,
+            height,
+            self.straighten_deg,
+            self.target_ratio(),
+        ));
+        if let Some(state) = self.crop_state() {
+            self.crop_history.reset(state);
         }
+    }
+
+    /// Whether 🔄 has anything to turn: Original and 1:1 look the same
+    /// either way round.
+    fn can_swap_orientation(&self) -> bool {
+        !matches!(
+            self.aspect_ratio_mode,
+            AspectRatioMode::Original | AspectRatioMode::Square
+        )
+    }
 
-        if rect.contains(pos) {
-            return Some(ResizeHandle::Center);
+    /// Flips the ratio between landscape and portrait, e.g. 4:3 ↔ 3:4.
+    fn swap_ratio_orientation(&mut self) {
+        self.is_portrait = !self.is_portrait;
+        self.aspect_lock = self.aspect_lock.map(f32::recip);
+        if self.aspect_ratio_mode == AspectRatioMode::Custom {
+            std::mem::swap(&mut self.custom_w, &mut self.custom_h);
+        } else {
+            self.aspect_ratio_mode = self.aspect_ratio_mode.counterpart();
         }
+    }
 
-        None
+    /// The 🔄 button: flips the preset, or without one turns the crop itself.
+    /// An aspect lock is turned with it rather than released.
+    fn flip_orientation(&mut self) {
+        match self.aspect_ratio_mode {
+            AspectRatioMode::Original | AspectRatioMode::Square => return,
+            AspectRatioMode::Free | AspectRatioMode::FixedArea => self.swap_crop_shape(),
+            _ => self.swap_ratio_orientation(),
+        }
+        self.apply_aspect_ratio();
     }
-}
 
-impl std::fmt::Display for AspectRatioMode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            AspectRatioMode::Free => "Free",
-            AspectRatioMode::Original => "Original",
-            AspectRatioMode::Square => "1:1",
-            AspectRatioMode::R3_2 => "3:2",
-            AspectRatioMode::R4_3 => "4:3",
-            AspectRatioMode::R16_9 => "16:9",
-            AspectRatioMode::R16_10 => "16:10",
-            AspectRatioMode::R2_3 => "2:3",
-            AspectRatioMode::R3_4 => "3:4",
-            AspectRatioMode::R9_16 => "9:16",
-            AspectRatioMode::R10_16 => "10:16",
-            AspectRatioMode::Custom => "Custom",
+    /// Swaps the crop's pixel width and height around its center, shrinking
+    /// it if the turned crop would not fit the image.
+    fn swap_crop_shape(&mut self) {
+        let (Some(image), Some(rect)) = (&self.image, self.crop_rect) else {
+            return;
         };
-        write!(f, "{}", s)
+        let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+        let size = rect.size() * image_size;
+        let turned = egui::vec2(size.y, size.x);
+        let scale = (image_size.x / turned.x)
+            .min(image_size.y / turned.y)
+            .min(1.0);
+        let half = turned * scale / image_size / 2.0;
+        let center = egui::pos2(
+            rect.center().x.clamp(half.x, 1.0 - half.x),
+            rect.center().y.clamp(half.y, 1.0 - half.y),
+        );
+        self.crop_rect = Some(egui::Rect::from_center_size(center, half * 2.0));
+        self.is_portrait = turned.y > turned.x;
+        self.aspect_lock = self.aspect_lock.map(f32::recip);
     }
-}
 
-impl eframe::App for ImageCropper {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn push_transform(&mut self, image: DynamicImage, crop_rect: egui::Rect) {
+        // The straightened image becomes the new starting point
+        self.straighten_base = None;
+        self.straighten_deg = 0.0;
+        if self.transform_history.len() == TRANSFORM_HISTORY {
+            self.transform_history.remove(0);
+        }
+        self.transform_history
+            .push((image, crop_rect, self.redacted));
+    }
+
+    /// Steps back to the image and crop from before the last transform.
+    fn undo_transform(&mut self, ctx: &egui::Context) {
+        if let Some((image, crop_rect, redacted)) = self.transform_history.pop() {
+            self.image = Some(image);
+            self.redacted = redacted;
+            self.load_texture(ctx);
+            self.crop_rect = Some(crop_rect);
+        }
+    }
+
+    /// Reuses a normalized crop from the previous image, clamped to this one.
+    fn carry_over_crop(&mut self, previous: egui::Rect) {
+        let clamped = previous.intersect(egui::Rect::from_min_max(
+            egui::Pos2::ZERO,
+            egui::pos2(1.0, 1.0),
+        ));
+        if clamped.is_positive() {
+            self.crop_rect = Some(clamped);
+            Self::push_recent_crop(&mut self.recent_crops, clamped);
+        }
+    }
+
+    /// Records `rect` as the most recent crop, dropping duplicates and the oldest.
+    fn push_recent_crop(recent: &mut std::collections::VecDeque<egui::Rect>, rect: egui::Rect) {
+        recent.retain(|r| *r != rect);
+        recent.push_front(rect);
+        recent.truncate(RECENT_CROPS);
+    }
+
+    fn set_zoom(&mut self, zoom: f32) {
+        self.actual_pixels = false;
+        self.zoom = zoom.clamp(1.0, MAX_ZOOM);
+        self.view_center = Self::clamp_view_center(self.view_center, self.zoom);
+    }
+
+    /// Zooms while keeping the normalized image point `anchor` at the same
+    /// place on screen, so the view zooms toward the pointer.
+    /// Returns the new zoom and view center.
+    fn zoom_at(
+        zoom: f32,
+        view_center: egui::Pos2,
+        new_zoom: f32,
+        anchor: egui::Pos2,
+    ) -> (f32, egui::Pos2) {
+        let new_zoom = new_zoom.clamp(1.0, MAX_ZOOM);
+        let center = anchor - (anchor - view_center) * (zoom / new_zoom);
+        (new_zoom, Self::clamp_view_center(center, new_zoom))
+    }
+
+    /// Scrolls the view by a screen-space delta, as if dragging the image.
+    fn pan_view(
+        view_center: egui::Pos2,
+        zoom: f32,
+        delta: egui::Vec2,
+        display_size: egui::Vec2,
+    ) -> egui::Pos2 {
+        Self::clamp_view_center(view_center - delta / display_size, zoom)
+    }
+
+    /// Zooms and pans so the crop's pixel bounds fill the canvas.
+    fn zoom_to_crop(&mut self) {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return;
+        };
+        let (zoom, center) = crop_view(image.width(), image.height(), crop_rect);
+        self.view_center = center;
+        self.set_zoom(zoom);
+    }
+
+    /// Zooms so one image pixel covers one screen point, and keeps it so
+    /// until another zoom is picked. This is the zoomed canvas view, not an
+    /// `egui::ScrollArea`: it scrolls with the wheel, middle or Space+drag
+    /// and the minimap, and the crop keeps a single screen mapping.
+    fn zoom_actual_size(&mut self) {
+        if self.fit_scale > 0.0 {
+            self.set_zoom(1.0 / self.fit_scale);
+            self.actual_pixels = true;
+        }
+    }
+
+    /// Canvas scale and zoom that show one image pixel per screen point for
+    /// an image that fits the canvas at `fit_scale`. An image smaller than
+    /// the canvas is shown on a smaller canvas rather than enlarged.
+    fn actual_size_view(fit_scale: f32) -> (f32, f32) {
+        let scale = fit_scale.min(1.0);
+        (scale, (1.0 / fit_scale).clamp(1.0, MAX_ZOOM))
+    }
+
+    /// Keeps the visible region inside the image at the given zoom.
+    fn clamp_view_center(center: egui::Pos2, zoom: f32) -> egui::Pos2 {
+        let half = 0.5 / zoom;
+        egui::pos2(
+            center.x.clamp(half, 1.0 - half),
+            center.y.clamp(half, 1.0 - half),
+        )
+    }
+
+    /// Bounds of the minimap in the bottom-right corner of `canvas_rect`.
+    fn minimap_rect(canvas_rect: egui::Rect, image_size: egui::Vec2) -> egui::Rect {
+        const MINIMAP_SIZE: f32 = 150.0;
+        const MARGIN: f32 = 8.0;
+        let scale = (MINIMAP_SIZE / image_size.x).min(MINIMAP_SIZE / image_size.y);
+        let size = image_size * scale;
+        egui::Rect::from_min_size(canvas_rect.max - size - egui::vec2(MARGIN, MARGIN), size)
+    }
+
+    /// Small white-on-dark label whose `anchor` corner sits at `pos`.
+    fn paint_badge(painter: &egui::Painter, pos: egui::Pos2, anchor: egui::Align2, text: String) {
+        let galley =
+            painter.layout_no_wrap(text, egui::FontId::proportional(12.0), egui::Color32::WHITE);
+        let badge = anchor.anchor_size(pos, galley.size() + egui::vec2(12.0, 6.0));
+        painter.rect_filled(badge, 4.0, egui::Color32::from_black_alpha(160));
+        painter.galley(
+            badge.min + egui::vec2(6.0, 3.0),
+            galley,
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// Draws the whole image into `minimap_rect` with the visible region and
+    /// the (normalized) crop rect marked.
+    fn paint_minimap(
+        painter: &egui::Painter,
+        texture_id: egui::TextureId,
+        minimap_rect: egui::Rect,
+        visible: egui::Rect,
+        crop_rect: egui::Rect,
+    ) {
+        let to_minimap = |rect: egui::Rect| {
+            egui::Rect::from_min_max(
+                minimap_rect.lerp_inside(rect.min.to_vec2()),
+                minimap_rect.lerp_inside(rect.max.to_vec2()),
+            )
+        };
+
+        painter.rect_filled(minimap_rect.expand(2.0), 2.0, egui::Color32::BLACK);
+        painter.image(
+            texture_id,
+            minimap_rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
+        painter.rect_stroke(
+            to_minimap(crop_rect),
+            0.0,
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+        );
+        painter.rect_stroke(
+            to_minimap(visible),
+            0.0,
+            egui::Stroke::new(1.5, egui::Color32::YELLOW),
+        );
+    }
+
+    fn rect_corners(rect: egui::Rect) -> [egui::Pos2; 4] {
+        [
+            rect.left_top(),
+            rect.right_top(),
+            rect.right_bottom(),
+            rect.left_bottom(),
+        ]
+    }
+
+    /// Returns the crop region of the source image in full resolution.
+    fn cropped_image(&self) -> Result<DynamicImage, CropperError> {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return Err(CropperError::NoImage);
+        };
+        let w = image.width() as f32;
+        let h = image.height() as f32;
+
+        if self.tool == Tool::Perspective {
+            return perspective::rectify(image, self.quad.map(|p| (p.x * w, p.y * h)))
+                .ok_or(CropperError::DegenerateQuad);
+        }
+
+        Ok(compute_crop(image, crop_rect))
+    }
+
+    /// What Save keeps before resizing: the crop, or with mask-out the whole
+    /// image with the crop (or its ellipse) cleared, e.g. to redact it. A
+    /// blurred or pixelated image is kept whole.
+    fn kept_image(&self) -> Result<DynamicImage, CropperError> {
+        match (&self.image, self.crop_rect) {
+            (Some(image), Some(crop_rect)) if self.mask_out && self.tool == Tool::Crop => {
+                Ok(clear_region(image, crop_rect, self.circular))
+            }
+            (Some(image), Some(_)) if self.redacted && self.tool == Tool::Crop => Ok(image.clone()),
+            _ => self.cropped_image(),
+        }
+    }
+
+    /// The original next to the crop, for before/after documentation.
+    fn comparison_image(&self) -> Result<DynamicImage, CropperError> {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return Err(CropperError::NoImage);
+        };
+        let cropped = self.cropped_image()?;
+        let (w, h) = (image.width() as f32, image.height() as f32);
+        let outline = self.settings.comparison_outline.then(|| match self.tool {
+            Tool::Perspective => self.quad.map(|p| (p.x * w, p.y * h)),
+            Tool::Crop => {
+                let (x, y, cw, ch) = crop_pixel_bounds(image.width(), image.height(), crop_rect);
+                let rect = egui::Rect::from_min_size(
+                    egui::pos2(x as f32, y as f32),
+                    // Trace the last pixel row/column rather than just past it
+                    egui::vec2(cw as f32 - 1.0, ch as f32 - 1.0),
+                );
+                Self::rect_corners(rect).map(|p| (p.x, p.y))
+            }
+        });
+        let [r, g, b] = self.settings.comparison_background;
+        Ok(export::compose_side_by_side(
+            image,
+            &cropped,
+            outline,
+            self.settings.comparison_gutter,
+            image::Rgba([r, g, b, 255]),
+        ))
+    }
+
+    fn start_template_batch(&mut self, ctx: &egui::Context) {
+        let (Some(template), Some(folder)) = (
+            self.crop_template,
+            self.image_path.as_ref().and_then(|p| p.parent()),
+        ) else {
+            return;
+        };
+        let folder = folder.to_path_buf();
+        let settings = self.settings.clone();
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(batch::apply_template(&folder, template, &settings));
+            ctx.request_repaint();
+        });
+        self.batch_job = Some(rx);
+        self.batch_status = None;
+        self.batch_progress = None;
+    }
+
+    /// Crops files picked in a dialog at the current crop, saving them into
+    /// a chosen folder. Sizes may differ since the crop is normalized.
+    fn start_files_batch(&mut self, ctx: &egui::Context) {
+        let Some(template) = self.crop_rect else {
+            return;
+        };
+        let Some(paths) = rfd::FileDialog::new()
+            .add_filter("Images", &IMAGE_EXTENSIONS)
+            .set_title("Images to crop")
+            .pick_files()
+        else {
+            return;
+        };
+        let Some(out_dir) = rfd::FileDialog::new()
+            .set_title("Output folder")
+            .pick_folder()
+        else {
+            return;
+        };
+        let total = paths.len();
+        let progress = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        let counter = progress.clone();
+        let settings = self.settings.clone();
+        std::thread::spawn(move || {
+            let summary = batch::crop_files(&paths, &out_dir, template, &settings, &counter);
+            let _ = tx.send(Ok(summary));
+            ctx.request_repaint();
+        });
+        self.batch_job = Some(rx);
+        self.batch_status = None;
+        self.batch_progress = Some((progress, total));
+    }
+
+    fn poll_template_batch(&mut self) {
+        let Some(rx) = &self.batch_job else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok(summary)) if self.batch_progress.take().is_some() => {
+                self.batch_status = Some((
+                    format!(
+                        "Batch: {} saved, {} failed",
+                        summary.saved,
+                        summary.failed.len()
+                    ),
+                    String::new(),
+                ));
+                if !summary.failed.is_empty() {
+                    self.error = Some(CropperError::BatchFailed(summary.failed));
+                }
+                self.batch_job = None;
+            }
+            Ok(Ok(summary)) => {
+                let details = summary
+                    .failed
+                    .iter()
+                    .map(|(path, e)| format!("{}: {}", path.display(), e))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.batch_status = Some((
+                    format!(
+                        "Template applied: {} saved, {} failed",
+                        summary.saved,
+                        summary.failed.len()
+                    ),
+                    details,
+                ));
+                self.batch_job = None;
+            }
+            Ok(Err(e)) => {
+                self.error = Some(e);
+                self.batch_job = None;
+                self.batch_progress = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.batch_job = None;
+                self.batch_progress = None;
+            }
+        }
+    }
+
+    /// Returns the crop resized to the selected output size preset.
+    fn output_image(&self) -> Result<DynamicImage, CropperError> {
+        let cropped = self.kept_image()?;
+        Ok(match self.size_preset {
+            SizePreset::Square(size) => {
+                cropped.resize_exact(size, size, image::imageops::FilterType::Lanczos3)
+            }
+            SizePreset::Custom => {
+                let size = (cropped.width(), cropped.height());
+                match scaled_size(size, self.output_size) {
+                    (w, h) if (w, h) == size => cropped,
+                    (w, h) => cropped.resize_exact(w, h, self.resize_filter.filter_type()),
+                }
+            }
+            SizePreset::Original | SizePreset::Favicon => cropped,
+        })
+        .map(|output| {
+            if self.circular && !self.mask_out && !self.redacted && self.tool == Tool::Crop {
+                mask_ellipse(&output)
+            } else {
+                output
+            }
+        })
+    }
+
+    /// Encodes `output` to `path` as Save does: AVIF at its own quality,
+    /// other formats at the JPEG quality with the source's colour profile.
+    fn write_output(&self, output: &DynamicImage, path: &Path) -> Result<(), CropperError> {
+        if export::is_avif(path) {
+            export::save_avif(output, path, self.settings.avif_quality)
+        } else {
+            export::save_image_with_profile(
+                output,
+                path,
+                self.export_icc_profile(),
+                self.settings.jpeg_quality,
+            )
+        }
+    }
+
+    /// Where to write `output` in `folder`: the filename template expanded
+    /// with `stem`, numbered if `used` already holds that name.
+    fn template_path(
+        &self,
+        folder: &Path,
+        stem: &str,
+        output: &DynamicImage,
+        extension: &str,
+        used: &mut HashSet<OsString>,
+    ) -> Result<PathBuf, CropperError> {
+        let name = filename::output_name(
+            &self.settings.filename_template,
+            stem,
+            (output.width(), output.height()),
+            extension,
+            self.settings.ratio_epsilon,
+        )?;
+        Ok(folder.join(batch::unique_name(Path::new(&name), used)))
+    }
+
+    /// The open file's name without its extension; `image` when pasted.
+    fn image_stem(&self) -> String {
+        self.image_path
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .map_or("image".to_owned(), |s| s.to_string_lossy().into_owned())
+    }
+
+    /// Saves every region into `folder` in the format Save would pick,
+    /// masked round if ◯ is on.
+    fn export_regions(&mut self, folder: &Path) -> Result<(), CropperError> {
+        let image = self.image.as_ref().ok_or(CropperError::NoImage)?;
+        let extension = self.estimate_format().extensions_str()[0];
+        let mut used = HashSet::new();
+        let count =
+            regions::export_all(image, &self.regions, &self.image_stem(), |stem, cropped| {
+                let output = if self.circular {
+                    mask_ellipse(&cropped)
+                } else {
+                    cropped
+                };
+                let path = self.template_path(folder, stem, &output, extension, &mut used)?;
+                self.write_output(&output, &path)
+            })?;
+        self.export_status = Some(format!("Saved {} regions", count));
+        Ok(())
+    }
+
+    /// Saves the largest crop of each Multi-ratio preset around the crop
+    /// center into `folder` as PNG.
+    fn export_ratio_crops(&mut self, folder: &Path) -> Result<(), CropperError> {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return Err(CropperError::NoImage);
+        };
+        let mut used = HashSet::new();
+        let count = export::export_ratios(
+            image,
+            crop_rect.center(),
+            &self.settings.multi_ratios,
+            &self.image_stem(),
+            |stem, cropped| {
+                let path = self.template_path(folder, stem, &cropped, "png", &mut used)?;
+                self.write_output(&cropped, &path)
+            },
+        )?;
+        self.export_status = Some(format!("Saved {} ratio crops", count));
+        Ok(())
+    }
+
+    fn save_cropped(&mut self, ctx: &egui::Context) {
+        let result = if self.size_preset == SizePreset::Favicon {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("Icon", &["ico"])
+                .save_file()
+            else {
+                return;
+            };
+            self.check_not_source(&path)
+                .and_then(|()| self.cropped_image())
+                .and_then(|cropped| export::save_ico(&cropped, &export::FAVICON_SIZES, &path))
+        } else {
+            let mut dialog = rfd::FileDialog::new();
+            if self.save_format != export::SaveFormat::Auto {
+                dialog =
+                    dialog.add_filter(self.save_format.to_string(), self.save_format.extensions());
+            }
+            dialog = dialog
+                .add_filter("Image", &IMAGE_EXTENSIONS)
+                .add_filter("AVIF", &["avif"]);
+            if let Some(folder) = self.image_path.as_ref().and_then(|p| p.parent()) {
+                dialog = dialog.set_directory(folder);
+            }
+            match self.suggested_file_name() {
+                Ok(Some(name)) => dialog = dialog.set_file_name(name),
+                Ok(None) => {}
+                Err(e) => self.error = Some(e),
+            }
+            let Some(path) = dialog.save_file() else {
+                return;
+            };
+            // A name typed without an extension would leave no encoder to use
+            let path = self.save_format.apply_to(&path);
+            if self.circular && !self.mask_out && export::drops_alpha(&path) {
+                self.export_status = Some(
+                    "This format has no transparency; save as PNG for a round crop".to_owned(),
+                );
+            }
+            self.check_not_source(&path)
+                .and_then(|()| self.output_image())
+                .and_then(|output| self.write_output(&output, &path))
+        };
+
+        match result {
+            Ok(()) => {
+                self.refresh_folder_images();
+                if self.settings.auto_advance {
+                    self.advance_keeping_crop(ctx);
+                }
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Output name from the filename template for the current crop, with
+    /// the chosen format's extension or else the source image's.
+    fn suggested_file_name(&self) -> Result<Option<String>, CropperError> {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return Ok(None);
+        };
+        let (mut width, mut height) = if (self.mask_out || self.redacted) && self.tool == Tool::Crop
+        {
+            (image.width(), image.height())
+        } else {
+            let (_, _, width, height) = crop_pixel_bounds(image.width(), image.height(), crop_rect);
+            (width, height)
+        };
+        match self.size_preset {
+            SizePreset::Square(size) => (width, height) = (size, size),
+            SizePreset::Custom => (width, height) = scaled_size((width, height), self.output_size),
+            SizePreset::Original | SizePreset::Favicon => {}
+        }
+        let path = self.image_path.as_deref();
+        let stem = path
+            .and_then(|p| p.file_stem())
+            .map_or("image".into(), |s| s.to_string_lossy());
+        // A round crop needs transparency, which PNG always has
+        let extension = match self.save_format.extensions().first() {
+            Some(extension) => extension.to_string(),
+            None => path
+                .and_then(|p| p.extension())
+                .filter(|_| !self.circular)
+                .map_or("png".to_owned(), |e| e.to_string_lossy().to_lowercase()),
+        };
+        filename::output_name(
+            &self.settings.filename_template,
+            &stem,
+            (width, height),
+            &extension,
+            self.settings.ratio_epsilon,
+        )
+        .map(Some)
+    }
+
+    /// Fails if "Protect source" is on and `path` is the open image's file.
+    fn check_not_source(&self, path: &Path) -> Result<(), CropperError> {
+        let Some(source) = self
+            .image_path
+            .as_deref()
+            .filter(|_| self.settings.protect_source)
+        else {
+            return Ok(());
+        };
+        // The target may not exist yet, in which case it cannot be the source
+        let same = match (path.canonicalize(), source.canonicalize()) {
+            (Ok(target), Ok(source)) => target == source,
+            _ => path == source,
+        };
+        if same {
+            Err(CropperError::OverwriteSource(path.to_path_buf()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The source profile to embed in exports, if preserving is enabled.
+    fn export_icc_profile(&self) -> Option<&[u8]> {
+        self.icc_profile
+            .as_deref()
+            .filter(|_| self.settings.preserve_icc_profile)
+    }
+
+    /// The images in `path`'s folder, sorted; empty if it can't be read.
+    fn list_folder(path: &Path) -> Vec<PathBuf> {
+        path.parent()
+            .and_then(|folder| batch::folder_images(folder).ok())
+            .unwrap_or_default()
+    }
+
+    /// Lists the current image's folder again, after a save may have added
+    /// a file to it.
+    fn refresh_folder_images(&mut self) {
+        self.folder_images = self
+            .image_path
+            .as_deref()
+            .map(Self::list_folder)
+            .unwrap_or_default();
+    }
+
+    /// Path of the image `offset` places away from the current one in its
+    /// folder, from the listing taken when it was loaded.
+    fn sibling_path(&self, offset: isize) -> Option<PathBuf> {
+        let path = self.image_path.as_ref()?;
+        let index = self.folder_images.iter().position(|p| p == path)?;
+        self.folder_images
+            .get(index.checked_add_signed(offset)?)
+            .cloned()
+    }
+
+    fn open_sibling(&mut self, ctx: &egui::Context, offset: isize) {
+        if let Some(path) = self.sibling_path(offset) {
+            self.start_open(ctx, path);
+        }
+    }
+
+    /// Opens the next image in the folder with the same crop, for processing
+    /// a sequence of similar images. Decoding happens on the worker thread.
+    fn advance_keeping_crop(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.sibling_path(1) else {
+            self.export_status = Some("Saved; that was the last image in the folder".to_owned());
+            return;
+        };
+        self.start_open_with_crop(ctx, path, self.crop_rect);
+    }
+
+    /// The format Save would write: the chosen one, else the source's.
+    fn estimate_format(&self) -> image::ImageFormat {
+        self.save_format.image_format().unwrap_or_else(|| {
+            self.image_path
+                .as_deref()
+                .filter(|_| !self.circular)
+                .and_then(|path| image::ImageFormat::from_path(path).ok())
+                .unwrap_or(image::ImageFormat::Png)
+        })
+    }
+
+    fn estimate_key(&self) -> Option<EstimateKey> {
+        Some((
+            self.texture.as_ref()?.id(),
+            self.crop_rect?,
+            self.quad,
+            self.tool,
+            self.size_preset,
+            self.output_size,
+            self.resize_filter,
+            self.circular,
+            self.mask_out,
+            self.estimate_format(),
+            self.settings.jpeg_quality,
+        ))
+    }
+
+    /// Keeps the output size estimate current. Encoding runs on a worker
+    /// thread, once the crop and format have held still for
+    /// `ESTIMATE_DELAY_SECS`, so dragging and the quality slider stay smooth.
+    fn update_size_estimate(&mut self, ctx: &egui::Context) {
+        if let Some((key, rx)) = &self.estimate_job {
+            match rx.try_recv() {
+                Ok(result) => {
+                    self.size_estimate = Some((*key, result.ok()));
+                    self.estimate_job = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => self.estimate_job = None,
+            }
+        }
+        let Some(key) = self.estimate_key() else {
+            self.size_estimate = None;
+            return;
+        };
+        if self
+            .size_estimate
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == key)
+        {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        match self.estimate_pending {
+            Some((pending, since)) if pending == key => {
+                let wait = ESTIMATE_DELAY_SECS - (now - since);
+                if wait > 0.0 {
+                    ctx.request_repaint_after_secs(wait as f32);
+                    return;
+                }
+            }
+            _ => {
+                self.estimate_pending = Some((key, now));
+                ctx.request_repaint_after_secs(ESTIMATE_DELAY_SECS as f32);
+                return;
+            }
+        }
+        self.estimate_pending = None;
+        let Ok(output) = self.output_image() else {
+            self.size_estimate = Some((key, None));
+            return;
+        };
+        let (format, quality) = (key.9, key.10);
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(export::encoded_size(&output, format, quality));
+            ctx.request_repaint();
+        });
+        self.estimate_job = Some((key, rx));
+    }
+
+    /// E.g. "≈ 412 KB as JPG q90", from the last finished estimate.
+    fn size_estimate_label(&self) -> Option<String> {
+        let (key, Some(size)) = self.size_estimate.as_ref()? else {
+            return None;
+        };
+        let (format, quality) = (key.9, key.10);
+        let mut label = format!(
+            "≈ {} as {}",
+            export::format_size(*size),
+            format.extensions_str()[0].to_uppercase()
+        );
+        if format == image::ImageFormat::Jpeg {
+            label.push_str(&format!(" q{}", quality));
+        }
+        Some(label)
+    }
+
+    /// Encodes the crop to every candidate format on a worker thread and
+    /// writes whichever is smallest next to `path`.
+    fn start_smallest_export(&mut self, ctx: &egui::Context, path: std::path::PathBuf) {
+        // The winning format replaces the extension, so any of them could hit the source
+        let source_extension = self.image_path.as_ref().and_then(|p| p.extension());
+        let checked = match source_extension {
+            Some(extension) => self.check_not_source(&path.with_extension(extension)),
+            None => Ok(()),
+        };
+        let cropped = match checked.and_then(|()| self.output_image()) {
+            Ok(cropped) => cropped,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        let icc_profile = self.export_icc_profile().map(<[u8]>::to_vec);
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(export::save_smallest(
+                &cropped,
+                &path,
+                icc_profile.as_deref(),
+            ));
+            ctx.request_repaint();
+        });
+        self.smallest_export = Some(rx);
+        self.export_status = Some("Finding smallest format...".to_owned());
+    }
+
+    fn poll_smallest_export(&mut self) {
+        let Some(rx) = &self.smallest_export else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok(result)) => {
+                self.export_status = Some(format!(
+                    "Saved {} as {} ({})",
+                    result
+                        .path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    result.format.extensions_str()[0].to_uppercase(),
+                    export::format_size(result.size),
+                ));
+                self.smallest_export = None;
+            }
+            Ok(Err(e)) => {
+                self.error = Some(e);
+                self.export_status = None;
+                self.smallest_export = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.smallest_export = None,
+        }
+    }
+
+    /// Width / height ratio the crop is locked to, `None` for free cropping.
+    fn target_ratio(&self) -> Option<f32> {
+        // Straightening grows the canvas; "Original" still means the photo's shape
+        let image = self.straighten_base.as_ref().or(self.image.as_ref())?;
+        if self.aspect_lock.is_some() {
+            return self.aspect_lock;
+        }
+        match self.aspect_ratio_mode {
+            AspectRatioMode::Free => None,
+            AspectRatioMode::Original => Some(image.width() as f32 / image.height() as f32),
+            AspectRatioMode::Custom => Some(custom_ratio(self.custom_w, self.custom_h)),
+            mode => mode.ratio(),
+        }
+    }
+
+    /// Switches between Free and the last constrained ratio used.
+    fn toggle_free_ratio(&mut self) {
+        if self.aspect_ratio_mode != AspectRatioMode::Free {
+            self.last_constrained = self.aspect_ratio_mode;
+            self.aspect_ratio_mode = AspectRatioMode::Free;
+            return;
+        }
+        // Never constrained yet: the image's own ratio is the natural lock
+        self.aspect_ratio_mode = match self.last_constrained {
+            AspectRatioMode::Free => AspectRatioMode::Original,
+            mode => mode,
+        };
+        if self.aspect_ratio_mode != AspectRatioMode::Custom {
+            self.is_portrait = self.aspect_ratio_mode.is_portrait();
+        }
+        self.apply_aspect_ratio();
+    }
+
+    /// Normalized area (fraction of the image) held in fixed-area mode.
+    fn fixed_area(&self) -> Option<f32> {
+        let image = self.image.as_ref()?;
+        (self.aspect_ratio_mode == AspectRatioMode::FixedArea && self.aspect_lock.is_none()).then(
+            || {
+                let image_pixels = image.width() as f32 * image.height() as f32;
+                (self.target_megapixels * 1_000_000.0 / image_pixels).clamp(f32::EPSILON, 1.0)
+            },
+        )
+    }
+
+    fn apply_aspect_ratio(&mut self) {
+        if let Some(area) = self.fixed_area() {
+            if let Some(crop_rect) = &mut self.crop_rect {
+                *crop_rect = Self::fit_area(*crop_rect, area);
+            }
+            self.record_crop();
+            return;
+        }
+
+        let target_ratio = self.target_ratio();
+        if let (Some(image), Some(crop_rect)) = (&self.image, &mut self.crop_rect) {
+            let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+
+            if let Some(ratio) = target_ratio {
+                // Calculate normalized target aspect ratio
+                let norm_aspect = ratio * (image_size.y / image_size.x);
+                let current_center = crop_rect.center();
+                let current_w = crop_rect.width();
+                let current_h = crop_rect.height();
+
+                // Preserve major dimension logic
+                let max_dim = current_w.max(current_h);
+
+                let (mut new_w, mut new_h) = if norm_aspect >= 1.0 {
+                    (max_dim, max_dim / norm_aspect)
+                } else {
+                    (max_dim * norm_aspect, max_dim)
+                };
+
+                // Fit to bounds if necessary
+                if new_w > 1.0 {
+                    new_w = 1.0;
+                    new_h = new_w / norm_aspect;
+                }
+                if new_h > 1.0 {
+                    new_h = 1.0;
+                    new_w = new_h * norm_aspect;
+                }
+
+                *crop_rect = egui::Rect::from_center_size(current_center, egui::vec2(new_w, new_h));
+
+                // Ensure it stays within 0.0-1.0 bounds logic
+                if crop_rect.min.x < 0.0 {
+                    *crop_rect = crop_rect.translate(egui::vec2(-crop_rect.min.x, 0.0));
+                }
+                if crop_rect.min.y < 0.0 {
+                    *crop_rect = crop_rect.translate(egui::vec2(0.0, -crop_rect.min.y));
+                }
+                if crop_rect.max.x > 1.0 {
+                    *crop_rect = crop_rect.translate(egui::vec2(1.0 - crop_rect.max.x, 0.0));
+                }
+                if crop_rect.max.y > 1.0 {
+                    *crop_rect = crop_rect.translate(egui::vec2(0.0, 1.0 - crop_rect.max.y));
+                }
+
+                // Hard clamp if still out (e.g. too big)
+                crop_rect.min = crop_rect
+                    .min
+                    .clamp(egui::Pos2::ZERO, egui::Pos2::new(1.0, 1.0));
+                crop_rect.max = crop_rect
+                    .max
+                    .clamp(egui::Pos2::ZERO, egui::Pos2::new(1.0, 1.0));
+            }
+        }
+        self.record_crop();
+    }
+
+    /// Covers the window with a hint while a file is dragged over it.
+    fn paint_drop_hint(ctx: &egui::Context) {
+        if ctx.input(|i| i.raw.hovered_files.is_empty()) {
+            return;
+        }
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("drop_hint"),
+        ));
+        let screen = ctx.screen_rect();
+        painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(160));
+        painter.text(
+            screen.center(),
+            egui::Align2::CENTER_CENTER,
+            "Drop image to open",
+            egui::FontId::proportional(24.0),
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// File, full image size and zoom for the status bar.
+    fn status_line(&self) -> String {
+        let Some(image) = &self.image else {
+            return "No image".to_owned();
+        };
+        let source = self
+            .image_path
+            .as_ref()
+            .map_or("Pasted image".to_owned(), |path| path.display().to_string());
+        let mut line = format!(
+            "{}  ·  {} × {} px  ·  {:.0}%",
+            source,
+            image.width(),
+            image.height(),
+            self.zoom * 100.0
+        );
+        if self.auto_rotated.is_some() {
+            line.push_str("  ·  rotated upright (EXIF)");
+        }
+        if self.redacted {
+            line.push_str("  ·  redacted, saving the whole image");
+        }
+        line
+    }
+
+    /// Shows the last error in a centered dialog until OK is pressed. The
+    /// rest of the window stays usable so a failed save can be retried.
+    fn show_error_dialog(&mut self, ctx: &egui::Context) {
+        let Some(error) = &self.error else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::Window::new("Error")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", error));
+                ui.vertical_centered(|ui| {
+                    dismissed =
+                        ui.button("OK").clicked() || ui.input(|i| i.key_pressed(egui::Key::Enter));
+                });
+            });
+        if dismissed {
+            self.error = None;
+        }
+    }
+
+    fn crop_state(&self) -> Option<CropState> {
+        Some(CropState {
+            rect: self.crop_rect?,
+            aspect_ratio: self.aspect_ratio_mode,
+            is_portrait: self.is_portrait,
+        })
+    }
+
+    /// Adds the current crop to the undo history if it changed.
+    fn record_crop(&mut self) {
+        if let Some(state) = self.crop_state() {
+            self.crop_history.record(state);
+        }
+    }
+
+    fn restore_crop(&mut self, state: CropState) {
+        self.crop_rect = Some(state.rect);
+        self.aspect_ratio_mode = state.aspect_ratio;
+        self.is_portrait = state.is_portrait;
+    }
+
+    fn undo_crop(&mut self) {
+        if let Some(state) = self.crop_history.undo() {
+            self.restore_crop(state);
+        }
+    }
+
+    fn redo_crop(&mut self) {
+        if let Some(state) = self.crop_history.redo() {
+            self.restore_crop(state);
+        }
+    }
+
+    fn hit_test(pos: egui::Pos2, rect: egui::Rect) -> Option<ResizeHandle> {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+
+        let tolerance = 10.0;
+
+        if !rect.expand(tolerance).contains(pos) {
+            return None;
+        }
+
+        // Classify each axis separately: near the min edge, near the max edge or
+        // in between. Grab zones reach `tolerance` outside the rect but at most a
+        // third of the way in, so on very thin crops (e.g. panoramas) the nearest
+        // edge wins instead of the first one tested, and the middle still moves.
+        let zone = |p: f32, min: f32, max: f32| {
+            let inner = tolerance.min((max - min) / 3.0);
+            if p < min + inner {
+                Less
+            } else if p > max - inner {
+                Greater
+            } else {
+                Equal
+            }
+        };
+
+        let handle = match (
+            zone(pos.x, rect.min.x, rect.max.x),
+            zone(pos.y, rect.min.y, rect.max.y),
+        ) {
+            (Less, Less) => ResizeHandle::TopLeft,
+            (Greater, Less) => ResizeHandle::TopRight,
+            (Less, Greater) => ResizeHandle::BottomLeft,
+            (Greater, Greater) => ResizeHandle::BottomRight,
+            (Less, Equal) => ResizeHandle::Left,
+            (Greater, Equal) => ResizeHandle::Right,
+            (Equal, Less) => ResizeHandle::Top,
+            (Equal, Greater) => ResizeHandle::Bottom,
+            (Equal, Equal) => ResizeHandle::Center,
+        };
+        Some(handle)
+    }
+
+    /// Moves `handle` by one adjust step in `direction` (unit vector in image
+    /// space), as if it had been dragged.
+    fn step_crop(&mut self, handle: ResizeHandle, direction: egui::Vec2) {
+        self.move_crop(handle, direction * ADJUST_STEP_PX);
+    }
+
+    /// Moves `handle` by `delta_px` image pixels, clamped like a drag.
+    fn move_crop(&mut self, handle: ResizeHandle, delta_px: egui::Vec2) {
+        let target_ratio = self.target_ratio();
+        let fixed_area = self.fixed_area();
+        let bounds = self.crop_bounds();
+        if let (Some(image), Some(crop_rect)) = (&self.image, &mut self.crop_rect) {
+            let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+            let delta_norm = delta_px / image_size;
+            Self::drag_crop_within(
+                crop_rect,
+                handle,
+                delta_norm,
+                image_size,
+                target_ratio,
+                fixed_area,
+                bounds,
+            );
+        }
+        self.record_crop();
+    }
+
+    /// Normalized region the crop is kept inside: the image, or a margin
+    /// around it when cropping outside the image is allowed.
+    fn crop_bounds(&self) -> egui::Rect {
+        let image = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        if self.settings.allow_outside_crop {
+            image.expand(OUTSIDE_MARGIN)
+        } else {
+            image
+        }
+    }
+
+    /// Maps normalized image coordinates into `bounds`-relative ones, where
+    /// `bounds` spans 0.0-1.0.
+    fn to_bounds(bounds: egui::Rect, rect: egui::Rect) -> egui::Rect {
+        egui::Rect::from_min_max(
+            ((rect.min - bounds.min) / bounds.size()).to_pos2(),
+            ((rect.max - bounds.min) / bounds.size()).to_pos2(),
+        )
+    }
+
+    fn from_bounds(bounds: egui::Rect, rect: egui::Rect) -> egui::Rect {
+        egui::Rect::from_min_max(
+            bounds.min + rect.min.to_vec2() * bounds.size(),
+            bounds.min + rect.max.to_vec2() * bounds.size(),
+        )
+    }
+
+    /// `drag_crop` or `drag_crop_fixed_area`, clamped to `bounds` rather than
+    /// the image. The drag math works in a 0.0-1.0 space, so it runs in
+    /// `bounds`-relative coordinates with the sizes scaled to match.
+    #[allow(clippy::too_many_arguments)]
+    fn drag_crop_within(
+        crop_rect: &mut egui::Rect,
+        handle: ResizeHandle,
+        delta_norm: egui::Vec2,
+        image_size: egui::Vec2,
+        target_ratio: Option<f32>,
+        fixed_area: Option<f32>,
+        bounds: egui::Rect,
+    ) {
+        let scale = bounds.size();
+        let mut local = Self::to_bounds(bounds, *crop_rect);
+        let delta = delta_norm / scale;
+        match fixed_area {
+            Some(area) => {
+                Self::drag_crop_fixed_area(&mut local, handle, delta, area / (scale.x * scale.y))
+            }
+            None => Self::drag_crop(&mut local, handle, delta, image_size * scale, target_ratio),
+        }
+        *crop_rect = Self::from_bounds(bounds, local);
+    }
+
+    /// The extra drag that puts the edges `handle` moves exactly on the image
+    /// border when they are within `tolerance` of it, or zero.
+    fn edge_snap(rect: egui::Rect, handle: ResizeHandle, tolerance: egui::Vec2) -> egui::Vec2 {
+        let moves_left = matches!(
+            handle,
+            ResizeHandle::TopLeft
+                | ResizeHandle::BottomLeft
+                | ResizeHandle::Left
+                | ResizeHandle::Center
+        );
+        let moves_right = matches!(
+            handle,
+            ResizeHandle::TopRight
+                | ResizeHandle::BottomRight
+                | ResizeHandle::Right
+                | ResizeHandle::Center
+        );
+        let moves_top = matches!(
+            handle,
+            ResizeHandle::TopLeft
+                | ResizeHandle::TopRight
+                | ResizeHandle::Top
+                | ResizeHandle::Center
+        );
+        let moves_bottom = matches!(
+            handle,
+            ResizeHandle::BottomLeft
+                | ResizeHandle::BottomRight
+                | ResizeHandle::Bottom
+                | ResizeHandle::Center
+        );
+        // The nearer of the two moving edges wins when both are close
+        let axis = |min: f32, max: f32, moves_min: bool, moves_max: bool, tolerance: f32| {
+            [(moves_min, -min), (moves_max, 1.0 - max)]
+                .into_iter()
+                .filter(|&(moves, offset)| moves && offset.abs() < tolerance)
+                .map(|(_, offset)| offset)
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or(0.0)
+        };
+        egui::vec2(
+            axis(rect.min.x, rect.max.x, moves_left, moves_right, tolerance.x),
+            axis(rect.min.y, rect.max.y, moves_top, moves_bottom, tolerance.y),
+        )
+    }
+
+    /// Locks to the preset closest to the current crop's ratio and grows the
+    /// crop to the largest rect of that ratio around the same center.
+    fn snap_and_maximize(&mut self) {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return;
+        };
+        let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+        let size = crop_rect.size() * image_size;
+        // A rough crop is rarely within the label tolerance, so take the closest
+        let Some(mode) = nearest_preset_ratio(size.x / size.y.max(f32::EPSILON), f32::INFINITY)
+        else {
+            return;
+        };
+        let ratio = mode.ratio().unwrap_or(1.0);
+
+        self.aspect_ratio_mode = mode;
+        self.aspect_lock = None;
+        self.is_portrait = mode.is_portrait();
+        let rect = Self::max_rect_around(crop_rect.center(), image_size, ratio);
+        self.crop_rect = Some(rect);
+        Self::push_recent_crop(&mut self.recent_crops, rect);
+        self.record_crop();
+    }
+
+    fn crop_data(&self) -> Option<crop_data::CropData> {
+        let image = self.image.as_ref()?;
+        Some(crop_data::CropData {
+            rect: self.crop_rect?,
+            image_size: (image.width(), image.height()),
+            aspect_ratio: self.aspect_ratio_mode,
+            is_portrait: self.is_portrait,
+            custom_ratio: (self.custom_w, self.custom_h),
+        })
+    }
+
+    /// `rect` clipped to `crop_bounds` and grown to at least MIN_CROP_PX a
+    /// side, or `None` if it misses the bounds.
+    fn fit_to_bounds(&self, rect: egui::Rect) -> Option<egui::Rect> {
+        let image = self.image.as_ref()?;
+        let bounds = self.crop_bounds();
+        let rect = rect.intersect(bounds);
+        if !rect.is_positive() {
+            return None;
+        }
+        let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+        let min_size = (egui::Vec2::splat(MIN_CROP_PX) / image_size).min(bounds.size());
+        if rect.width() >= min_size.x && rect.height() >= min_size.y {
+            return Some(rect);
+        }
+        let size = rect.size().max(min_size);
+        let center = rect
+            .center()
+            .clamp(bounds.min + size / 2.0, bounds.max - size / 2.0);
+        Some(egui::Rect::from_center_size(center, size))
+    }
+
+    /// Restores the crop and ratio settings from `data`. The rect is
+    /// normalized, so it applies even if this image has another size.
+    fn apply_crop_data(&mut self, data: crop_data::CropData) -> Result<(), CropperError> {
+        let rect = self.fit_to_bounds(data.rect).ok_or_else(|| {
+            CropperError::InvalidCropData("the crop lies outside the image".to_owned())
+        })?;
+        self.crop_rect = Some(rect);
+        self.aspect_ratio_mode = data.aspect_ratio;
+        self.is_portrait = data.is_portrait;
+        (self.custom_w, self.custom_h) = data.custom_ratio;
+        self.record_crop();
+        Ok(())
+    }
+
+    /// Asks where to write the crop as a JSON sidecar, next to the image by
+    /// default.
+    fn export_crop_data(&mut self) -> Result<(), CropperError> {
+        let data = self.crop_data().ok_or(CropperError::NoImage)?;
+        let mut dialog = rfd::FileDialog::new().add_filter("JSON", &["json"]);
+        if let Some(path) = &self.image_path {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            dialog = dialog.set_file_name(format!("{}.crop.json", stem));
+            if let Some(folder) = path.parent() {
+                dialog = dialog.set_directory(folder);
+            }
+        }
+        if let Some(path) = dialog.save_file() {
+            std::fs::write(&path, data.to_json())?;
+            self.export_status = Some(format!("Crop data saved to {}", path.display()));
+        }
+        Ok(())
+    }
+
+    fn import_crop_data(&mut self) -> Result<(), CropperError> {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .pick_file()
+        else {
+            return Ok(());
+        };
+        let data = crop_data::CropData::parse(&std::fs::read_to_string(path)?)?;
+        self.apply_crop_data(data)
+    }
+
+    /// Switches to a Custom ratio equal to the current crop's pixel shape,
+    /// reduced so 1920×1080 reads 16:9, leaving the crop as it is.
+    fn lock_current_ratio(&mut self) {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return;
+        };
+        let (_, _, width, height) = crop_pixel_bounds(image.width(), image.height(), crop_rect);
+        let divisor = gcd(width, height);
+        self.custom_w = width / divisor;
+        self.custom_h = height / divisor;
+        self.aspect_ratio_mode = AspectRatioMode::Custom;
+        self.aspect_lock = None;
+        self.is_portrait = height > width;
+    }
+
+    /// Turns "Aspect lock" on, holding the crop's current pixel shape through
+    /// every handle whatever the ratio mode, or off again.
+    fn set_aspect_lock(&mut self, locked: bool) {
+        self.aspect_lock = match (&self.image, self.crop_rect) {
+            (Some(image), Some(crop_rect)) if locked => {
+                let (_, _, width, height) =
+                    crop_pixel_bounds(image.width(), image.height(), crop_rect);
+                Some(width as f32 / height as f32)
+            }
+            _ => None,
+        };
+    }
+
+    /// Switches to a Custom ratio matching a `width`×`height` screen and grows
+    /// the crop to the largest rect of that ratio around its center.
+    fn match_monitor(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let divisor = gcd(width, height);
+        self.custom_w = width / divisor;
+        self.custom_h = height / divisor;
+        self.aspect_ratio_mode = AspectRatioMode::Custom;
+        self.aspect_lock = None;
+        self.is_portrait = height > width;
+        if let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) {
+            let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+            let ratio = width as f32 / height as f32;
+            let rect = Self::max_rect_around(crop_rect.center(), image_size, ratio);
+            self.crop_rect = Some(rect);
+            Self::push_recent_crop(&mut self.recent_crops, rect);
+        }
+        self.record_crop();
+    }
+
+    /// Largest normalized rect of pixel aspect `ratio` centered on `center`
+    /// that fits inside the image.
+    fn max_rect_around(center: egui::Pos2, image_size: egui::Vec2, ratio: f32) -> egui::Rect {
+        let max_w = 2.0 * center.x.min(1.0 - center.x) * image_size.x;
+        let max_h = 2.0 * center.y.min(1.0 - center.y) * image_size.y;
+        let width = max_w.min(max_h * ratio);
+        let size = egui::vec2(width, width / ratio) / image_size;
+        egui::Rect::from_center_size(center, size)
+    }
+
+    /// Treats the current crop as a box around a face and replaces it with a
+    /// headshot crop in the target ratio (square when free).
+    fn frame_face(&mut self) {
+        let ratio = self.target_ratio().unwrap_or(1.0);
+        let headroom = self.settings.face_headroom;
+        if let (Some(image), Some(crop_rect)) = (&self.image, &mut self.crop_rect) {
+            let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+            *crop_rect = Self::face_crop(*crop_rect, image_size, ratio, headroom);
+            Self::push_recent_crop(&mut self.recent_crops, *crop_rect);
+        }
+        self.record_crop();
+    }
+
+    /// Finds the largest face with the detector and frames it like
+    /// `frame_face`. Asks for the model file the first time.
+    #[cfg(feature = "face-detect")]
+    fn detect_face(&mut self) {
+        let Some(image) = &self.image else {
+            return;
+        };
+        let model = match &self.settings.face_model {
+            Some(path) => path.clone(),
+            None => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title("Choose the face model (seeta_fd_frontal_v1.0.bin)")
+                    .add_filter("SeetaFace model", &["bin"])
+                    .pick_file()
+                else {
+                    return;
+                };
+                self.settings.face_model = Some(path.clone());
+                path
+            }
+        };
+        match face::detect_largest(image, &model) {
+            Ok(Some(face)) => {
+                self.crop_rect = Some(face);
+                self.frame_face();
+            }
+            Ok(None) => self.error = Some(CropperError::NoFace),
+            Err(e) => {
+                // Ask again next time rather than failing on the same file
+                self.settings.face_model = None;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Normalized crop of pixel aspect `ratio` around the normalized `face`,
+    /// leaving `headroom` face heights above it and centered horizontally.
+    /// Shrinks to fit if the image is too small for the full framing.
+    fn face_crop(
+        face: egui::Rect,
+        image_size: egui::Vec2,
+        ratio: f32,
+        headroom: f32,
+    ) -> egui::Rect {
+        let face_h = face.height() * image_size.y;
+        let mut height = face_h * (1.0 + headroom + FACE_CHIN_ROOM);
+        let mut width = height * ratio;
+        let fit = (image_size.x / width).min(image_size.y / height).min(1.0);
+        width *= fit;
+        height *= fit;
+
+        let top = face.min.y * image_size.y - headroom * face_h * fit;
+        let left = face.center().x * image_size.x - width * 0.5;
+        let rect = egui::Rect::from_min_size(
+            egui::pos2(left / image_size.x, top / image_size.y),
+            egui::vec2(width / image_size.x, height / image_size.y),
+        );
+        Self::slide_inside(rect)
+    }
+
+    /// Rescales `rect` about its center to normalized area `area`.
+    fn fit_area(rect: egui::Rect, area: f32) -> egui::Rect {
+        let w = rect.width().max(f32::EPSILON);
+        let h = rect.height().max(f32::EPSILON);
+        // Scale both sides equally so the current shape is kept where possible
+        let k = (area / (w * h)).sqrt();
+        let (w, h) = Self::area_size_from_width(w * k, area);
+        Self::slide_inside(egui::Rect::from_center_size(
+            rect.center(),
+            egui::vec2(w, h),
+        ))
+    }
+
+    /// Width and height with normalized area `area`, starting from `width` and
+    /// clamped so neither side exceeds the image.
+    fn area_size_from_width(width: f32, area: f32) -> (f32, f32) {
+        let width = width.clamp(area, 1.0);
+        (width, area / width)
+    }
+
+    /// Translates `rect` (no larger than the image) back inside 0.0-1.0.
+    fn slide_inside(rect: egui::Rect) -> egui::Rect {
+        let offset = egui::vec2(
+            (-rect.min.x).max(0.0) + (1.0 - rect.max.x).min(0.0),
+            (-rect.min.y).max(0.0) + (1.0 - rect.max.y).min(0.0),
+        );
+        rect.translate(offset)
+    }
+
+    /// Like `drag_crop`, but holds the normalized area of `crop_rect` at
+    /// `area` while the aspect ratio follows the drag.
+    fn drag_crop_fixed_area(
+        crop_rect: &mut egui::Rect,
+        handle: ResizeHandle,
+        delta_norm: egui::Vec2,
+        area: f32,
+    ) {
+        let rect = *crop_rect;
+        let new_rect = match handle {
+            ResizeHandle::Center => {
+                Self::drag_crop(crop_rect, handle, delta_norm, egui::Vec2::splat(1.0), None);
+                return;
+            }
+            // Side handles drive one dimension; the other follows to hold the area
+            ResizeHandle::Left | ResizeHandle::Right => {
+                let raw_w = match handle {
+                    ResizeHandle::Left => rect.width() - delta_norm.x,
+                    _ => rect.width() + delta_norm.x,
+                };
+                let (w, h) = Self::area_size_from_width(raw_w, area);
+                let (min_x, max_x) = match handle {
+                    ResizeHandle::Left => (rect.max.x - w, rect.max.x),
+                    _ => (rect.min.x, rect.min.x + w),
+                };
+                egui::Rect::from_min_max(
+                    egui::pos2(min_x, rect.center().y - h * 0.5),
+                    egui::pos2(max_x, rect.center().y + h * 0.5),
+                )
+            }
+            ResizeHandle::Top | ResizeHandle::Bottom => {
+                let raw_h = match handle {
+                    ResizeHandle::Top => rect.height() - delta_norm.y,
+                    _ => rect.height() + delta_norm.y,
+                };
+                let (w, h) = Self::area_size_from_width(area / raw_h.max(area), area);
+                let (min_y, max_y) = match handle {
+                    ResizeHandle::Top => (rect.max.y - h, rect.max.y),
+                    _ => (rect.min.y, rect.min.y + h),
+                };
+                egui::Rect::from_min_max(
+                    egui::pos2(rect.center().x - w * 0.5, min_y),
+                    egui::pos2(rect.center().x + w * 0.5, max_y),
+                )
+            }
+            // Corners follow the dragged shape, rescaled onto the target area
+            ResizeHandle::TopLeft
+            | ResizeHandle::TopRight
+            | ResizeHandle::BottomLeft
+            | ResizeHandle::BottomRight => {
+                let (anchor, corner) = match handle {
+                    ResizeHandle::TopLeft => (rect.max, rect.min),
+                    ResizeHandle::TopRight => (rect.left_bottom(), rect.right_top()),
+                    ResizeHandle::BottomLeft => (rect.right_top(), rect.left_bottom()),
+                    _ => (rect.min, rect.max),
+                };
+                let dragged = corner + delta_norm - anchor;
+                let raw = dragged.abs().max(egui::Vec2::splat(f32::EPSILON));
+                let k = (area / (raw.x * raw.y)).sqrt();
+                let (w, h) = Self::area_size_from_width(raw.x * k, area);
+                let far = anchor + egui::vec2(w * dragged.x.signum(), h * dragged.y.signum());
+                egui::Rect::from_two_pos(anchor, far)
+            }
+        };
+        *crop_rect = Self::slide_inside(new_rect);
+    }
+
+    /// Movement to apply this frame for a press that has moved `total` from
+    /// its origin. Nothing happens until `DRAG_THRESHOLD` is passed; the frame
+    /// that passes it applies all the movement so far so nothing is lost.
+    fn thresholded_drag_delta(
+        engaged: &mut bool,
+        total: egui::Vec2,
+        frame_delta: egui::Vec2,
+    ) -> egui::Vec2 {
+        if *engaged {
+            frame_delta
+        } else if total.length() >= DRAG_THRESHOLD {
+            *engaged = true;
+            total
+        } else {
+            egui::Vec2::ZERO
+        }
+    }
+
+    /// Crop drawn from the normalized `anchor` towards `pointer`. With a
+    /// `target_ratio` the far corner follows the ratio line closest to the
+    /// pointer; either way the rect stays inside the image.
+    fn draw_crop(
+        anchor: egui::Pos2,
+        pointer: egui::Pos2,
+        image_size: egui::Vec2,
+        target_ratio: Option<f32>,
+    ) -> egui::Rect {
+        let pointer = pointer.clamp(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        let Some(ratio) = target_ratio else {
+            return egui::Rect::from_two_pos(anchor, pointer);
+        };
+
+        let delta = (pointer - anchor) * image_size;
+        // Room from the anchor to the image edge in the drag direction, in pixels
+        let room = egui::vec2(
+            if delta.x < 0.0 {
+                anchor.x
+            } else {
+                1.0 - anchor.x
+            },
+            if delta.y < 0.0 {
+                anchor.y
+            } else {
+                1.0 - anchor.y
+            },
+        ) * image_size;
+
+        // Project onto the ratio line (ratio, 1), then shrink to the room left
+        let u = egui::vec2(ratio, 1.0);
+        let lambda = delta.abs().dot(u) / u.length_sq();
+        let size = u * lambda;
+        let size = size * (room.x / size.x).min(room.y / size.y).min(1.0);
+        let size = if size.x.is_finite() && size.y.is_finite() {
+            size
+        } else {
+            egui::Vec2::ZERO
+        };
+
+        let far = anchor
+            + egui::vec2(
+                size.x / image_size.x * if delta.x < 0.0 { -1.0 } else { 1.0 },
+                size.y / image_size.y * if delta.y < 0.0 { -1.0 } else { 1.0 },
+            );
+        egui::Rect::from_two_pos(anchor, far)
+    }
+
+    /// Applies a handle drag of `delta_norm` (normalized units) to `crop_rect`,
+    /// honouring `target_ratio` and keeping the rect inside the image.
+    fn drag_crop(
+        crop_rect: &mut egui::Rect,
+        handle: ResizeHandle,
+        delta_norm: egui::Vec2,
+        image_size: egui::Vec2,
+        target_ratio: Option<f32>,
+    ) {
+        if !(image_size.x > 0.0 && image_size.y > 0.0) {
+            return;
+        }
+        let norm_aspect = target_ratio.map(|r| r * (image_size.y / image_size.x));
+
+        if let (Some(ratio), Some(norm_aspect)) = (target_ratio, norm_aspect) {
+            // Constrained resize
+            // Helper to convert normalized width/height to pixel space
+            let to_screen = |w_norm: f32, h_norm: f32| -> egui::Vec2 {
+                egui::vec2(w_norm * image_size.x, h_norm * image_size.y)
+            };
+            // Helper to convert pixel dimensions back to normalized space dimensions
+            let to_norm = |w_screen: f32, h_screen: f32| -> egui::Vec2 {
+                egui::vec2(w_screen / image_size.x, h_screen / image_size.y)
+            };
+
+            match handle {
+                ResizeHandle::Center => {
+                    // Safe Panning: constrain delta to stay within bounds
+                    let mut final_delta = delta_norm;
+                    if crop_rect.min.x + final_delta.x < 0.0 {
+                        final_delta.x = -crop_rect.min.x;
+                    }
+                    if crop_rect.max.x + final_delta.x > 1.0 {
+                        final_delta.x = 1.0 - crop_rect.max.x;
+                    }
+                    if crop_rect.min.y + final_delta.y < 0.0 {
+                        final_delta.y = -crop_rect.min.y;
+                    }
+                    if crop_rect.max.y + final_delta.y > 1.0 {
+                        final_delta.y = 1.0 - crop_rect.max.y;
+                    }
+
+                    *crop_rect = crop_rect.translate(final_delta);
+                }
+                // Corner Handles: Use projection logic for smooth interactions
+                ResizeHandle::TopLeft
+                | ResizeHandle::TopRight
+                | ResizeHandle::BottomLeft
+                | ResizeHandle::BottomRight => {
+                    // 1. Identify Anchor (Fixed Point) and current Corner
+                    let (anchor, mut corner) = match handle {
+                        ResizeHandle::TopLeft => (crop_rect.max, crop_rect.min),
+                        ResizeHandle::TopRight => (
+                            egui::pos2(crop_rect.min.x, crop_rect.max.y),
+                            egui::pos2(crop_rect.max.x, crop_rect.min.y),
+                        ),
+                        ResizeHandle::BottomLeft => (
+                            egui::pos2(crop_rect.max.x, crop_rect.min.y),
+                            egui::pos2(crop_rect.min.x, crop_rect.max.y),
+                        ),
+                        ResizeHandle::BottomRight => (crop_rect.min, crop_rect.max),
+                        _ => (egui::Pos2::ZERO, egui::Pos2::ZERO), // Unreachable
+                    };
+
+                    // 2. Calculate suggested new dimensions in screen space
+                    // Apply delta to corner
+                    match handle {
+                        ResizeHandle::TopLeft => corner += delta_norm,
+                        ResizeHandle::TopRight => {
+                            corner.y += delta_norm.y;
+                            corner.x += delta_norm.x;
+                        }
+                        ResizeHandle::BottomLeft => {
+                            corner.x += delta_norm.x;
+                            corner.y += delta_norm.y;
+                        }
+                        ResizeHandle::BottomRight => corner += delta_norm,
+                        _ => {}
+                    }
+
+                    // Calculate raw new width/height, measured away from the anchor so
+                    // dragging past it bottoms out instead of mirroring the crop
+                    let (sign_x, sign_y) = match handle {
+                        ResizeHandle::TopLeft => (-1.0, -1.0),
+                        ResizeHandle::TopRight => (1.0, -1.0),
+                        ResizeHandle::BottomLeft => (-1.0, 1.0),
+                        _ => (1.0, 1.0),
+                    };
+                    let raw_w_norm = ((corner.x - anchor.x) * sign_x).max(0.0);
+                    let raw_h_norm = ((corner.y - anchor.y) * sign_y).max(0.0);
+                    let raw_screen =
+                        to_screen(raw_w_norm, raw_h_norm).max(egui::Vec2::splat(MIN_CROP_PX));
+
+                    // 3. Project onto aspect ratio vector
+                    // Vector direction U = (ratio, 1.0)
+                    let u = egui::vec2(ratio, 1.0);
+                    let p = raw_screen; // Computed target vector
+                    // Projection: (P . U) / (U . U) * U
+                    // Keep both sides at least MIN_CROP_PX long
+                    let lambda =
+                        (p.dot(u) / u.length_sq()).max(MIN_CROP_PX * (1.0 / ratio).max(1.0));
+                    let constrained_screen = u * lambda;
+
+                    // 4. Convert back to normalized
+                    let mut final_dim = to_norm(constrained_screen.x, constrained_screen.y);
+
+                    // 5. Constrain to Image Bounds BEFORE applying
+                    // Calculate max available dimensions from anchor
+                    let (max_w, max_h) = match handle {
+                        ResizeHandle::TopLeft => (anchor.x, anchor.y),
+                        ResizeHandle::TopRight => (1.0 - anchor.x, anchor.y),
+                        ResizeHandle::BottomLeft => (anchor.x, 1.0 - anchor.y),
+                        ResizeHandle::BottomRight => (1.0 - anchor.x, 1.0 - anchor.y),
+                        _ => (1.0, 1.0),
+                    };
+
+                    // Scale down if exceeding bounds
+                    let mut scale_factor = 1.0f32;
+                    if final_dim.x > max_w {
+                        scale_factor = scale_factor.min(max_w / final_dim.x);
+                    }
+                    if final_dim.y > max_h {
+                        scale_factor = scale_factor.min(max_h / final_dim.y);
+                    }
+                    final_dim *= scale_factor;
+
+                    // Reconstruct rect from Anchor
+                    let (new_min, new_max) = match handle {
+                        ResizeHandle::TopLeft => (anchor - final_dim, anchor),
+                        ResizeHandle::TopRight => (
+                            egui::pos2(anchor.x, anchor.y - final_dim.y),
+                            egui::pos2(anchor.x + final_dim.x, anchor.y),
+                        ),
+                        ResizeHandle::BottomLeft => (
+                            egui::pos2(anchor.x - final_dim.x, anchor.y),
+                            egui::pos2(anchor.x, anchor.y + final_dim.y),
+                        ),
+                        ResizeHandle::BottomRight => (anchor, anchor + final_dim),
+                        _ => (egui::Pos2::ZERO, egui::Pos2::ZERO),
+                    };
+
+                    *crop_rect = egui::Rect::from_min_max(new_min, new_max);
+                }
+
+                // Side Handles: Drive one dimension, center the other
+                ResizeHandle::Left | ResizeHandle::Right => {
+                    // Drive Width
+                    match handle {
+                        ResizeHandle::Left => {
+                            crop_rect.min.x += delta_norm.x;
+                        }
+                        ResizeHandle::Right => {
+                            crop_rect.max.x += delta_norm.x;
+                        }
+                        _ => {}
+                    }
+
+                    // A side dragged past the opposite one bottoms out at the minimum size
+                    let min_w = MIN_CROP_PX * ratio.max(1.0) / image_size.x;
+                    let mut new_w = (crop_rect.max.x - crop_rect.min.x).max(min_w);
+
+                    // Constrain Width
+                    // 1. Stop at the image edge on the dragged side, so the later
+                    //    clamp never has to cut the rect and skew its ratio
+                    let room = match handle {
+                        ResizeHandle::Left => crop_rect.max.x,
+                        _ => 1.0 - crop_rect.min.x,
+                    };
+                    if new_w > room {
+                        new_w = room;
+                    }
+
+                    // 2. Calc Height
+                    let mut new_h = new_w / norm_aspect;
+
+                    // 3. If Height > 1.0, clamp Height => clamp Width
+                    if new_h > 1.0 {
+                        new_h = 1.0;
+                        new_w = new_h * norm_aspect;
+                    }
+
+                    // 4. Center Height
+                    let old_center_y = crop_rect.center().y;
+                    let mut min_y = old_center_y - new_h * 0.5;
+                    let mut max_y = old_center_y + new_h * 0.5;
+
+                    // 5. Bounds Check & Slide
+                    if min_y < 0.0 {
+                        let offset = -min_y;
+                        min_y += offset;
+                        max_y += offset;
+                    }
+                    if max_y > 1.0 {
+                        let offset = 1.0 - max_y;
+                        min_y += offset;
+                        max_y += offset;
+                    }
+
+                    // Apply
+                    crop_rect.min.y = min_y;
+                    crop_rect.max.y = max_y;
+                    // Re-apply width to handled side (keeping center if possible? No, we moved one side)
+                    // Actually we just updated crop_rect.min/max.x above directly.
+                    // We need to re-sync them to the valid new_w
+                    match handle {
+                        ResizeHandle::Left => crop_rect.min.x = crop_rect.max.x - new_w,
+                        ResizeHandle::Right => crop_rect.max.x = crop_rect.min.x + new_w,
+                        _ => {}
+                    }
+                }
+                ResizeHandle::Top | ResizeHandle::Bottom => {
+                    // Drive Height
+                    match handle {
+                        ResizeHandle::Top => {
+                            crop_rect.min.y += delta_norm.y;
+                        }
+                        ResizeHandle::Bottom => {
+                            crop_rect.max.y += delta_norm.y;
+                        }
+                        _ => {}
+                    }
+
+                    let min_h = MIN_CROP_PX * (1.0 / ratio).max(1.0) / image_size.y;
+                    let mut new_h = (crop_rect.max.y - crop_rect.min.y).max(min_h);
+
+                    // Constrain to the room on the dragged side
+                    let room = match handle {
+                        ResizeHandle::Top => crop_rect.max.y,
+                        _ => 1.0 - crop_rect.min.y,
+                    };
+                    if new_h > room {
+                        new_h = room;
+                    }
+
+                    let mut new_w = new_h * norm_aspect;
+
+                    if new_w > 1.0 {
+                        new_w = 1.0;
+                        new_h = new_w / norm_aspect;
+                    }
+
+                    let old_center_x = crop_rect.center().x;
+                    let mut min_x = old_center_x - new_w * 0.5;
+                    let mut max_x = old_center_x + new_w * 0.5;
+
+                    if min_x < 0.0 {
+                        let offset = -min_x;
+                        min_x += offset;
+                        max_x += offset;
+                    }
+                    if max_x > 1.0 {
+                        let offset = 1.0 - max_x;
+                        min_x += offset;
+                        max_x += offset;
+                    }
+
+                    crop_rect.min.x = min_x;
+                    crop_rect.max.x = max_x;
+
+                    match handle {
+                        ResizeHandle::Top => crop_rect.min.y = crop_rect.max.y - new_h,
+                        ResizeHandle::Bottom => crop_rect.max.y = crop_rect.min.y + new_h,
+                        _ => {}
+                    }
+                }
+            }
+        } else {
+            // Free resize
+            match handle {
+                ResizeHandle::Center => {
+                    // Safe Panning: constrain delta to stay within bounds
+                    let mut final_delta = delta_norm;
+                    if crop_rect.min.x + final_delta.x < 0.0 {
+                        final_delta.x = -crop_rect.min.x;
+                    }
+                    if crop_rect.max.x + final_delta.x > 1.0 {
+                        final_delta.x = 1.0 - crop_rect.max.x;
+                    }
+                    if crop_rect.min.y + final_delta.y < 0.0 {
+                        final_delta.y = -crop_rect.min.y;
+                    }
+                    if crop_rect.max.y + final_delta.y > 1.0 {
+                        final_delta.y = 1.0 - crop_rect.max.y;
+                    }
+
+                    *crop_rect = crop_rect.translate(final_delta);
+                }
+                ResizeHandle::TopLeft => {
+                    crop_rect.min += delta_norm;
+                }
+                ResizeHandle::TopRight => {
+                    crop_rect.min.y += delta_norm.y;
+                    crop_rect.max.x += delta_norm.x;
+                }
+                ResizeHandle::BottomLeft => {
+                    crop_rect.min.x += delta_norm.x;
+                    crop_rect.max.y += delta_norm.y;
+                }
+                ResizeHandle::BottomRight => {
+                    crop_rect.max += delta_norm;
+                }
+                ResizeHandle::Top => {
+                    crop_rect.min.y += delta_norm.y;
+                }
+                ResizeHandle::Bottom => {
+                    crop_rect.max.y += delta_norm.y;
+                }
+                ResizeHandle::Left => {
+                    crop_rect.min.x += delta_norm.x;
+                }
+                ResizeHandle::Right => {
+                    crop_rect.max.x += delta_norm.x;
+                }
+            }
+
+            // The dragged edges stop short of the opposite ones
+            let min_size = (egui::Vec2::splat(MIN_CROP_PX) / image_size).min(egui::vec2(1.0, 1.0));
+            if matches!(
+                handle,
+                ResizeHandle::TopLeft | ResizeHandle::BottomLeft | ResizeHandle::Left
+            ) {
+                crop_rect.min.x = crop_rect.min.x.min(crop_rect.max.x - min_size.x);
+            }
+            if matches!(
+                handle,
+                ResizeHandle::TopRight | ResizeHandle::BottomRight | ResizeHandle::Right
+            ) {
+                crop_rect.max.x = crop_rect.max.x.max(crop_rect.min.x + min_size.x);
+            }
+            if matches!(
+                handle,
+                ResizeHandle::TopLeft | ResizeHandle::TopRight | ResizeHandle::Top
+            ) {
+                crop_rect.min.y = crop_rect.min.y.min(crop_rect.max.y - min_size.y);
+            }
+            if matches!(
+                handle,
+                ResizeHandle::BottomLeft | ResizeHandle::BottomRight | ResizeHandle::Bottom
+            ) {
+                crop_rect.max.y = crop_rect.max.y.max(crop_rect.min.y + min_size.y);
+            }
+        }
+
+        // Clamp and ensure min < max
+        if crop_rect.min.x < 0.0 {
+            crop_rect.min.x = 0.0;
+        }
+        if crop_rect.min.y < 0.0 {
+            crop_rect.min.y = 0.0;
+        }
+        if crop_rect.max.x > 1.0 {
+            crop_rect.max.x = 1.0;
+        }
+        if crop_rect.max.y > 1.0 {
+            crop_rect.max.y = 1.0;
+        }
+        // The handles stop at MIN_CROP_PX, so an axis only flips here if the
+        // rect passed in was already inverted
+        if crop_rect.min.x > crop_rect.max.x {
+            std::mem::swap(&mut crop_rect.min.x, &mut crop_rect.max.x);
+        }
+        if crop_rect.min.y > crop_rect.max.y {
+            std::mem::swap(&mut crop_rect.min.y, &mut crop_rect.max.y);
+        }
+    }
+
+    /// Returns the index of the quad corner under `pos`, if any.
+    fn hit_test_quad(pos: egui::Pos2, corners: [egui::Pos2; 4]) -> Option<usize> {
+        let tolerance = 10.0;
+        corners
+            .iter()
+            .position(|corner| pos.distance(*corner) < tolerance)
+    }
+
+    fn paint_quad(
+        painter: &egui::Painter,
+        image_rect: egui::Rect,
+        corners: [egui::Pos2; 4],
+        settings: &Settings,
+        show_overlay: bool,
+    ) {
+        // Dim the area between the image bounds and the quad, one trapezoid per edge
+        if show_overlay && settings.show_overlays {
+            let overlay_color = egui::Color32::from_black_alpha(settings.overlay_alpha);
+            let outer = Self::rect_corners(image_rect);
+            for i in 0..4 {
+                let j = (i + 1) % 4;
+                painter.add(egui::Shape::convex_polygon(
+                    vec![outer[i], outer[j], corners[j], corners[i]],
+                    overlay_color,
+                    egui::Stroke::NONE,
+                ));
+            }
+        }
+
+        if settings.contrast_border {
+            painter.add(egui::Shape::closed_line(
+                corners.to_vec(),
+                egui::Stroke::new(3.0, egui::Color32::from_black_alpha(180)),
+            ));
+        }
+        painter.add(egui::Shape::closed_line(
+            corners.to_vec(),
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+        ));
+
+        if !show_overlay {
+            return;
+        }
+        for pos in corners {
+            painter.circle(
+                pos,
+                settings.handle_radius,
+                egui::Color32::WHITE,
+                egui::Stroke::new(1.0, egui::Color32::BLACK),
+            );
+        }
+    }
+
+    /// X, Y, width and height of the crop in pixels, for exact entry. They
+    /// are read back from `crop_rect` every frame, so drags show up live.
+    fn show_pixel_fields(&mut self, ui: &mut egui::Ui) {
+        let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) else {
+            return;
+        };
+        let (image_w, image_h) = (image.width(), image.height());
+        let (mut x, mut y, mut w, mut h) = crop_pixel_bounds(image_w, image_h, crop_rect);
+        let mut edited = None;
+        let mut finished = false;
+        ui.horizontal(|ui| {
+            ui.label("Crop (px):");
+            for (label, value, max, is_size) in [
+                ("X", &mut x, image_w - 1, false),
+                ("Y", &mut y, image_h - 1, false),
+                ("W", &mut w, image_w, true),
+                ("H", &mut h, image_h, true),
+            ] {
+                ui.label(label);
+                let min = if is_size { 1 } else { 0 };
+                let response = ui.add(egui::DragValue::new(value).range(min..=max));
+                if response.changed() {
+                    edited = Some(label);
+                }
+                // Dragging a value edits every frame; keep one undo step per drag
+                finished |= response.drag_stopped() || (response.changed() && !response.dragged());
+            }
+        });
+        if finished {
+            self.record_crop();
+        }
+        let Some(label) = edited else {
+            return;
+        };
+        // With a locked ratio, the edited side drives the other one
+        if let Some(ratio) = self.target_ratio() {
+            match label {
+                "W" => h = (w as f32 / ratio).round().max(1.0) as u32,
+                "H" => w = (h as f32 * ratio).round().max(1.0) as u32,
+                _ => {}
+            }
+        }
+        let rect = pixel_crop_rect(image_w, image_h, (x, y, w, h), self.target_ratio());
+        self.crop_rect = Some(rect);
+        if finished {
+            self.record_crop();
+        }
+    }
+
+    /// Named crops to save the current one under and recall later, on this
+    /// image or any other.
+    fn show_crop_presets_row(&mut self, ui: &mut egui::Ui) {
+        let Some(crop_rect) = self.crop_rect else {
+            return;
+        };
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Presets:");
+            let field = ui.add(
+                egui::TextEdit::singleline(&mut self.preset_name)
+                    .hint_text("name")
+                    .desired_width(100.0),
+            );
+            let entered = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let named = !self.preset_name.trim().is_empty();
+            if (ui
+                .add_enabled(named, egui::Button::new("Save"))
+                .on_hover_text("Keep the current crop under this name; an existing one is replaced")
+                .clicked()
+                || entered)
+                && named
+            {
+                self.crop_presets.set(&self.preset_name, crop_rect);
+                self.preset_name.clear();
+            }
+
+            let mut recall = None;
+            let mut remove = None;
+            for (name, rect) in self.crop_presets.iter() {
+                if ui
+                    .selectable_label(crop_rect == rect, name)
+                    .on_hover_text("Apply this crop")
+                    .clicked()
+                {
+                    recall = Some(rect);
+                }
+                if ui.small_button("✖").clicked() {
+                    remove = Some(name.to_owned());
+                }
+            }
+            if let Some(rect) = recall {
+                self.crop_rect = Some(rect);
+                self.record_crop();
+            }
+            if let Some(name) = remove {
+                self.crop_presets.remove(&name);
+            }
+        });
+    }
+
+    /// Multi-crop: named regions that can be imported from JSON, selected
+    /// for editing and exported together.
+    fn show_regions_row(&mut self, ui: &mut egui::Ui) {
+        let Some(image) = &self.image else {
+            return;
+        };
+        let mut result = Ok(());
+        let mut select = None;
+        let mut export_to = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Regions:");
+            if ui
+                .button("Import…")
+                .on_hover_text(
+                    "Load a JSON array of {\"name\", \"x\", \"y\", \"width\", \"height\"} in pixels",
+                )
+                .clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .pick_file()
+            {
+                result = std::fs::read_to_string(&path)
+                    .map_err(CropperError::from)
+                    .and_then(|json| regions::parse_regions(&json, image.width(), image.height()))
+                    .map(|regions| self.regions = regions);
+            }
+            if let Some(crop_rect) = self.crop_rect
+                && ui.button("+ Add").on_hover_text("Add the current crop").clicked()
+            {
+                let name = (self.regions.len() + 1..)
+                    .map(|n| format!("region{}", n))
+                    .find(|name| self.regions.iter().all(|r| &r.name != name))
+                    .unwrap_or_default();
+                self.regions.push(regions::Region {
+                    name,
+                    rect: crop_rect,
+                });
+            }
+
+            let mut remove = None;
+            for (index, region) in self.regions.iter().enumerate() {
+                let selected = self.crop_rect == Some(region.rect);
+                if ui.selectable_label(selected, &region.name).clicked() {
+                    select = Some(region.rect);
+                }
+                if ui.small_button("✖").clicked() {
+                    remove = Some(index);
+                }
+            }
+            if let Some(index) = remove {
+                self.regions.remove(index);
+            }
+
+            if !self.regions.is_empty()
+                && ui
+                    .button("Export All…")
+                    .on_hover_text(
+                        "Save every region in a folder, encoded as Save would and named by the \
+                         filename template with <image>_<name> as {stem}",
+                    )
+                    .clicked()
+            {
+                export_to = rfd::FileDialog::new().pick_folder();
+            }
+        });
+        if select.is_some() {
+            self.crop_rect = select;
+            self.record_crop();
+        }
+        if let Some(folder) = export_to {
+            result = result.and_then(|()| self.export_regions(&folder));
+        }
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    /// Angle slider for levelling a tilted horizon. The image is only turned
+    /// once the slider is released, since that resamples the whole image.
+    fn show_straighten_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Straighten:");
+            let response = ui
+                .add(
+                    egui::Slider::new(
+                        &mut self.straighten_deg,
+                        -straighten::MAX_ANGLE..=straighten::MAX_ANGLE,
+                    )
+                    .suffix("°")
+                    .step_by(0.1),
+                )
+                .on_hover_text("Turn the image clockwise to level it; the crop avoids the corners");
+            let mut apply = response.drag_stopped() || (response.changed() && !response.dragged());
+            if ui
+                .add_enabled(self.straighten_deg != 0.0, egui::Button::new("Reset"))
+                .clicked()
+            {
+                self.straighten_deg = 0.0;
+                apply = true;
+            }
+            if apply {
+                self.apply_straighten(ui.ctx());
+            }
+        });
+    }
+
+    /// Buttons that blur or pixelate the crop in place, for redacting part
+    /// of an image.
+    fn show_redact_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Redact:");
+            ui.add(
+                egui::Slider::new(&mut self.redact_strength, 1..=redact::MAX_STRENGTH)
+                    .suffix(" px"),
+            )
+            .on_hover_text("Blur radius, or the size of the pixelation blocks");
+            if ui
+                .button("Blur selection")
+                .on_hover_text("Blur the pixels under the crop; press again to blur more")
+                .clicked()
+            {
+                self.redact_crop(ui.ctx(), false);
+            }
+            if ui
+                .button("Pixelate selection")
+                .on_hover_text("Replace the pixels under the crop with coarse blocks")
+                .clicked()
+            {
+                self.redact_crop(ui.ctx(), true);
+            }
+        });
+    }
+
+    /// Preset toggles and an export that writes the largest crop of each
+    /// selected ratio around the current crop's center.
+    fn show_multi_ratio_row(&mut self, ui: &mut egui::Ui) {
+        if self.image.is_none() || self.crop_rect.is_none() {
+            return;
+        }
+        let mut export_to = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Multi-ratio:");
+            for mode in AspectRatioMode::ALL
+                .into_iter()
+                .filter(|m| m.ratio().is_some())
+            {
+                let selected = self.settings.multi_ratios.contains(&mode);
+                if ui.selectable_label(selected, mode.to_string()).clicked() {
+                    if selected {
+                        self.settings.multi_ratios.retain(|m| *m != mode);
+                    } else {
+                        self.settings.multi_ratios.push(mode);
+                    }
+                }
+            }
+            if ui
+                .add_enabled(
+                    !self.settings.multi_ratios.is_empty(),
+                    egui::Button::new("Export Ratios…"),
+                )
+                .on_hover_text(
+                    "Save the largest crop of each selected ratio around the crop center, \
+                     named by the filename template with <image>_<ratio> as {stem}",
+                )
+                .clicked()
+            {
+                export_to = rfd::FileDialog::new().pick_folder();
+            }
+        });
+        if let Some(folder) = export_to
+            && let Err(e) = self.export_ratio_crops(&folder)
+        {
+            self.error = Some(e);
+        }
+    }
+
+    /// Resets the crop to the full frame, or the largest rect of the current
+    /// ratio, as when the image was loaded.
+    fn select_whole_image(&mut self) {
+        self.crop_rect = Some(egui::Rect::from_min_max(
+            egui::Pos2::ZERO,
+            egui::pos2(1.0, 1.0),
+        ));
+        self.selected_handle = None;
+        self.apply_aspect_ratio();
+        if let Some(rect) = self.crop_rect {
+            Self::push_recent_crop(&mut self.recent_crops, rect);
+        }
+    }
+
+    /// Context menu for the canvas, opened by right-click or, on touch
+    /// screens, by a long press.
+    fn show_canvas_menu(&mut self, ctx: &egui::Context) {
+        let Some(pos) = self.canvas_menu else {
+            return;
+        };
+        let mut close = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        let area = egui::Area::new(egui::Id::new("canvas_menu"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::menu(ui.style()).show(ui, |ui| {
+                    if ui.button("Select whole image").clicked() {
+                        self.select_whole_image();
+                        close = true;
+                    }
+                    if let (Some(image), Some(rect)) = (&self.image, self.crop_rect)
+                        && ui.button("Copy crop size").clicked()
+                    {
+                        let (_, _, w, h) = crop_pixel_bounds(image.width(), image.height(), rect);
+                        if let Err(e) = copy_text(&format!("{}x{}", w, h)) {
+                            self.error = Some(e);
+                        }
+                        close = true;
+                    }
+                    if ui.button("Save Cropped Image...").clicked() {
+                        close = true;
+                        self.save_cropped(ctx);
+                    }
+                });
+            });
+
+        let pressed_elsewhere = ctx.input(|i| {
+            i.pointer.any_pressed()
+                && i.pointer
+                    .interact_pos()
+                    .is_some_and(|p| !area.response.rect.contains(p))
+        });
+        if close || pressed_elsewhere {
+            self.canvas_menu = None;
+        }
+    }
+
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let settings = &mut self.settings;
+        egui::Window::new("Settings")
+            .open(&mut self.show_settings)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Startup");
+                    if ui.small_button("Reset").clicked() {
+                        settings.reset_startup();
+                    }
+                });
+                egui::Grid::new("settings_startup")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Tool:");
+                        ui.horizontal(|ui| {
+                            for tool in Tool::ALL {
+                                ui.radio_value(&mut settings.default_tool, tool, tool.to_string());
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Aspect ratio:");
+                        ui.horizontal(|ui| {
+                            ui.add_enabled_ui(!settings.remember_ratio, |ui| {
+                                egui::ComboBox::from_id_salt("settings_default_aspect_ratio")
+                                    .selected_text(settings.default_aspect_ratio.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for mode in AspectRatioMode::ALL {
+                                            ui.selectable_value(
+                                                &mut settings.default_aspect_ratio,
+                                                mode,
+                                                mode.to_string(),
+                                            );
+                                        }
+                                    });
+                            });
+                            ui.checkbox(&mut settings.remember_ratio, "Remember last used")
+                                .on_hover_text("Start with the ratio and custom size used last time");
+                        });
+                        ui.end_row();
+
+                        ui.label("Clipboard:");
+                        ui.checkbox(&mut settings.open_clipboard_path, "Open copied image path")
+                            .on_hover_text(
+                                "If the clipboard holds the path of an image file, open it at launch",
+                            );
+                        ui.end_row();
+
+                        ui.label("Orientation:");
+                        ui.checkbox(&mut settings.auto_orient, "Rotate photos upright")
+                            .on_hover_text(
+                                "Apply the EXIF orientation a camera stored with the photo when opening it",
+                            );
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.heading("Workflow");
+                    if ui.small_button("Reset").clicked() {
+                        settings.reset_workflow();
+                    }
+                });
+                ui.checkbox(&mut settings.auto_advance, "Open next image after saving")
+                    .on_hover_text(
+                        "Keeps the current crop so a folder can be processed in sequence",
+                    );
+                ui.checkbox(
+                    &mut settings.apply_last_crop,
+                    "Apply last crop to new images",
+                )
+                .on_hover_text("Start each newly opened image with the previous crop");
+                ui.checkbox(&mut settings.allow_outside_crop, "Allow crop outside image")
+                    .on_hover_text("The area past the image edges is exported as transparent");
+                ui.horizontal(|ui| {
+                    ui.label("Auto-save every:");
+                    ui.add(
+                        egui::Slider::new(&mut settings.autosave_secs, 0..=300).custom_formatter(
+                            |v, _| {
+                                if v == 0.0 {
+                                    "off".to_owned()
+                                } else {
+                                    format!("{}s", v)
+                                }
+                            },
+                        ),
+                    )
+                    .on_hover_text(
+                        "Periodically save the crop and settings so a crash loses little work",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Face headroom:");
+                    ui.add(
+                        egui::Slider::new(&mut settings.face_headroom, 0.0..=1.5)
+                            .custom_formatter(|v, _| format!("{:.0}%", v * 100.0))
+                            .custom_parser(|s| {
+                                s.trim_end_matches('%')
+                                    .parse::<f64>()
+                                    .ok()
+                                    .map(|v| v / 100.0)
+                            }),
+                    )
+                    .on_hover_text(
+                        "Space above the face for Frame Face, relative to the face height",
+                    );
+                });
+                #[cfg(feature = "face-detect")]
+                ui.horizontal(|ui| {
+                    ui.label("Face model:");
+                    match &settings.face_model {
+                        Some(path) => {
+                            ui.label(path.file_name().unwrap_or_default().to_string_lossy())
+                                .on_hover_text(path.display().to_string());
+                            if ui.small_button("Forget").clicked() {
+                                settings.face_model = None;
+                            }
+                        }
+                        None => {
+                            ui.weak("asked for on first Detect Face");
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Ratio tolerance:");
+                    ui.add(
+                        egui::Slider::new(&mut settings.ratio_epsilon, 0.0..=0.05)
+                            .custom_formatter(|v, _| format!("{:.1}%", v * 100.0))
+                            .custom_parser(|s| {
+                                s.trim_end_matches('%')
+                                    .parse::<f64>()
+                                    .ok()
+                                    .map(|v| v / 100.0)
+                            }),
+                    )
+                    .on_hover_text(
+                        "How close a free crop must be to a preset to be labelled with it",
+                    );
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.heading("Export");
+                    if ui.small_button("Reset").clicked() {
+                        settings.reset_export();
+                    }
+                });
+                egui::Grid::new("settings_export")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("JPEG quality:");
+                        ui.add(egui::Slider::new(&mut settings.jpeg_quality, 1..=100));
+                        ui.end_row();
+
+                        ui.label("AVIF quality:");
+                        ui.add_enabled(
+                            cfg!(feature = "avif"),
+                            egui::Slider::new(&mut settings.avif_quality, 1..=100),
+                        )
+                        .on_disabled_hover_text("This build has no AVIF support");
+                        ui.end_row();
+
+                        ui.label("File name:");
+                        ui.vertical(|ui| {
+                            ui.text_edit_singleline(&mut settings.filename_template)
+                                .on_hover_text(format!(
+                                    "Suggested save name; tokens: {}",
+                                    filename::TOKENS.map(|t| format!("{{{}}}", t)).join(" ")
+                                ));
+                            if let Err(e) = filename::validate(&settings.filename_template) {
+                                ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Before/after gutter:");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.comparison_gutter)
+                                .range(0..=512)
+                                .suffix(" px"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Before/after background:");
+                        ui.color_edit_button_srgb(&mut settings.comparison_background);
+                        ui.end_row();
+
+                        ui.label("Before/after original:");
+                        ui.checkbox(&mut settings.comparison_outline, "Outline the crop");
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.heading("Appearance");
+                    if ui.small_button("Reset").clicked() {
+                        settings.reset_appearance();
+                    }
+                });
+                egui::Grid::new("settings_appearance")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Theme:");
+                        ui.horizontal(|ui| {
+                            ui.radio_value(
+                                &mut settings.theme,
+                                egui::ThemePreference::System,
+                                "System",
+                            );
+                            ui.radio_value(
+                                &mut settings.theme,
+                                egui::ThemePreference::Light,
+                                "Light",
+                            );
+                            ui.radio_value(
+                                &mut settings.theme,
+                                egui::ThemePreference::Dark,
+                                "Dark",
+                            );
+                        });
+                        ui.end_row();
+
+                        ui.label("Overlay opacity:");
+                        ui.add(egui::Slider::new(&mut settings.overlay_alpha, 0..=255));
+                        ui.end_row();
+
+                        ui.label("Handle size:");
+                        ui.add(
+                            egui::Slider::new(&mut settings.handle_radius, 3.0..=12.0)
+                                .suffix(" px"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Handles:");
+                        ui.checkbox(&mut settings.handles_on_hover, "Only near the pointer")
+                            .on_hover_text(
+                                "Hide the handles unless the pointer is over the crop or dragging it",
+                            );
+                        ui.end_row();
+
+                        ui.label("Canvas padding:");
+                        ui.add(
+                            egui::Slider::new(&mut settings.canvas_padding, 0.0..=60.0)
+                                .suffix(" px"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Crop size:");
+                        ui.checkbox(&mut settings.corner_readout, "Show in canvas corner");
+                        ui.end_row();
+
+                        ui.label("Crop border:");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut settings.contrast_border, "Contrast outline");
+                            ui.checkbox(&mut settings.anti_alias, "Anti-alias");
+                            ui.checkbox(&mut settings.marching_ants, "Marching ants")
+                                .on_hover_text(
+                                    "Animate the border as a dashed line; repaints continuously",
+                                );
+                        });
+                        ui.end_row();
+                    });
+            });
+    }
+}
+
+/// The preset whose ratio is closest to `ratio`, if it is within the relative
+/// tolerance `eps` (e.g. 0.01 for 1%).
+fn nearest_preset_ratio(ratio: f32, eps: f32) -> Option<AspectRatioMode> {
+    AspectRatioMode::ALL
+        .into_iter()
+        .filter_map(|mode| Some((mode, (ratio / mode.ratio()? - 1.0).abs())))
+        .filter(|&(_, error)| error <= eps)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(mode, _)| mode)
+}
+
+fn copy_text(text: &str) -> Result<(), CropperError> {
+    arboard::Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
+
+fn copy_image(image: &DynamicImage) -> Result<(), CropperError> {
+    arboard::Clipboard::new()?.set_image(clipboard_image(image))?;
+    Ok(())
+}
+
+/// Converts clipboard pixels back into an image; `None` if the buffer does
+/// not match the stated size.
+fn image_from_clipboard(data: arboard::ImageData) -> Option<DynamicImage> {
+    let rgba = image::RgbaImage::from_raw(
+        data.width as u32,
+        data.height as u32,
+        data.bytes.into_owned(),
+    )?;
+    Some(DynamicImage::ImageRgba8(rgba))
+}
+
+/// The image as the unpremultiplied RGBA pixels the clipboard expects.
+fn clipboard_image(image: &DynamicImage) -> arboard::ImageData<'static> {
+    let rgba = image.to_rgba8();
+    arboard::ImageData {
+        width: rgba.width() as usize,
+        height: rgba.height() as usize,
+        bytes: rgba.into_raw().into(),
+    }
+}
+
+/// Layout of the crop size readouts drawn on the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ReadoutStyle {
+    Corner, // `1920×1080 px · 16:9`, the always-on corner badge
+    Drag,   // `1920 × 1080 (16:9)`, under the crop while it is dragged
+}
+
+/// The crop's pixel size and ratio in `style`, naming the ratio after a
+/// preset when it is within `eps` of one.
+fn crop_readout(
+    width: u32,
+    height: u32,
+    rect: egui::Rect,
+    eps: f32,
+    style: ReadoutStyle,
+) -> String {
+    let (_, _, w, h) = crop_pixel_bounds(width, height, rect);
+    let ratio = w as f32 / h as f32;
+    let ratio = match nearest_preset_ratio(ratio, eps) {
+        Some(preset) => preset.to_string(),
+        None => format!("{:.2}:1", ratio),
+    };
+    match style {
+        ReadoutStyle::Corner => format!("{}×{} px · {}", w, h, ratio),
+        ReadoutStyle::Drag => format!("{} × {} ({})", w, h, ratio),
+    }
+}
+
+/// Width over height of a `w`:`h` custom ratio. A zero side counts as one,
+/// so the ratio is always finite and positive.
+fn custom_ratio(w: u32, h: u32) -> f32 {
+    w.max(1) as f32 / h.max(1) as f32
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// The image file named by clipboard `text`, as left there by screenshot
+/// tools: a single existing path (optionally a `file://` URL) with one of the
+/// supported image extensions.
+fn image_path_from_text(text: &str) -> Option<PathBuf> {
+    let text = text.trim();
+    if text.is_empty() || text.contains('\n') {
+        return None;
+    }
+    let path = expand_tilde(text.strip_prefix("file://").unwrap_or(text));
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    (IMAGE_EXTENSIONS.contains(&extension.as_str()) && path.is_file()).then_some(path)
+}
+
+/// Expands a leading `~` in a typed path to the user's home directory.
+fn expand_tilde(input: &str) -> PathBuf {
+    let home = || std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" });
+    match input.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(std::path::is_separator) => {
+            match home() {
+                Some(home) => {
+                    PathBuf::from(home).join(rest.trim_start_matches(std::path::is_separator))
+                }
+                None => PathBuf::from(input),
+            }
+        }
+        _ => PathBuf::from(input),
+    }
+}
+
+/// Whether the normalized `rect` selects every pixel of a `width`×`height` image.
+fn covers_full_image(width: u32, height: u32, rect: egui::Rect) -> bool {
+    crop_pixel_bounds(width, height, rect) == (0, 0, width, height)
+}
+
+/// The pixels selected by the normalized `rect`, with no file I/O. This is
+/// the one crop computation behind saving, copying, batches and exports;
+/// a rect reaching past the image is padded with transparency.
+fn compute_crop(image: &DynamicImage, rect: egui::Rect) -> DynamicImage {
+    let image_bounds = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+    if image_bounds.contains_rect(rect) {
+        crop_normalized(image, rect)
+    } else {
+        crop_padded(image, rect)
+    }
+}
+
+/// Crops the normalized `rect` (0.0-1.0) out of `image`, clamped to its bounds.
+fn crop_normalized(image: &DynamicImage, rect: egui::Rect) -> DynamicImage {
+    let (x, y, width, height) = crop_pixel_bounds(image.width(), image.height(), rect);
+    image.crop_imm(x, y, width, height)
+}
+
+/// Like `crop_normalized`, but `rect` may reach past the image; the area
+/// outside it is left transparent.
+fn crop_padded(image: &DynamicImage, rect: egui::Rect) -> DynamicImage {
+    let edge = |t: f32, size: u32| (t * size as f32).round() as i64;
+    let (x0, x1) = (
+        edge(rect.min.x, image.width()),
+        edge(rect.max.x, image.width()),
+    );
+    let (y0, y1) = (
+        edge(rect.min.y, image.height()),
+        edge(rect.max.y, image.height()),
+    );
+    let mut canvas = image::RgbaImage::new((x1 - x0).max(1) as u32, (y1 - y0).max(1) as u32);
+    image::imageops::overlay(&mut canvas, &image.to_rgba8(), -x0, -y0);
+    DynamicImage::ImageRgba8(canvas)
+}
+
+/// Output size for a crop of `size` scaled to `requested`. A zero side
+/// follows the crop's aspect from the other; both zero keeps `size`.
+fn scaled_size(size: (u32, u32), requested: (u32, u32)) -> (u32, u32) {
+    let scale = |side: u32, num: u32, den: u32| {
+        ((side as f64 * num as f64 / den as f64).round() as u32).max(1)
+    };
+    match requested {
+        (0, 0) => size,
+        (w, 0) => (w, scale(w, size.1, size.0)),
+        (0, h) => (scale(h, size.0, size.1), h),
+        requested => requested,
+    }
+}
+
+/// Points around the ellipse inscribed in `rect`, clockwise from the right.
+fn ellipse_points(rect: egui::Rect) -> Vec<egui::Pos2> {
+    const SEGMENTS: usize = 64;
+    (0..SEGMENTS)
+        .map(|i| {
+            let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+            rect.center() + rect.size() * 0.5 * egui::vec2(angle.cos(), angle.sin())
+        })
+        .collect()
+}
+
+/// Fills the four corners of `rect` that lie outside its inscribed ellipse.
+/// Each corner is a fan from the corner point over its quarter of the arc.
+fn ellipse_corners_mesh(rect: egui::Rect, color: egui::Color32) -> egui::Mesh {
+    let points = ellipse_points(rect);
+    let quarter = points.len() / 4;
+    let corners = [
+        rect.right_bottom(),
+        rect.left_bottom(),
+        rect.left_top(),
+        rect.right_top(),
+    ];
+    let mut mesh = egui::Mesh::default();
+    for (q, corner) in corners.into_iter().enumerate() {
+        let base = mesh.vertices.len() as u32;
+        mesh.colored_vertex(corner, color);
+        for i in 0..=quarter {
+            mesh.colored_vertex(points[(q * quarter + i) % points.len()], color);
+        }
+        for i in 0..quarter as u32 {
+            mesh.add_triangle(base, base + 1 + i, base + 2 + i);
+        }
+    }
+    mesh
+}
+
+/// Makes everything outside the ellipse inscribed in the image transparent,
+/// with a one-pixel soft edge. Cleared pixels are also blacked out so formats
+/// without alpha get black corners rather than the cut-away content.
+fn mask_ellipse(image: &DynamicImage) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let coverage = ellipse_coverage(x, y, w, h);
+        if coverage == 0.0 {
+            pixel.0 = [0; 4];
+        } else {
+            pixel.0[3] = (pixel.0[3] as f32 * coverage).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// How much of pixel (`x`, `y`) lies inside the ellipse inscribed in a
+/// `w` × `h` image, from 0 to 1 with a one-pixel soft edge.
+fn ellipse_coverage(x: u32, y: u32, w: u32, h: u32) -> f32 {
+    let (w, h) = (w as f32, h as f32);
+    let nx = (x as f32 + 0.5) / w * 2.0 - 1.0;
+    let ny = (y as f32 + 0.5) / h * 2.0 - 1.0;
+    // Approximate distance inside the edge, in pixels
+    let inside = (1.0 - (nx * nx + ny * ny).sqrt()) * w.min(h) / 2.0;
+    (inside + 0.5).clamp(0.0, 1.0)
+}
+
+/// The whole image with the pixels `rect` selects cleared, or only those
+/// inside its inscribed ellipse when `ellipse`. Like `mask_ellipse`,
+/// cleared pixels are blacked out as well as made transparent.
+fn clear_region(image: &DynamicImage, rect: egui::Rect, ellipse: bool) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let (left, top, w, h) = crop_pixel_bounds(rgba.width(), rgba.height(), rect);
+    for y in 0..h {
+        for x in 0..w {
+            let coverage = if ellipse {
+                ellipse_coverage(x, y, w, h)
+            } else {
+                1.0
+            };
+            let pixel = rgba.get_pixel_mut(left + x, top + y);
+            if coverage == 1.0 {
+                pixel.0 = [0; 4];
+            } else {
+                pixel.0[3] = (pixel.0[3] as f32 * (1.0 - coverage)).round() as u8;
+            }
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Mirrors a normalized rect across the image's vertical center line when
+/// `horizontal`, otherwise across the horizontal one.
+fn mirror_rect(rect: egui::Rect, horizontal: bool) -> egui::Rect {
+    if horizontal {
+        egui::Rect::from_x_y_ranges(1.0 - rect.max.x..=1.0 - rect.min.x, rect.y_range())
+    } else {
+        egui::Rect::from_x_y_ranges(rect.x_range(), 1.0 - rect.max.y..=1.0 - rect.min.y)
+    }
+}
+
+/// Zoom (relative to fit) and view center that make the pixels selected by
+/// `rect` fill the canvas. The canvas shares the image's aspect ratio, so the
+/// limiting side alone decides the zoom.
+fn crop_view(width: u32, height: u32, rect: egui::Rect) -> (f32, egui::Pos2) {
+    let (x, y, w, h) = crop_pixel_bounds(width, height, rect);
+    let zoom = (width as f32 / w as f32).min(height as f32 / h as f32);
+    let center = egui::pos2(
+        (x as f32 + w as f32 / 2.0) / width as f32,
+        (y as f32 + h as f32 / 2.0) / height as f32,
+    );
+    (zoom, center)
+}
+
+/// Normalized rect for a pixel crop `(x, y, width, height)`, clamped like
+/// `crop_pixel_bounds`: x and y inside the image and the size within what is
+/// left past them. With a `ratio`, a size that does not fit shrinks as a
+/// whole so the ratio survives the clamp.
+fn pixel_crop_rect(
+    image_width: u32,
+    image_height: u32,
+    (x, y, width, height): (u32, u32, u32, u32),
+    ratio: Option<f32>,
+) -> egui::Rect {
+    let x = x.min(image_width - 1);
+    let y = y.min(image_height - 1);
+    let (room_w, room_h) = ((image_width - x) as f32, (image_height - y) as f32);
+    let mut size = egui::vec2(width.max(1) as f32, height.max(1) as f32);
+    if ratio.is_some() {
+        size *= (room_w / size.x).min(room_h / size.y).min(1.0);
+    }
+    let size = egui::vec2(size.x.clamp(1.0, room_w), size.y.clamp(1.0, room_h));
+    let image_size = egui::vec2(image_width as f32, image_height as f32);
+    egui::Rect::from_min_size(
+        (egui::vec2(x as f32, y as f32) / image_size).to_pos2(),
+        size / image_size,
+    )
+}
+
+/// Converts a normalized crop rect to `(x, y, width, height)` in pixels of an
+/// `image_width` × `image_height` image, clamped to its bounds.
+fn crop_pixel_bounds(
+    image_width: u32,
+    image_height: u32,
+    rect: egui::Rect,
+) -> (u32, u32, u32, u32) {
+    // Round both edges and take the size from their difference, so an edge
+    // always lands on the same pixel regardless of where the other one is
+    let edges = |min: f32, max: f32, size: u32| {
+        let start = ((min * size as f32).round().max(0.0) as u32).min(size - 1);
+        let end = ((max * size as f32).round().max(0.0) as u32).clamp(start + 1, size);
+        (start, end - start)
+    };
+
+    let (x, width) = edges(rect.min.x, rect.max.x, image_width);
+    let (y, height) = edges(rect.min.y, rect.max.y, image_height);
+
+    (x, y, width, height)
+}
+
+impl std::fmt::Display for AspectRatioMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AspectRatioMode::Free => "Free",
+            AspectRatioMode::Original => "Original",
+            AspectRatioMode::Square => "1:1",
+            AspectRatioMode::R3_2 => "3:2",
+            AspectRatioMode::R4_3 => "4:3",
+            AspectRatioMode::R16_9 => "16:9",
+            AspectRatioMode::R16_10 => "16:10",
+            AspectRatioMode::R2_3 => "2:3",
+            AspectRatioMode::R3_4 => "3:4",
+            AspectRatioMode::R9_16 => "9:16",
+            AspectRatioMode::R10_16 => "10:16",
+            AspectRatioMode::Golden => "Golden",
+            AspectRatioMode::GoldenPortrait => "Golden Portrait",
+            AspectRatioMode::R7_5 => "7:5",
+            AspectRatioMode::R5_7 => "5:7",
+            AspectRatioMode::R10_8 => "10:8",
+            AspectRatioMode::R8_10 => "8:10",
+            AspectRatioMode::IsoA => "A Paper",
+            AspectRatioMode::IsoAPortrait => "A Paper Portrait",
+            AspectRatioMode::Custom => "Custom",
+            AspectRatioMode::FixedArea => "Fixed Area",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl ImageCropper {
+    /// Runs one frame of the UI. Kept apart from `update` so tests can drive
+    /// it with raw input, as an `eframe::Frame` can't be made outside eframe.
+    fn show(&mut self, ctx: &egui::Context) {
+        self.poll_loading(ctx);
+        self.poll_smallest_export();
+        self.update_size_estimate(ctx);
+        self.poll_template_batch();
+
+        let settings_before = self.settings.clone();
+        ctx.tessellation_options_mut(|options| options.feathering = self.settings.anti_alias);
+
+        // Holding Space temporarily hides the dim overlay and handles to peek at the full image
+        let peeking = !ctx.wants_keyboard_input() && ctx.input(|i| i.key_down(egui::Key::Space));
+
+        // L toggles between freehand and the last locked ratio
+        if self.aspect_ratio_mode != AspectRatioMode::Free {
+            self.last_constrained = self.aspect_ratio_mode;
+        }
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::L)) {
+            self.toggle_free_ratio();
+        }
+
+        // Ctrl+Z undoes a crop edit; Ctrl+Shift+Z or Ctrl+Y redoes it. Redo is
+        // checked first because Ctrl+Z also matches with Shift held.
+        if !ctx.wants_keyboard_input() {
+            let shortcut = |modifiers, key| egui::KeyboardShortcut::new(modifiers, key);
+            let redo_shift = shortcut(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            );
+            if ctx.input_mut(|i| {
+                i.consume_shortcut(&redo_shift)
+                    || i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Y))
+            }) {
+                self.redo_crop();
+            } else if ctx.input_mut(|i| {
+                i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Z))
+            }) {
+                self.undo_crop();
+            }
+
+            // Ctrl+O opens an image and Ctrl+S saves the crop, as in other editors
+            if ctx.input_mut(|i| {
+                i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::O))
+            }) {
+                self.open_dialog(ctx);
+            }
+            if self.image.is_some()
+                && ctx.input_mut(|i| {
+                    i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::S))
+                })
+            {
+                self.save_cropped(ctx);
+            }
+        }
+
+        // Arrow keys nudge the crop by a pixel, or by ten with Shift
+        if self.crop_rect.is_some()
+            && !self.crop_locked
+            && !ctx.wants_keyboard_input()
+            && !ctx.input(|i| i.pointer.any_down())
+        {
+            let delta = ctx.input(|i| {
+                let step = if i.modifiers.shift { 10.0 } else { 1.0 };
+                [
+                    (egui::Key::ArrowLeft, egui::vec2(-1.0, 0.0)),
+                    (egui::Key::ArrowRight, egui::vec2(1.0, 0.0)),
+                    (egui::Key::ArrowUp, egui::vec2(0.0, -1.0)),
+                    (egui::Key::ArrowDown, egui::vec2(0.0, 1.0)),
+                ]
+                .into_iter()
+                .filter(|(key, _)| i.key_pressed(*key))
+                .map(|(_, direction)| direction * step)
+                .fold(egui::Vec2::ZERO, |sum, delta| sum + delta)
+            });
+            if delta != egui::Vec2::ZERO {
+                self.move_crop(ResizeHandle::Center, delta);
+            }
+        }
+
+        // Ctrl+V pastes an image. Nothing to paste is not worth an error
+        // here, unlike a click on the Paste button.
+        let paste = ctx.input(|i| Self::paste_shortcut(&i.events, &mut self.v_key_seen));
+        if paste && !ctx.wants_keyboard_input() {
+            match self.paste_image(ctx) {
+                Ok(()) | Err(CropperError::NoClipboardImage) => {}
+                Err(e) => self.error = Some(e),
+            }
+        }
+
         // Handle dropped files
         if !ctx.input(|i| i.raw.dropped_files.is_empty()) {
             let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
-            if let Some(file) = dropped_files.first() {
-                if let Some(path) = &file.path {
-                    if let Ok(img) = image::open(path) {
-                        self.image = Some(img);
-                        self.load_texture(ctx);
+            if let Some(file) = dropped_files.first()
+                && let Some(path) = &file.path
+            {
+                self.start_open(ctx, path.clone());
+            }
+        }
+
+        self.show_error_dialog(ctx);
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            if let Some((name, _)) = &self.loading {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Loading {name}…"));
+                });
+            } else {
+                ui.label(self.status_line());
+            }
+        });
+
+        if self.settings.show_preview {
+            self.update_preview(ctx);
+            if let Some((_, _, texture)) = &self.preview {
+                egui::SidePanel::right("preview")
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        ui.label("Result");
+                        ui.image((texture.id(), texture.size_vec2()));
+                    });
+            }
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Open Image")
+                    .on_hover_text("Open an image file (Ctrl+O)")
+                    .clicked()
+                {
+                    self.open_dialog(ctx);
+                }
+                ui.add_enabled_ui(!self.recent_files.is_empty(), |ui| {
+                    ui.menu_button("Recent", |ui| {
+                        let paths: Vec<PathBuf> =
+                            self.recent_files.paths().map(Path::to_path_buf).collect();
+                        for path in paths {
+                            let name = path.file_name().map_or_else(
+                                || path.display().to_string(),
+                                |name| name.to_string_lossy().into_owned(),
+                            );
+                            if ui
+                                .button(name)
+                                .on_hover_text(path.display().to_string())
+                                .clicked()
+                            {
+                                ui.close_menu();
+                                if path.is_file() {
+                                    self.start_open(ctx, path);
+                                } else {
+                                    // Files that were moved or deleted leave the list
+                                    self.recent_files.remove(&path);
+                                    self.error = Some(CropperError::NotAFile(path));
+                                }
+                            }
+                        }
+                    });
+                });
+                if ui
+                    .button("Paste")
+                    .on_hover_text("Crop the image on the clipboard (Ctrl+V)")
+                    .clicked()
+                    && let Err(e) = self.paste_image(ctx)
+                {
+                    self.error = Some(e);
+                }
+
+                let field = ui.add(
+                    egui::TextEdit::singleline(&mut self.path_input)
+                        .hint_text("or paste a path…")
+                        .desired_width(180.0),
+                );
+                let entered = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if (ui.button("Load").clicked() || entered) && !self.path_input.trim().is_empty() {
+                    let path = expand_tilde(self.path_input.trim());
+                    if path.is_file() {
+                        self.start_open(ctx, path);
+                        self.path_input.clear();
+                    } else {
+                        self.error = Some(CropperError::NotAFile(path));
+                    }
+                }
+
+                if self.image.is_some() {
+                    if ui.button("⟲").on_hover_text("Rotate Left").clicked() {
+                        self.rotate_image(ctx, false);
+                    }
+                    if ui.button("⟳").on_hover_text("Rotate Right").clicked() {
+                        self.rotate_image(ctx, true);
+                    }
+                    if ui.button("⇔").on_hover_text("Flip Horizontal").clicked() {
+                        self.flip_image(ctx, true);
+                    }
+                    if ui.button("⇕").on_hover_text("Flip Vertical").clicked() {
+                        self.flip_image(ctx, false);
+                    }
+                }
+
+                if self.image_path.is_some() {
+                    if ui
+                        .add_enabled(self.sibling_path(-1).is_some(), egui::Button::new("◀"))
+                        .on_hover_text("Previous image in folder")
+                        .clicked()
+                    {
+                        self.open_sibling(ctx, -1);
+                    }
+                    if ui
+                        .add_enabled(self.sibling_path(1).is_some(), egui::Button::new("▶"))
+                        .on_hover_text("Next image in folder")
+                        .clicked()
+                    {
+                        self.open_sibling(ctx, 1);
+                    }
+                }
+
+                if ui
+                    .selectable_label(self.show_settings, "⚙ Settings")
+                    .clicked()
+                {
+                    self.show_settings = !self.show_settings;
+                }
+
+                if let Some(image) = &self.image {
+                    ui.separator();
+                    let image_size = format!("{}x{}", image.width(), image.height());
+                    let crop_size = self.crop_rect.map(|rect| {
+                        let (_, _, w, h) = crop_pixel_bounds(image.width(), image.height(), rect);
+                        (
+                            format!("{}x{}", w, h),
+                            w as f32 * h as f32 / 1_000_000.0,
+                            w as f32 / h as f32,
+                        )
+                    });
+                    // Name the ratio a free-form crop happens to land on
+                    let show_ratio = matches!(
+                        self.aspect_ratio_mode,
+                        AspectRatioMode::Free | AspectRatioMode::FixedArea
+                    );
+
+                    let mut copy = None;
+                    let info = match &crop_size {
+                        Some((crop_size, megapixels, ratio)) => {
+                            let mut info = format!(
+                                "{} px · Crop {} px ({:.2} MP)",
+                                image_size, crop_size, megapixels
+                            );
+                            if show_ratio
+                                && let Some(preset) =
+                                    nearest_preset_ratio(*ratio, self.settings.ratio_epsilon)
+                            {
+                                info.push_str(&format!(" ~{}", preset));
+                            }
+                            if let Some(estimate) = self.size_estimate_label() {
+                                info.push_str(&format!(" · {}", estimate));
+                            }
+                            info
+                        }
+                        None => format!("{} px", image_size),
+                    };
+                    ui.label(info).context_menu(|ui| {
+                        if ui.button("Copy image size").clicked() {
+                            copy = Some(image_size.clone());
+                            ui.close_menu();
+                        }
+                        if let Some((crop_size, ..)) = &crop_size
+                            && ui.button("Copy crop size").clicked()
+                        {
+                            copy = Some(crop_size.clone());
+                            ui.close_menu();
+                        }
+                    });
+                    if ui
+                        .small_button("📋")
+                        .on_hover_text("Copy image size (right-click the size for more)")
+                        .clicked()
+                    {
+                        copy = Some(image_size);
+                    }
+
+                    if let Some(text) = copy
+                        && let Err(e) = copy_text(&text)
+                    {
+                        self.error = Some(e);
+                    }
+                }
+            });
+
+            if self.texture.is_some() {
+                ui.horizontal(|ui| {
+                    ui.label("Aspect Ratio:");
+                    let mut changed = false;
+                    egui::ComboBox::from_id_salt("params_aspect_ratio")
+                        .selected_text(format!("{}", self.aspect_ratio_mode))
+                        .show_ui(ui, |ui| {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.aspect_ratio_mode,
+                                    AspectRatioMode::Free,
+                                    "Free",
+                                )
+                                .changed();
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.aspect_ratio_mode,
+                                    AspectRatioMode::Original,
+                                    "Original",
+                                )
+                                .changed();
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.aspect_ratio_mode,
+                                    AspectRatioMode::Square,
+                                    "1:1",
+                                )
+                                .changed();
+
+                            ui.separator();
+                            if !self.is_portrait {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R3_2,
+                                        "3:2",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R4_3,
+                                        "4:3",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R16_9,
+                                        "16:9",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R16_10,
+                                        "16:10",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::Golden,
+                                        "Golden",
+                                    )
+                                    .changed();
+                            } else {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R2_3,
+                                        "2:3",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R3_4,
+                                        "3:4",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R9_16,
+                                        "9:16",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::R10_16,
+                                        "10:16",
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.aspect_ratio_mode,
+                                        AspectRatioMode::GoldenPortrait,
+                                        "Golden",
+                                    )
+                                    .changed();
+                            }
+
+                            // Print sizes follow the same orientation
+                            ui.separator();
+                            ui.weak("Print");
+                            let print = if self.is_portrait {
+                                [
+                                    (AspectRatioMode::R5_7, "5:7"),
+                                    (AspectRatioMode::R8_10, "8:10"),
+                                    (AspectRatioMode::IsoAPortrait, "A4 / A-series"),
+                                ]
+                            } else {
+                                [
+                                    (AspectRatioMode::R7_5, "7:5"),
+                                    (AspectRatioMode::R10_8, "10:8"),
+                                    (AspectRatioMode::IsoA, "A4 / A-series"),
+                                ]
+                            };
+                            for (mode, label) in print {
+                                changed |= ui
+                                    .selectable_value(&mut self.aspect_ratio_mode, mode, label)
+                                    .changed();
+                            }
+
+                            ui.separator();
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.aspect_ratio_mode,
+                                    AspectRatioMode::Custom,
+                                    "Custom",
+                                )
+                                .changed();
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.aspect_ratio_mode,
+                                    AspectRatioMode::FixedArea,
+                                    "Fixed Area",
+                                )
+                                .changed();
+                        })
+                        .response
+                        .on_hover_text("Press L to toggle between Free and the last locked ratio");
+
+                    if ui
+                        .add_enabled(self.can_swap_orientation(), egui::Button::new("🔄"))
+                        .on_hover_text("Swap between landscape and portrait")
+                        .on_disabled_hover_text("This ratio is the same either way round")
+                        .clicked()
+                    {
+                        self.flip_orientation();
+                    }
+
+                    let monitor = ctx.input(|i| {
+                        let viewport = i.viewport();
+                        let size = viewport.monitor_size? * viewport.native_pixels_per_point?;
+                        Some((size.x.round() as u32, size.y.round() as u32))
+                    });
+                    if let Some((width, height)) = monitor
+                        && ui
+                            .button("🖥")
+                            .on_hover_text(format!(
+                                "Crop for a wallpaper on this monitor ({}×{})",
+                                width, height
+                            ))
+                            .clicked()
+                    {
+                        self.match_monitor(width, height);
+                    }
+
+                    ui.toggle_value(&mut self.circular, "◯").on_hover_text(
+                        "Circular crop: outside the inscribed ellipse is transparent",
+                    );
+                    ui.toggle_value(&mut self.mask_out, "Invert").on_hover_text(
+                        "Mask out: save the whole image with the crop cleared, e.g. to redact it",
+                    );
+                    ui.toggle_value(&mut self.crop_locked, "🔒")
+                        .on_hover_text("Lock the crop so dragging can't move or resize it");
+
+                    if self.aspect_ratio_mode == AspectRatioMode::Custom {
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut self.custom_w)
+                                    .speed(1.0)
+                                    .range(1..=10000),
+                            )
+                            .changed();
+                        ui.label(":");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut self.custom_h)
+                                    .speed(1.0)
+                                    .range(1..=10000),
+                            )
+                            .changed();
+                    }
+
+                    if self.aspect_ratio_mode == AspectRatioMode::FixedArea {
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut self.target_megapixels)
+                                    .speed(0.05)
+                                    .range(0.01..=1000.0)
+                                    .suffix(" MP"),
+                            )
+                            .changed();
+                    }
+
+                    let mut locked = self.aspect_lock.is_some();
+                    if ui
+                        .checkbox(&mut locked, "Aspect lock")
+                        .on_hover_text("Keep the crop's current proportions while resizing it")
+                        .changed()
+                    {
+                        self.set_aspect_lock(locked);
+                    }
+
+                    if changed {
+                        // Picking a ratio means that one should apply
+                        self.aspect_lock = None;
+                        self.apply_aspect_ratio();
+                    }
+
+                    ui.separator();
+                    ui.label("Tool:");
+                    let previous_tool = self.tool;
+                    for tool in Tool::ALL {
+                        ui.selectable_value(&mut self.tool, tool, tool.to_string())
+                            .on_hover_text(tool.description());
+                    }
+                    if self.tool != previous_tool
+                        && self.tool == Tool::Perspective
+                        && let Some(crop_rect) = self.crop_rect
+                    {
+                        self.quad = Self::rect_corners(crop_rect);
+                    }
+                    ui.separator();
+
+                    if ui
+                        .button("✂ Crop")
+                        .on_hover_text("Replace the working image with the crop to crop again")
+                        .clicked()
+                        && let Err(e) = self.bake_crop(ctx)
+                    {
+                        self.error = Some(e);
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.transform_history.is_empty(),
+                            egui::Button::new("↶ Revert"),
+                        )
+                        .on_hover_text(
+                            "Restore the image and crop from before the last Crop or rotation",
+                        )
+                        .clicked()
+                    {
+                        self.undo_transform(ctx);
+                    }
+
+                    if ui
+                        .button("Save Cropped Image")
+                        .on_hover_text("Save the crop to a file (Ctrl+S)")
+                        .clicked()
+                    {
+                        self.save_cropped(ctx);
+                    }
+                    egui::ComboBox::from_id_salt("save_format")
+                        .selected_text(self.save_format.to_string())
+                        .width(70.0)
+                        .show_ui(ui, |ui| {
+                            for format in export::SaveFormat::ALL {
+                                ui.selectable_value(
+                                    &mut self.save_format,
+                                    format,
+                                    format.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Format to save in; the extension is added if the name lacks it",
+                        );
+
+                    if ui
+                        .button("Export as ICO")
+                        .on_hover_text("Write a 16/32/48/256 px icon; non-square crops are padded")
+                        .clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Icon", &["ico"])
+                            .save_file()
+                        && let Err(e) = self
+                            .check_not_source(&path)
+                            .and_then(|()| self.cropped_image())
+                            .and_then(|cropped| {
+                                export::save_ico(&cropped, &export::ICO_SIZES, &path)
+                            })
+                    {
+                        self.error = Some(e);
+                    }
+
+                    if ui
+                        .button("Save Before/After")
+                        .on_hover_text(
+                            "Write the original and the crop side by side (see Settings)",
+                        )
+                        .clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("PNG", &["png"])
+                            .add_filter("JPEG", &["jpg", "jpeg"])
+                            .save_file()
+                        && let Err(e) = self
+                            .check_not_source(&path)
+                            .and_then(|()| self.comparison_image())
+                            .and_then(|image| {
+                                export::save_image_with_profile(
+                                    &image,
+                                    &path,
+                                    self.export_icc_profile(),
+                                    self.settings.jpeg_quality,
+                                )
+                            })
+                    {
+                        self.error = Some(e);
+                    }
+
+                    if ui
+                        .button("Copy to Clipboard")
+                        .on_hover_text("Copy the cropped pixels for pasting into another app")
+                        .clicked()
+                    {
+                        let result = self.output_image().and_then(|output| {
+                            copy_image(&output)?;
+                            Ok((output.width(), output.height()))
+                        });
+                        match result {
+                            Ok((w, h)) => {
+                                self.export_status = Some(format!("Copied {}×{} image", w, h))
+                            }
+                            Err(e) => self.error = Some(e),
+                        }
+                    }
+
+                    if ui
+                        .button("Copy as Data URI")
+                        .on_hover_text("Copy a base64 PNG data: URI for pasting into HTML or CSS")
+                        .clicked()
+                    {
+                        let result = self.output_image().and_then(|output| {
+                            let uri = export::data_uri(&output, image::ImageFormat::Png)?;
+                            copy_text(&uri)?;
+                            Ok(uri.len())
+                        });
+                        match result {
+                            Ok(len) => {
+                                self.export_status =
+                                    Some(format!("Copied data URI ({})", export::format_size(len)))
+                            }
+                            Err(e) => self.error = Some(e),
+                        }
+                    }
+
+                    let exporting = self.smallest_export.is_some();
+                    if ui
+                        .add_enabled(!exporting, egui::Button::new("Save Smallest File"))
+                        .on_hover_text("Encode as PNG, JPEG and WebP and keep the smallest")
+                        .clicked()
+                        && let Some(path) = rfd::FileDialog::new().save_file()
+                    {
+                        self.start_smallest_export(ctx, path);
+                    }
+
+                    if exporting {
+                        ui.spinner();
+                    }
+                    if let Some(status) = &self.export_status {
+                        ui.label(status);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Output Size:");
+                    let mut changed = false;
+                    egui::ComboBox::from_id_salt("params_size_preset")
+                        .selected_text(format!("{}", self.size_preset))
+                        .show_ui(ui, |ui| {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.size_preset,
+                                    SizePreset::Original,
+                                    "Original",
+                                )
+                                .changed();
+                            ui.separator();
+                            for size in SizePreset::SQUARE_SIZES {
+                                let preset = SizePreset::Square(size);
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.size_preset,
+                                        preset,
+                                        preset.to_string(),
+                                    )
+                                    .changed();
+                            }
+                            ui.separator();
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.size_preset,
+                                    SizePreset::Favicon,
+                                    "Favicon (.ico)",
+                                )
+                                .changed();
+                            ui.separator();
+                            ui.selectable_value(
+                                &mut self.size_preset,
+                                SizePreset::Custom,
+                                "Custom",
+                            );
+                        });
+
+                    // Fixed sizes are all square, so lock the crop to match
+                    if changed
+                        && matches!(
+                            self.size_preset,
+                            SizePreset::Square(_) | SizePreset::Favicon
+                        )
+                    {
+                        self.aspect_ratio_mode = AspectRatioMode::Square;
+                        self.aspect_lock = None;
+                        self.apply_aspect_ratio();
+                    }
+
+                    if self.size_preset == SizePreset::Custom {
+                        let (width, height) = &mut self.output_size;
+                        ui.add(
+                            egui::DragValue::new(width)
+                                .range(0..=16384)
+                                .custom_formatter(|n, _| match n {
+                                    0.0 => "auto".to_owned(),
+                                    n => format!("{}", n),
+                                }),
+                        )
+                        .on_hover_text("Output width in pixels; auto follows the crop's aspect");
+                        ui.label("×");
+                        ui.add(
+                            egui::DragValue::new(height)
+                                .range(0..=16384)
+                                .custom_formatter(|n, _| match n {
+                                    0.0 => "auto".to_owned(),
+                                    n => format!("{}", n),
+                                }),
+                        )
+                        .on_hover_text("Output height in pixels; auto follows the crop's aspect");
+                        egui::ComboBox::from_id_salt("params_resize_filter")
+                            .selected_text(self.resize_filter.to_string())
+                            .show_ui(ui, |ui| {
+                                for filter in ResizeFilter::ALL {
+                                    ui.selectable_value(
+                                        &mut self.resize_filter,
+                                        filter,
+                                        filter.to_string(),
+                                    );
+                                }
+                            });
+                    }
+
+                    ui.separator();
+                    let hover = if self.icc_profile.is_some() {
+                        "Embed the source ICC profile in PNG, JPEG and WebP exports"
+                    } else {
+                        "This image has no embedded color profile"
+                    };
+                    ui.add_enabled(
+                        self.icc_profile.is_some(),
+                        egui::Checkbox::new(
+                            &mut self.settings.preserve_icc_profile,
+                            "Preserve color profile",
+                        ),
+                    )
+                    .on_hover_text(hover)
+                    .on_disabled_hover_text(hover);
+                    ui.checkbox(&mut self.settings.protect_source, "Protect source")
+                        .on_hover_text("Refuse to save over the image that is open");
+                });
+
+                if self.tool == Tool::Crop {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(self.crop_history.can_undo(), egui::Button::new("↶"))
+                            .on_hover_text("Undo crop edit (Ctrl+Z)")
+                            .clicked()
+                        {
+                            self.undo_crop();
+                        }
+                        if ui
+                            .add_enabled(self.crop_history.can_redo(), egui::Button::new("↷"))
+                            .on_hover_text("Redo crop edit (Ctrl+Shift+Z)")
+                            .clicked()
+                        {
+                            self.redo_crop();
+                        }
+                        ui.separator();
+
+                        ui.label("Adjust:");
+                        for (name, handle, outward) in [
+                            ("Left", ResizeHandle::Left, egui::vec2(-1.0, 0.0)),
+                            ("Right", ResizeHandle::Right, egui::vec2(1.0, 0.0)),
+                            ("Top", ResizeHandle::Top, egui::vec2(0.0, -1.0)),
+                            ("Bottom", ResizeHandle::Bottom, egui::vec2(0.0, 1.0)),
+                        ] {
+                            ui.label(name);
+                            if ui.button("−").clicked() {
+                                self.step_crop(handle, -outward);
+                            }
+                            if ui.button("+").clicked() {
+                                self.step_crop(handle, outward);
+                            }
+                            ui.separator();
+                        }
+
+                        ui.label("Move");
+                        for (arrow, direction) in [
+                            ("⬅", egui::vec2(-1.0, 0.0)),
+                            ("➡", egui::vec2(1.0, 0.0)),
+                            ("⬆", egui::vec2(0.0, -1.0)),
+                            ("⬇", egui::vec2(0.0, 1.0)),
+                        ] {
+                            if ui.button(arrow).clicked() {
+                                self.step_crop(ResizeHandle::Center, direction);
+                            }
+                        }
+
+                        ui.separator();
+                        if ui
+                            .button("Snap & Maximize")
+                            .on_hover_text(
+                                "Lock to the preset nearest the current shape and grow the crop \
+                                 as far as it goes around the same center",
+                            )
+                            .clicked()
+                        {
+                            self.snap_and_maximize();
+                        }
+                        if ui
+                            .button("Lock Current")
+                            .on_hover_text(
+                                "Keep the crop's exact current shape as a custom ratio for \
+                                 further resizing",
+                            )
+                            .clicked()
+                        {
+                            self.lock_current_ratio();
+                        }
+                        if ui
+                            .button("Frame Face")
+                            .on_hover_text(
+                                "Draw the crop tightly around a face, then click to expand it \
+                                 into a headshot in the current ratio (headroom in Settings)",
+                            )
+                            .clicked()
+                        {
+                            self.frame_face();
+                        }
+                        #[cfg(feature = "face-detect")]
+                        if ui
+                            .button("Detect Face")
+                            .on_hover_text(
+                                "Find the largest face and frame it as a headshot in the current \
+                                 ratio; adjust the crop afterwards as needed",
+                            )
+                            .clicked()
+                        {
+                            self.detect_face();
+                        }
+                    });
+                }
+
+                if self.tool == Tool::Crop {
+                    self.show_straighten_row(ui);
+                    self.show_redact_row(ui);
+                    self.show_pixel_fields(ui);
+                    self.show_crop_presets_row(ui);
+                    self.show_regions_row(ui);
+                    self.show_multi_ratio_row(ui);
+                }
+
+                if self.tool == Tool::Crop
+                    && let Some(image) = &self.image
+                    && self.recent_crops.len() > 1
+                {
+                    let mut apply = None;
+                    ui.horizontal(|ui| {
+                        ui.label("Recent:");
+                        for rect in &self.recent_crops {
+                            let (x, y, w, h) =
+                                crop_pixel_bounds(image.width(), image.height(), *rect);
+                            let button = ui
+                                .selectable_label(
+                                    self.crop_rect == Some(*rect),
+                                    format!("{}x{}", w, h),
+                                )
+                                .on_hover_text(format!("At {}, {}", x, y));
+                            if button.clicked() {
+                                apply = Some(*rect);
+                            }
+                        }
+                    });
+                    if apply.is_some() {
+                        self.crop_rect = apply;
+                        self.record_crop();
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Template:");
+                    if ui
+                        .add_enabled(self.tool == Tool::Crop, egui::Button::new("Save Template"))
+                        .on_hover_text("Remember the current crop to reuse on other images")
+                        .clicked()
+                    {
+                        self.crop_template = self.crop_rect;
+                    }
+
+                    let running = self.batch_job.is_some();
+                    let can_apply =
+                        self.crop_template.is_some() && self.image_path.is_some() && !running;
+                    if ui
+                        .add_enabled(can_apply, egui::Button::new("Apply to Folder"))
+                        .on_hover_text(
+                            "Crop every image in this folder, named by the filename template",
+                        )
+                        .clicked()
+                    {
+                        self.start_template_batch(ctx);
+                    }
+                    if ui
+                        .add_enabled(
+                            self.tool == Tool::Crop && self.crop_rect.is_some() && !running,
+                            egui::Button::new("Batch…"),
+                        )
+                        .on_hover_text(
+                            "Crop other images at the same place into a chosen folder, named by \
+                             the filename template",
+                        )
+                        .clicked()
+                    {
+                        self.start_files_batch(ctx);
+                    }
+
+                    if let Some((done, total)) = &self.batch_progress {
+                        let done = done.load(Ordering::Relaxed);
+                        ui.add(
+                            egui::ProgressBar::new(done as f32 / (*total).max(1) as f32)
+                                .desired_width(120.0)
+                                .text(format!("{} / {}", done, total)),
+                        );
+                        ctx.request_repaint_after_secs(0.1);
+                    } else if running {
+                        ui.spinner();
+                    }
+                    if let Some((summary, details)) = &self.batch_status {
+                        let label = ui.label(summary);
+                        if !details.is_empty() {
+                            label.on_hover_text(details);
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Crop data:");
+                    if ui
+                        .add_enabled(self.crop_rect.is_some(), egui::Button::new("Export…"))
+                        .on_hover_text(
+                            "Write the crop and ratio as JSON so other tools can re-apply it",
+                        )
+                        .clicked()
+                        && let Err(e) = self.export_crop_data()
+                    {
+                        self.error = Some(e);
+                    }
+                    if ui
+                        .add_enabled(self.image.is_some(), egui::Button::new("Import…"))
+                        .on_hover_text("Restore a crop and ratio from an exported JSON file")
+                        .clicked()
+                        && let Err(e) = self.import_crop_data()
+                    {
+                        self.error = Some(e);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("View:");
+                    ui.label("Zoom:");
+                    if ui.button("−").clicked() {
+                        self.set_zoom(self.zoom / ZOOM_STEP);
+                    }
+                    ui.label(format!("{:.0}%", self.zoom * 100.0));
+                    if ui.button("+").clicked() {
+                        self.set_zoom(self.zoom * ZOOM_STEP);
+                    }
+                    if ui
+                        .selectable_label(self.zoom == 1.0 && !self.actual_pixels, "Fit")
+                        .on_hover_text("Show the whole image")
+                        .clicked()
+                    {
+                        self.set_zoom(1.0);
+                    }
+                    if ui
+                        .selectable_label(self.actual_pixels, "Actual Size")
+                        .on_hover_text(
+                            "One image pixel per screen point; scroll or middle-drag to look around",
+                        )
+                        .clicked()
+                    {
+                        if self.actual_pixels {
+                            self.set_zoom(1.0);
+                        } else {
+                            self.zoom_actual_size();
+                        }
+                    }
+                    if ui
+                        .button("Zoom to Crop")
+                        .on_hover_text(
+                            "Fill the canvas with exactly the pixels that will be exported",
+                        )
+                        .clicked()
+                    {
+                        self.zoom_to_crop();
+                    }
+                    ui.separator();
+                    ui.toggle_value(&mut self.settings.show_overlays, "Overlays")
+                        .on_hover_text(
+                            "Show the dim, badges and labels; the border and handles stay",
+                        );
+                    egui::ComboBox::from_id_salt("grid_mode")
+                        .selected_text(self.settings.grid_mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for mode in GridMode::ALL {
+                                ui.selectable_value(
+                                    &mut self.settings.grid_mode,
+                                    mode,
+                                    mode.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("Composition guides inside the crop; not saved");
+                    ui.checkbox(&mut self.settings.show_preview, "Preview")
+                        .on_hover_text("Show a thumbnail of the cropped result beside the canvas");
+                    ui.checkbox(&mut self.settings.contrast_border, "Contrast Border")
+                        .on_hover_text(
+                            "Outline the crop border in black so it stays visible on light images",
+                        );
+                    ui.checkbox(&mut self.settings.anti_alias, "Anti-alias")
+                        .on_hover_text("Smooth the border and handles; turn off for crisp lines");
+                });
+
+                ui.separator();
+            }
+
+            let target_ratio = self.target_ratio();
+            let fixed_area = self.fixed_area();
+            let bounds = self.crop_bounds();
+            let mut select_all = false;
+            if let (Some(texture), Some(crop_rect), Some(image)) =
+                (&self.texture, &mut self.crop_rect, &self.image)
+            {
+                let padding = self.settings.canvas_padding;
+                let available_size = ui.available_size();
+                let max_size = available_size - egui::vec2(padding * 2.0, padding * 2.0);
+                // The texture may be downscaled, so sizes come from the source
+                let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+
+                // Calculate size to fit within available space while maintaining aspect ratio
+                let mut scale = (max_size.x / image_size.x).min(max_size.y / image_size.y);
+                self.fit_scale = scale;
+                if self.actual_pixels {
+                    (scale, self.zoom) = Self::actual_size_view(scale);
+                    self.view_center = Self::clamp_view_center(self.view_center, self.zoom);
+                }
+                let fit_size = image_size * scale;
+
+                let total_display_size = fit_size + egui::vec2(padding * 2.0, padding * 2.0);
+
+                // Manual centering
+                let x_offset = (available_size.x - total_display_size.x) / 2.0;
+                let y_offset = (available_size.y - total_display_size.y) / 2.0;
+                let start_pos = ui.cursor().min + egui::vec2(x_offset.max(0.0), y_offset.max(0.0));
+
+                let target_rect = egui::Rect::from_min_size(start_pos, total_display_size);
+
+                let response = ui.allocate_rect(target_rect, egui::Sense::click_and_drag());
+                let painter = ui.painter_at(target_rect);
+
+                // Center the canvas within the response rect (which includes padding)
+                let canvas_rect = egui::Rect::from_min_size(
+                    target_rect.min + egui::vec2(padding, padding),
+                    fit_size,
+                );
+
+                // Minimap: click or drag to recenter the zoomed view
+                let minimap_rect =
+                    (self.zoom > 1.0).then(|| Self::minimap_rect(canvas_rect, image_size));
+                if let Some(minimap_rect) = minimap_rect {
+                    let minimap = ui.interact(
+                        minimap_rect,
+                        ui.id().with("minimap"),
+                        egui::Sense::click_and_drag(),
+                    );
+                    if (minimap.clicked() || minimap.dragged())
+                        && let Some(pos) = minimap.interact_pointer_pos()
+                    {
+                        let center = ((pos - minimap_rect.min) / minimap_rect.size()).to_pos2();
+                        self.view_center = Self::clamp_view_center(center, self.zoom);
+                    }
+                }
+
+                // Ctrl+scroll or pinch zooms toward the pointer, plain scroll
+                // pans. Middle-drag or Space+drag pans without touching the crop.
+                if response.drag_started() {
+                    self.panning = ui.input(|i| {
+                        i.pointer.middle_down()
+                            || (i.key_down(egui::Key::Space) && !ctx.wants_keyboard_input())
+                    });
+                }
+                let mut view_delta = egui::Vec2::ZERO;
+                if self.panning {
+                    view_delta += response.drag_delta();
+                    ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
+                    if response.drag_stopped() {
+                        self.panning = false;
+                    }
+                }
+                if let Some(pointer) = response.hover_pos() {
+                    let (zoom_delta, scroll_delta) =
+                        ui.input(|i| (i.zoom_delta(), i.smooth_scroll_delta));
+                    if zoom_delta != 1.0 {
+                        let display_size = fit_size * self.zoom;
+                        let image_min =
+                            canvas_rect.center() - self.view_center.to_vec2() * display_size;
+                        let anchor = ((pointer - image_min) / display_size).to_pos2();
+                        self.actual_pixels = false;
+                        (self.zoom, self.view_center) = Self::zoom_at(
+                            self.zoom,
+                            self.view_center,
+                            self.zoom * zoom_delta,
+                            anchor,
+                        );
+                    } else {
+                        view_delta += scroll_delta;
+                    }
+                }
+                if view_delta != egui::Vec2::ZERO {
+                    self.view_center = Self::pan_view(
+                        self.view_center,
+                        self.zoom,
+                        view_delta,
+                        fit_size * self.zoom,
+                    );
+                }
+
+                // The zoomed image is positioned so `view_center` sits at the canvas center
+                let display_size = fit_size * self.zoom;
+                let image_rect = egui::Rect::from_min_size(
+                    canvas_rect.center() - self.view_center.to_vec2() * display_size,
+                    display_size,
+                );
+                self.image_screen_rect = Some(image_rect);
+                let visible = egui::Rect::from_center_size(
+                    self.view_center,
+                    egui::Vec2::splat(1.0 / self.zoom),
+                );
+
+                // Draw image
+                painter.image(
+                    texture.id(),
+                    image_rect,
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::WHITE,
+                );
+
+                // Convert normalized crop rect to screen coordinates
+                let mut screen_crop_rect = egui::Rect::from_min_max(
+                    image_rect.lerp_inside(crop_rect.min.to_vec2()),
+                    image_rect.lerp_inside(crop_rect.max.to_vec2()),
+                );
+
+                if self.tool == Tool::Perspective {
+                    let to_screen = |p: egui::Pos2| image_rect.lerp_inside(p.to_vec2());
+
+                    if response.drag_started()
+                        && !self.panning
+                        && let Some(pos) = ui.input(|i| i.pointer.press_origin())
+                    {
+                        self.selected_corner = Self::hit_test_quad(pos, self.quad.map(to_screen));
+                    }
+                    if response.dragged()
+                        && let Some(i) = self.selected_corner
+                    {
+                        let delta_norm = response.drag_delta() / display_size;
+                        self.quad[i] = (self.quad[i] + delta_norm)
+                            .clamp(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+                    }
+                    if response.drag_stopped() {
+                        self.selected_corner = None;
+                    }
+
+                    Self::paint_quad(
+                        &painter,
+                        image_rect,
+                        self.quad.map(to_screen),
+                        &self.settings,
+                        !peeking,
+                    );
+                    if let Some(minimap_rect) = minimap_rect {
+                        Self::paint_minimap(
+                            &painter,
+                            texture.id(),
+                            minimap_rect,
+                            visible,
+                            *crop_rect,
+                        );
+                    }
+                    return;
+                }
+
+                // Handle Input. A drag only starts once the pointer has moved,
+                // so what it grabs is decided where the press began.
+                if response.drag_started()
+                    && !self.panning
+                    && !self.crop_locked
+                    && let Some(pos) = ui.input(|i| i.pointer.press_origin())
+                {
+                    self.selected_handle = Self::hit_test(pos, screen_crop_rect);
+                    self.unsnapped_crop = None;
+                    self.drag_engaged = false;
+                    // Pressing outside the crop starts drawing a new one
+                    if self.selected_handle.is_none() && image_rect.contains(pos) {
+                        let anchor = ((pos - image_rect.min) / image_rect.size()).to_pos2();
+                        self.draw_start = Some((anchor, *crop_rect));
+                    }
+                }
+
+                // Small slips while clicking leave the crop alone
+                let drag_delta = match (
+                    response.dragged(),
+                    ui.input(|i| i.pointer.press_origin()),
+                    response.interact_pointer_pos(),
+                ) {
+                    (true, Some(origin), Some(pos)) => Self::thresholded_drag_delta(
+                        &mut self.drag_engaged,
+                        pos - origin,
+                        response.drag_delta(),
+                    ),
+                    _ => egui::Vec2::ZERO,
+                };
+
+                if response.dragged()
+                    && self.drag_engaged
+                    && let Some((anchor, _)) = self.draw_start
+                    && let Some(pos) = response.interact_pointer_pos()
+                {
+                    let pointer = ((pos - image_rect.min) / image_rect.size()).to_pos2();
+                    let local = |p: egui::Pos2| ((p - bounds.min) / bounds.size()).to_pos2();
+                    let drawn = Self::draw_crop(
+                        local(anchor),
+                        local(pointer),
+                        image_size * bounds.size(),
+                        target_ratio,
+                    );
+                    *crop_rect = Self::from_bounds(bounds, drawn);
+                    screen_crop_rect = egui::Rect::from_min_max(
+                        image_rect.lerp_inside(crop_rect.min.to_vec2()),
+                        image_rect.lerp_inside(crop_rect.max.to_vec2()),
+                    );
+                }
+
+                if drag_delta != egui::Vec2::ZERO
+                    && let Some(handle) = self.selected_handle
+                {
+                    let delta_norm = drag_delta / display_size; // Normalize delta
+                    // The drag runs on the unsnapped rect so slow motion can
+                    // pull an edge back off the border
+                    let unsnapped = self.unsnapped_crop.get_or_insert(*crop_rect);
+                    Self::drag_crop_within(
+                        unsnapped,
+                        handle,
+                        delta_norm,
+                        image_size,
+                        target_ratio,
+                        fixed_area,
+                        bounds,
+                    );
+                    *crop_rect = *unsnapped;
+
+                    // Edges near the image border snap onto it; Alt drags freely
+                    let snap = Self::edge_snap(
+                        *crop_rect,
+                        handle,
+                        egui::Vec2::splat(SNAP_DISTANCE) / display_size,
+                    );
+                    if snap != egui::Vec2::ZERO && !ui.input(|i| i.modifiers.alt) {
+                        Self::drag_crop_within(
+                            crop_rect,
+                            handle,
+                            snap,
+                            image_size,
+                            target_ratio,
+                            fixed_area,
+                            bounds,
+                        );
+                    }
+
+                    // Re-calculate screen rect for display after modification
+                    screen_crop_rect = egui::Rect::from_min_max(
+                        image_rect.lerp_inside(crop_rect.min.to_vec2()),
+                        image_rect.lerp_inside(crop_rect.max.to_vec2()),
+                    );
+                }
+
+                // Touch screens have no right-click, so holding still opens the
+                // menu too. Any real movement makes the press a drag instead.
+                if response.is_pointer_button_down_on() && ui.input(|i| i.pointer.any_pressed()) {
+                    self.press_moved = false;
+                }
+                if response.is_pointer_button_down_on() && !self.press_moved && !self.panning {
+                    let (held, moved) = ui.input(|i| {
+                        let held = i
+                            .pointer
+                            .press_start_time()
+                            .is_some_and(|start| i.time - start >= LONG_PRESS_SECS);
+                        let moved = i
+                            .pointer
+                            .press_origin()
+                            .zip(i.pointer.latest_pos())
+                            .is_some_and(|(origin, pos)| origin.distance(pos) > LONG_PRESS_SLOP);
+                        (held, moved)
+                    });
+                    self.press_moved = moved;
+                    if !moved && held {
+                        self.canvas_menu = response.interact_pointer_pos();
                         self.selected_handle = None;
+                        if let Some((_, previous)) = self.draw_start.take() {
+                            *crop_rect = previous;
+                        }
+                        self.press_moved = true;
+                    } else if !moved {
+                        ctx.request_repaint_after_secs(LONG_PRESS_SECS as f32);
+                    }
+                }
+                if response.hovered()
+                    && ui.input(|i| i.pointer.button_clicked(egui::PointerButton::Secondary))
+                {
+                    self.canvas_menu = response.hover_pos();
+                }
+
+                if response.drag_stopped() {
+                    if let Some((_, previous)) = self.draw_start.take() {
+                        // A click or a slip of the pointer keeps the old crop
+                        let drawn = crop_rect.size() * display_size;
+                        if drawn.x < 4.0 || drawn.y < 4.0 {
+                            *crop_rect = previous;
+                        } else {
+                            if let Some(area) = fixed_area {
+                                *crop_rect = Self::fit_area(*crop_rect, area);
+                            }
+                            Self::push_recent_crop(&mut self.recent_crops, *crop_rect);
+                        }
+                    }
+                    if self.selected_handle.is_some() {
+                        Self::push_recent_crop(&mut self.recent_crops, *crop_rect);
+                    }
+                    self.selected_handle = None;
+                    self.unsnapped_crop = None;
+                    self.crop_history.record(CropState {
+                        rect: *crop_rect,
+                        aspect_ratio: self.aspect_ratio_mode,
+                        is_portrait: self.is_portrait,
+                    });
+                }
+
+                // Double-clicking the image starts over from the full frame
+                if response.double_clicked()
+                    && !self.crop_locked
+                    && response
+                        .interact_pointer_pos()
+                        .is_some_and(|pos| image_rect.contains(pos))
+                {
+                    select_all = true;
+                }
+
+                // The cursor shows what a press would grab, or what is being dragged
+                let cursor_handle = match self.selected_handle {
+                    Some(handle) => Some((handle, true)),
+                    None if !response.dragged() && !self.crop_locked => response
+                        .hover_pos()
+                        .and_then(|pos| Self::hit_test(pos, screen_crop_rect))
+                        .map(|handle| (handle, false)),
+                    None => None,
+                };
+                if let Some((handle, dragging)) = cursor_handle
+                    && !self.panning
+                {
+                    ctx.set_cursor_icon(handle.cursor_icon(dragging));
+                }
+
+                // Saving a crop that covers everything just copies the image
+                let full_image = !self.circular
+                    && !self.mask_out
+                    && self.image.as_ref().is_some_and(|image| {
+                        covers_full_image(image.width(), image.height(), *crop_rect)
+                    });
+
+                // Draw overlay (dimmed area outside crop)
+                let overlay_color = if peeking || full_image || !self.settings.show_overlays {
+                    egui::Color32::TRANSPARENT
+                } else {
+                    egui::Color32::from_black_alpha(self.settings.overlay_alpha)
+                };
+
+                // Mask-out dims the crop instead, since that is what goes away
+                let (outside_color, inside_color) = if self.mask_out {
+                    (egui::Color32::TRANSPARENT, overlay_color)
+                } else {
+                    (overlay_color, egui::Color32::TRANSPARENT)
+                };
+
+                // Only the image is dimmed, so the hole is the part of the
+                // crop that lies on it
+                let hole = Some(screen_crop_rect.intersect(image_rect))
+                    .filter(|hole| hole.is_positive())
+                    .unwrap_or(egui::Rect::from_min_size(image_rect.min, egui::Vec2::ZERO));
+
+                // Top
+                painter.rect_filled(
+                    egui::Rect::from_min_max(
+                        image_rect.min,
+                        egui::pos2(image_rect.max.x, hole.min.y),
+                    ),
+                    0.0,
+                    outside_color,
+                );
+                // Bottom
+                painter.rect_filled(
+                    egui::Rect::from_min_max(
+                        egui::pos2(image_rect.min.x, hole.max.y),
+                        image_rect.max,
+                    ),
+                    0.0,
+                    outside_color,
+                );
+                // Left
+                painter.rect_filled(
+                    egui::Rect::from_min_max(
+                        egui::pos2(image_rect.min.x, hole.min.y),
+                        egui::pos2(hole.min.x, hole.max.y),
+                    ),
+                    0.0,
+                    outside_color,
+                );
+                // Right
+                painter.rect_filled(
+                    egui::Rect::from_min_max(
+                        egui::pos2(hole.max.x, hole.min.y),
+                        egui::pos2(image_rect.max.x, hole.max.y),
+                    ),
+                    0.0,
+                    outside_color,
+                );
+                // The corners a round crop cuts away
+                if self.circular {
+                    painter.add(ellipse_corners_mesh(screen_crop_rect, outside_color));
+                }
+                if inside_color != egui::Color32::TRANSPARENT {
+                    if self.circular {
+                        painter.add(egui::Shape::convex_polygon(
+                            ellipse_points(screen_crop_rect),
+                            inside_color,
+                            egui::Stroke::NONE,
+                        ));
+                    } else {
+                        painter.rect_filled(hole, 0.0, inside_color);
+                    }
+                }
+
+                let show_overlays = self.settings.show_overlays && !peeking;
+                if full_image && show_overlays {
+                    Self::paint_badge(
+                        &painter,
+                        screen_crop_rect.min + egui::vec2(8.0, 8.0),
+                        egui::Align2::LEFT_TOP,
+                        "Full image (no crop)".to_owned(),
+                    );
+                }
+
+                if self.settings.corner_readout
+                    && show_overlays
+                    && let Some(image) = &self.image
+                {
+                    Self::paint_badge(
+                        &painter,
+                        canvas_rect.left_bottom() + egui::vec2(8.0, -8.0),
+                        egui::Align2::LEFT_BOTTOM,
+                        crop_readout(
+                            image.width(),
+                            image.height(),
+                            *crop_rect,
+                            self.settings.ratio_epsilon,
+                            ReadoutStyle::Corner,
+                        ),
+                    );
+                }
+
+                // Live size while a handle or a new crop is being dragged
+                if response.dragged()
+                    && self.drag_engaged
+                    && show_overlays
+                    && let Some(image) = &self.image
+                {
+                    let below = screen_crop_rect.center_bottom() + egui::vec2(0.0, 6.0);
+                    let pos = egui::pos2(below.x, below.y.min(canvas_rect.max.y - 24.0));
+                    Self::paint_badge(
+                        &painter,
+                        pos,
+                        egui::Align2::CENTER_TOP,
+                        crop_readout(
+                            image.width(),
+                            image.height(),
+                            *crop_rect,
+                            self.settings.ratio_epsilon,
+                            ReadoutStyle::Drag,
+                        ),
+                    );
+                }
+
+                // Without feathering, a border on pixel centers keeps 1px lines crisp
+                let border_rect = if self.settings.anti_alias {
+                    screen_crop_rect
+                } else {
+                    egui::Rect::from_min_max(
+                        painter.round_pos_to_pixel_center(screen_crop_rect.min),
+                        painter.round_pos_to_pixel_center(screen_crop_rect.max),
+                    )
+                };
+
+                // Draw crop border (dark stroke underneath keeps it visible on white content)
+                // A round crop outlines its ellipse; the handles stay on the rect
+                let mut path = if self.circular {
+                    ellipse_points(border_rect)
+                } else {
+                    vec![
+                        border_rect.left_top(),
+                        border_rect.right_top(),
+                        border_rect.right_bottom(),
+                        border_rect.left_bottom(),
+                    ]
+                };
+                path.push(path[0]);
+                if self.settings.contrast_border {
+                    painter.add(egui::Shape::line(
+                        path.clone(),
+                        egui::Stroke::new(3.0, egui::Color32::from_black_alpha(180)),
+                    ));
+                }
+                // A locked crop is outlined in amber
+                let border_color = if self.crop_locked {
+                    egui::Color32::from_rgb(255, 190, 40)
+                } else {
+                    egui::Color32::WHITE
+                };
+                if self.settings.marching_ants {
+                    // White dashes crawling over a black line, one dash period per cycle
+                    let period = MARCHING_ANTS_DASH * 2.0;
+                    let offset = (ui.input(|i| i.time) as f32 * MARCHING_ANTS_SPEED) % period;
+                    painter.add(egui::Shape::line(
+                        path.clone(),
+                        egui::Stroke::new(1.0, egui::Color32::BLACK),
+                    ));
+                    painter.extend(egui::Shape::dashed_line_with_offset(
+                        &path,
+                        egui::Stroke::new(1.0, border_color),
+                        &[MARCHING_ANTS_DASH],
+                        &[MARCHING_ANTS_DASH],
+                        period - offset,
+                    ));
+                    ui.ctx().request_repaint();
+                } else {
+                    painter.add(egui::Shape::line(
+                        path,
+                        egui::Stroke::new(1.0, border_color),
+                    ));
+                }
+
+                // Composition guides, on screen only
+                if show_overlays {
+                    let stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(110));
+                    for segment in self.settings.grid_mode.lines(screen_crop_rect) {
+                        painter.line_segment(segment, stroke);
+                    }
+                }
+
+                // Draw handles
+                let handle_radius = self.settings.handle_radius;
+                let handle_stroke = egui::Stroke::new(1.0, egui::Color32::BLACK);
+                let handle_fill = egui::Color32::WHITE;
+
+                let mut handles = vec![
+                    screen_crop_rect.min,
+                    screen_crop_rect.max,
+                    egui::pos2(screen_crop_rect.min.x, screen_crop_rect.max.y),
+                    egui::pos2(screen_crop_rect.max.x, screen_crop_rect.min.y),
+                ];
+                // Side handles would sit on top of the corners on very thin crops
+                if screen_crop_rect.width() > handle_radius * 4.0 {
+                    handles.push(screen_crop_rect.center_top());
+                    handles.push(screen_crop_rect.center_bottom());
+                }
+                if screen_crop_rect.height() > handle_radius * 4.0 {
+                    handles.push(screen_crop_rect.left_center());
+                    handles.push(screen_crop_rect.right_center());
+                }
+
+                // Handles can stay hidden until the pointer could grab one
+                let near = self.selected_handle.is_some()
+                    || response
+                        .hover_pos()
+                        .and_then(|pos| Self::hit_test(pos, screen_crop_rect))
+                        .is_some();
+                let show_handles = !self.settings.handles_on_hover || near;
+
+                // A locked crop has nothing to grab
+                if !peeking && !self.crop_locked && show_handles {
+                    for pos in handles {
+                        painter.circle(pos, handle_radius, handle_fill, handle_stroke);
                     }
                 }
+
+                if let Some(minimap_rect) = minimap_rect {
+                    Self::paint_minimap(&painter, texture.id(), minimap_rect, visible, *crop_rect);
+                }
+            }
+            if select_all {
+                self.select_whole_image();
+            }
+        });
+
+        self.show_canvas_menu(ctx);
+        self.show_settings_window(ctx);
+        Self::paint_drop_hint(ctx);
+
+        if self.settings != settings_before {
+            ctx.set_theme(self.settings.theme);
+            if !self.settings.allow_outside_crop
+                && let Some(rect) = &mut self.crop_rect
+            {
+                let image = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+                let clamped = rect.intersect(image);
+                *rect = if clamped.is_positive() {
+                    clamped
+                } else {
+                    image
+                };
+            }
+        }
+        self.closing = ctx.input(|i| i.viewport().close_requested());
+        if self.settings.autosave_secs > 0 {
+            // eframe only auto-saves from a frame, so keep them coming while idle
+            ctx.request_repaint_after(std::time::Duration::from_secs(
+                self.settings.autosave_secs as u64,
+            ));
+        }
+    }
+}
+
+impl eframe::App for ImageCropper {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.show(ctx);
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, Settings::STORAGE_KEY, &self.settings);
+        eframe::set_value(
+            storage,
+            LastRatio::STORAGE_KEY,
+            &LastRatio {
+                mode: self.aspect_ratio_mode,
+                custom_w: self.custom_w,
+                custom_h: self.custom_h,
+                is_portrait: self.is_portrait,
+            },
+        );
+        eframe::set_value(storage, RecentFiles::STORAGE_KEY, &self.recent_files);
+        eframe::set_value(storage, CropPresets::STORAGE_KEY, &self.crop_presets);
+        eframe::set_value(storage, Session::STORAGE_KEY, &self.session());
+    }
+
+    fn auto_save_interval(&self) -> std::time::Duration {
+        match self.settings.autosave_secs {
+            0 => std::time::Duration::from_secs(30),
+            secs => std::time::Duration::from_secs(secs as u64),
+        }
+    }
+}
+
+fn main() -> eframe::Result {
+    // `imagecropper photo.png`, as also used by file managers' "Open with"
+    let startup_path = std::env::args_os()
+        .skip(1)
+        .find(|arg| !arg.to_string_lossy().starts_with('-'))
+        .map(PathBuf::from);
+    // Reopen where the window was last closed; the first run gets 800×600
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([800.0, 600.0]),
+        persist_window: true,
+        ..Default::default()
+    };
+    eframe::run_native(
+        "Image Cropper",
+        options,
+        Box::new(|cc| Ok(Box::new(ImageCropper::new(cc, startup_path)))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// In-memory stand-in for eframe's persisted storage.
+    #[derive(Default)]
+    struct MemoryStorage(std::collections::HashMap<String, String>);
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_owned(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("image_cropper_{}_{}", std::process::id(), name))
+    }
+
+    fn cropper_with_image(width: u32, height: u32) -> ImageCropper {
+        ImageCropper {
+            image: Some(DynamicImage::new_rgb8(width, height)),
+            crop_rect: Some(egui::Rect::from_min_max(
+                egui::Pos2::ZERO,
+                egui::pos2(1.0, 1.0),
+            )),
+            ..Default::default()
+        }
+    }
+
+    /// Canvas the drag maths tests map a 2:1 image onto, 2 screen px per image px.
+    const DRAG_CANVAS: egui::Rect =
+        egui::Rect::from_min_max(egui::pos2(20.0, 20.0), egui::pos2(420.0, 220.0));
+
+    /// Runs the canvas drag maths for a drag from `from` to `to` (screen
+    /// space) over `DRAG_CANVAS`: the hit test on press, then `drag_crop`
+    /// over several frames. Pointer input, the drag threshold and snapping
+    /// are not involved. Returns the grabbed handle.
+    fn drag_math(
+        cropper: &mut ImageCropper,
+        from: egui::Pos2,
+        to: egui::Pos2,
+    ) -> Option<ResizeHandle> {
+        const FRAMES: usize = 4;
+        let image = cropper.image.as_ref().unwrap();
+        let image_size = egui::vec2(image.width() as f32, image.height() as f32);
+        let target_ratio = cropper.target_ratio();
+        let crop_rect = cropper.crop_rect.as_mut().unwrap();
+        let screen_crop_rect = egui::Rect::from_min_max(
+            DRAG_CANVAS.lerp_inside(crop_rect.min.to_vec2()),
+            DRAG_CANVAS.lerp_inside(crop_rect.max.to_vec2()),
+        );
+        let handle = ImageCropper::hit_test(from, screen_crop_rect)?;
+        let frame_delta = (to - from) / FRAMES as f32;
+        for _ in 0..FRAMES {
+            let delta_norm = frame_delta / DRAG_CANVAS.size();
+            ImageCropper::drag_crop(crop_rect, handle, delta_norm, image_size, target_ratio);
+        }
+        Some(handle)
+    }
+
+    /// Drives `ImageCropper::show` with raw pointer events on a 1920×1200
+    /// screen, one frame per call, so drags go through the canvas's `Sense`,
+    /// egui's drag detection, the drag threshold and edge snapping.
+    struct UiHarness {
+        ctx: egui::Context,
+        time: f64,
+    }
+
+    impl UiHarness {
+        /// Shows a `width`×`height` image with its crop set to `crop`.
+        fn new(width: u32, height: u32, crop: egui::Rect) -> (Self, ImageCropper) {
+            let mut harness = Self {
+                ctx: egui::Context::default(),
+                time: 0.0,
+            };
+            let mut cropper = ImageCropper {
+                zoom: 1.0,
+                view_center: egui::pos2(0.5, 0.5),
+                ..Default::default()
+            };
+            cropper.set_image(
+                &harness.ctx,
+                DynamicImage::new_rgb8(width, height),
+                None,
+                None,
+            );
+            cropper.crop_rect = Some(crop);
+            // The panels settle over the first frames
+            harness.frame(&mut cropper, Vec::new());
+            harness.frame(&mut cropper, Vec::new());
+            (harness, cropper)
+        }
+
+        fn frame(&mut self, cropper: &mut ImageCropper, events: Vec<egui::Event>) {
+            self.time += 1.0 / 60.0;
+            let input = egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(1920.0, 1200.0),
+                )),
+                time: Some(self.time),
+                events,
+                ..Default::default()
+            };
+            let _ = self.ctx.run(input, |ctx| cropper.show(ctx));
+        }
+
+        fn button(&mut self, cropper: &mut ImageCropper, pos: egui::Pos2, pressed: bool) {
+            let event = egui::Event::PointerButton {
+                pos,
+                button: egui::PointerButton::Primary,
+                pressed,
+                modifiers: egui::Modifiers::NONE,
+            };
+            self.frame(cropper, vec![event]);
+        }
+
+        /// Screen position of the normalized image point `p`.
+        fn screen(cropper: &ImageCropper, p: egui::Pos2) -> egui::Pos2 {
+            cropper.image_screen_rect.unwrap().lerp_inside(p.to_vec2())
+        }
+
+        /// Presses at `from`, moves to `to` over four frames and releases.
+        /// Returns the handle the canvas held while the pointer moved.
+        fn drag(
+            &mut self,
+            cropper: &mut ImageCropper,
+            from: egui::Pos2,
+            to: egui::Pos2,
+        ) -> Option<ResizeHandle> {
+            self.frame(cropper, vec![egui::Event::PointerMoved(from)]);
+            self.button(cropper, from, true);
+            let mut held = None;
+            for step in 1..=4 {
+                let pos = from.lerp(to, step as f32 / 4.0);
+                self.frame(cropper, vec![egui::Event::PointerMoved(pos)]);
+                held = held.or(cropper.selected_handle);
+            }
+            self.button(cropper, to, false);
+            self.frame(cropper, Vec::new());
+            held
+        }
+    }
+
+    fn assert_rect_near(actual: egui::Rect, expected: egui::Rect) {
+        let close = |a: egui::Pos2, b: egui::Pos2| (a - b).length() < 1e-4;
+        assert!(
+            close(actual.min, expected.min) && close(actual.max, expected.max),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn centered_quarter_crop() -> ImageCropper {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.25),
+            egui::pos2(0.75, 0.75),
+        ));
+        cropper
+    }
+
+    #[test]
+    fn load_missing_file_is_load_error() {
+        let result = ImageCropper::load_image(&temp_path("does_not_exist.png"));
+        assert!(matches!(result, Err(CropperError::Load(_))));
+    }
+
+    #[test]
+    fn load_corrupt_file_is_load_error() {
+        let path = temp_path("corrupt.png");
+        std::fs::write(&path, b"definitely not a png").unwrap();
+        let result = ImageCropper::load_image(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(CropperError::Load(_))));
+    }
+
+    #[test]
+    fn crop_without_image_is_no_image_error() {
+        let cropper = ImageCropper::default();
+        assert!(matches!(
+            cropper.cropped_image(),
+            Err(CropperError::NoImage)
+        ));
+    }
+
+    #[test]
+    fn crop_with_degenerate_quad_is_degenerate_quad_error() {
+        let mut cropper = cropper_with_image(100, 100);
+        cropper.tool = Tool::Perspective;
+        cropper.quad = [egui::pos2(0.5, 0.5); 4];
+        assert!(matches!(
+            cropper.cropped_image(),
+            Err(CropperError::DegenerateQuad)
+        ));
+    }
+
+    #[test]
+    fn crop_returns_selected_region() {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.5),
+            egui::pos2(0.75, 1.0),
+        ));
+        let cropped = cropper.cropped_image().unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (100, 50));
+    }
+
+    #[test]
+    fn save_with_unknown_extension_is_encode_error() {
+        let image = DynamicImage::new_rgb8(4, 4);
+        let result = export::save_image(&image, &temp_path("out.unknown"));
+        assert!(matches!(result, Err(CropperError::Encode(_))));
+    }
+
+    #[test]
+    fn save_into_missing_directory_is_io_error() {
+        let image = DynamicImage::new_rgb8(4, 4);
+        let path = temp_path("missing_dir").join("out.png");
+        assert!(matches!(
+            export::save_image(&image, &path),
+            Err(CropperError::Io(_))
+        ));
+        assert!(matches!(
+            export::save_smallest(&image, &path, None),
+            Err(CropperError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn ico_export_pads_non_square_crop() {
+        let padded = export::pad_to_square(&DynamicImage::new_rgb8(40, 10));
+        assert_eq!((padded.width(), padded.height()), (40, 40));
+        // Padding is transparent, the original rows sit in the middle
+        assert_eq!(padded.to_rgba8().get_pixel(0, 0).0[3], 0);
+        assert_eq!(padded.to_rgba8().get_pixel(0, 20).0[3], 255);
+    }
+
+    #[test]
+    fn template_batch_crops_folder_and_reports_failures() {
+        let folder = temp_path("batch");
+        std::fs::create_dir_all(&folder).unwrap();
+        DynamicImage::new_rgb8(100, 50)
+            .save(folder.join("a.png"))
+            .unwrap();
+        DynamicImage::new_rgb8(40, 40)
+            .save(folder.join("b.png"))
+            .unwrap();
+        std::fs::write(folder.join("broken.png"), b"not an image").unwrap();
+
+        let template = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.5, 0.5));
+        let summary = batch::apply_template(&folder, template, &Settings::default()).unwrap();
+        let cropped = image::open(folder.join("a_cropped.png")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(summary.saved, 2);
+        assert_eq!(summary.failed.len(), 1);
+        assert!(matches!(summary.failed[0].1, CropperError::Load(_)));
+        assert_eq!((cropped.width(), cropped.height()), (50, 25));
+    }
+
+    #[test]
+    fn settings_round_trip_and_default_missing_fields() {
+        let settings = Settings {
+            default_tool: Tool::Perspective,
+            default_aspect_ratio: AspectRatioMode::R9_16,
+            open_clipboard_path: true,
+            auto_orient: false,
+            remember_ratio: false,
+            theme: egui::ThemePreference::Dark,
+            overlay_alpha: 90,
+            handle_radius: 8.5,
+            handles_on_hover: true,
+            canvas_padding: 0.0,
+            contrast_border: false,
+            anti_alias: false,
+            marching_ants: true,
+            show_overlays: false,
+            grid_mode: GridMode::Diagonals,
+            show_preview: false,
+            auto_advance: true,
+            ratio_epsilon: 0.02,
+            face_headroom: 0.25,
+            face_model: Some(PathBuf::from("/models/seeta_fd_frontal_v1.0.bin")),
+            preserve_icc_profile: false,
+            protect_source: false,
+            apply_last_crop: true,
+            allow_outside_crop: true,
+            corner_readout: true,
+            comparison_gutter: 0,
+            comparison_background: [18, 52, 86],
+            comparison_outline: false,
+            filename_template: "{stem}-{w}.{ext}".to_owned(),
+            multi_ratios: vec![AspectRatioMode::R4_3, AspectRatioMode::R2_3],
+            autosave_secs: 0,
+            avif_quality: 55,
+            jpeg_quality: 77,
+        };
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, Settings::STORAGE_KEY, &settings);
+        assert_eq!(
+            eframe::get_value::<Settings>(&storage, Settings::STORAGE_KEY),
+            Some(settings)
+        );
+
+        eframe::Storage::set_string(
+            &mut storage,
+            Settings::STORAGE_KEY,
+            "(future_option: 3, overlay_alpha: 90)".to_owned(),
+        );
+        assert_eq!(
+            eframe::get_value::<Settings>(&storage, Settings::STORAGE_KEY),
+            Some(Settings {
+                overlay_alpha: 90,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn hit_test_disambiguates_handles_on_panorama() {
+        // A 10000×200 panorama fitted into a 760 px wide canvas is ~15 px tall
+        let image_size = egui::vec2(10000.0, 200.0);
+        let display_size = image_size * (760.0 / image_size.x);
+        let rect = egui::Rect::from_min_size(egui::pos2(20.0, 20.0), display_size);
+        let mid_x = rect.center().x;
+
+        let hit = |x: f32, y: f32| ImageCropper::hit_test(egui::pos2(x, y), rect);
+        assert_eq!(hit(mid_x, rect.center().y), Some(ResizeHandle::Center));
+        assert_eq!(hit(mid_x, rect.min.y + 2.0), Some(ResizeHandle::Top));
+        assert_eq!(hit(mid_x, rect.max.y - 2.0), Some(ResizeHandle::Bottom));
+        assert_eq!(hit(mid_x, rect.min.y - 8.0), Some(ResizeHandle::Top));
+        assert_eq!(hit(mid_x, rect.max.y + 8.0), Some(ResizeHandle::Bottom));
+        assert_eq!(
+            hit(rect.max.x, rect.max.y - 2.0),
+            Some(ResizeHandle::BottomRight)
+        );
+        assert_eq!(
+            hit(rect.min.x, rect.min.y + 2.0),
+            Some(ResizeHandle::TopLeft)
+        );
+        assert_eq!(hit(mid_x, rect.max.y + 12.0), None);
+    }
+
+    #[test]
+    fn fixed_area_drag_holds_area() {
+        let area = 0.25;
+        let start = egui::Rect::from_center_size(egui::pos2(0.5, 0.5), egui::vec2(0.5, 0.5));
+        for handle in [
+            ResizeHandle::Right,
+            ResizeHandle::Top,
+            ResizeHandle::BottomLeft,
+            ResizeHandle::TopRight,
+        ] {
+            let mut rect = start;
+            ImageCropper::drag_crop_fixed_area(&mut rect, handle, egui::vec2(0.1, -0.05), area);
+            assert!(
+                (rect.area() - area).abs() < 1e-4,
+                "{:?}: area {}",
+                handle,
+                rect.area()
+            );
+            assert!(rect.min.x >= 0.0 && rect.min.y >= 0.0);
+            assert!(rect.max.x <= 1.0 && rect.max.y <= 1.0);
+        }
+    }
+
+    #[test]
+    fn crop_pixel_bounds_round_edges_consistently() {
+        let rect = |x0: f32, y0: f32, x1: f32, y1: f32| {
+            egui::Rect::from_min_max(egui::pos2(x0, y0), egui::pos2(x1, y1))
+        };
+
+        // Full frame is exact for any size
+        for (w, h) in [(1, 1), (3, 7), (640, 480), (10000, 200)] {
+            assert_eq!(
+                crop_pixel_bounds(w, h, rect(0.0, 0.0, 1.0, 1.0)),
+                (0, 0, w, h)
+            );
+        }
+
+        // Halves of odd sizes meet at the same pixel and cover the whole image
+        let (lx, _, lw, _) = crop_pixel_bounds(101, 101, rect(0.0, 0.0, 0.5, 1.0));
+        let (rx, _, rw, _) = crop_pixel_bounds(101, 101, rect(0.5, 0.0, 1.0, 1.0));
+        assert_eq!(lx + lw, rx);
+        assert_eq!(lw + rw, 101);
+
+        // Float noise around a pixel boundary does not lose or gain a pixel
+        let third = 1.0 / 3.0;
+        assert_eq!(
+            crop_pixel_bounds(300, 300, rect(third, third, 2.0 * third, 2.0 * third)),
+            (100, 100, 100, 100)
+        );
+        assert_eq!(
+            crop_pixel_bounds(1000, 1000, rect(0.2999999, 0.1, 0.7000001, 0.9)),
+            (300, 100, 400, 800)
+        );
+
+        // Degenerate and out-of-range rects still give at least one pixel inside
+        assert_eq!(
+            crop_pixel_bounds(50, 50, rect(1.0, 1.0, 1.0, 1.0)),
+            (49, 49, 1, 1)
+        );
+        assert_eq!(
+            crop_pixel_bounds(50, 50, rect(-0.5, -0.5, 2.0, 2.0)),
+            (0, 0, 50, 50)
+        );
+    }
+
+    #[test]
+    fn free_drag_math_moves_grabbed_corner_only() {
+        let mut cropper = centered_quarter_crop();
+        // Bottom-right corner sits at (320, 170) on screen
+        let handle = drag_math(
+            &mut cropper,
+            egui::pos2(318.0, 168.0),
+            egui::pos2(358.0, 188.0),
+        );
+        assert_eq!(handle, Some(ResizeHandle::BottomRight));
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.85, 0.85)),
+        );
+
+        // Dragging far past the image edge stops at the edge
+        let handle = drag_math(
+            &mut cropper,
+            egui::pos2(122.0, 72.0),
+            egui::pos2(-500.0, 72.0),
+        );
+        assert_eq!(handle, Some(ResizeHandle::TopLeft));
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.25), egui::pos2(0.85, 0.85)),
+        );
+    }
+
+    #[test]
+    fn constrained_corner_drag_math_keeps_ratio_and_anchor() {
+        for mode in [
+            AspectRatioMode::Square,
+            AspectRatioMode::R16_9,
+            AspectRatioMode::R9_16,
+        ] {
+            let mut cropper = centered_quarter_crop();
+            cropper.aspect_ratio_mode = mode;
+            cropper.apply_aspect_ratio();
+            let ratio = cropper.target_ratio().unwrap();
+            let start = cropper.crop_rect.unwrap();
+            let corner = DRAG_CANVAS.lerp_inside(start.min.to_vec2());
+
+            let handle = drag_math(&mut cropper, corner, corner + egui::vec2(-30.0, -5.0));
+            assert_eq!(handle, Some(ResizeHandle::TopLeft), "{}", mode);
+            let rect = cropper.crop_rect.unwrap();
+            let pixel_ratio = (rect.width() * 200.0) / (rect.height() * 100.0);
+            assert!(
+                (pixel_ratio - ratio).abs() < 1e-3,
+                "{}: {}",
+                mode,
+                pixel_ratio
+            );
+            assert_eq!(rect.max, start.max, "{}", mode);
+            assert!(rect.width() > start.width(), "{}", mode);
+        }
+    }
+
+    #[test]
+    fn edge_drag_math_resizes_one_axis_or_follows_ratio() {
+        // Free: only the right edge moves
+        let mut cropper = centered_quarter_crop();
+        let handle = drag_math(
+            &mut cropper,
+            egui::pos2(319.0, 120.0),
+            egui::pos2(279.0, 140.0),
+        );
+        assert_eq!(handle, Some(ResizeHandle::Right));
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.65, 0.75)),
+        );
+
+        // Constrained: the height follows so the ratio is kept
+        let mut cropper = centered_quarter_crop();
+        cropper.aspect_ratio_mode = AspectRatioMode::Square;
+        cropper.apply_aspect_ratio();
+        let start = cropper.crop_rect.unwrap();
+        let bottom = DRAG_CANVAS.lerp_inside(egui::vec2(start.center().x, start.max.y));
+        let handle = drag_math(&mut cropper, bottom, bottom + egui::vec2(0.0, 20.0));
+        assert_eq!(handle, Some(ResizeHandle::Bottom));
+        let rect = cropper.crop_rect.unwrap();
+        assert_eq!(rect.min.y, start.min.y);
+        assert!(rect.height() > start.height());
+        assert!((rect.width() * 200.0 - rect.height() * 100.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn center_drag_math_pans_without_resizing() {
+        let mut cropper = centered_quarter_crop();
+        let handle = drag_math(
+            &mut cropper,
+            egui::pos2(220.0, 120.0),
+            egui::pos2(260.0, 100.0),
+        );
+        assert_eq!(handle, Some(ResizeHandle::Center));
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.35, 0.15), egui::pos2(0.85, 0.65)),
+        );
+
+        // Panning past the edge stops there with the size unchanged
+        drag_math(
+            &mut cropper,
+            egui::pos2(220.0, 120.0),
+            egui::pos2(-400.0, 900.0),
+        );
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.5), egui::pos2(0.5, 1.0)),
+        );
+
+        // Pressing outside the crop grabs nothing
+        assert_eq!(
+            drag_math(
+                &mut cropper,
+                egui::pos2(400.0, 30.0),
+                egui::pos2(410.0, 40.0)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn nearest_preset_ratio_respects_tolerance() {
+        assert_eq!(
+            nearest_preset_ratio(1.0, 0.0),
+            Some(AspectRatioMode::Square)
+        );
+        assert_eq!(
+            nearest_preset_ratio(1920.0 / 1081.0, 0.01),
+            Some(AspectRatioMode::R16_9)
+        );
+        assert_eq!(
+            nearest_preset_ratio(1081.0 / 1920.0, 0.01),
+            Some(AspectRatioMode::R9_16)
+        );
+        // 16:10 and 3:2 are about 7% apart; the closer one wins
+        assert_eq!(
+            nearest_preset_ratio(1.52, 0.05),
+            Some(AspectRatioMode::R3_2)
+        );
+        assert_eq!(
+            nearest_preset_ratio(1.58, 0.05),
+            Some(AspectRatioMode::R16_10)
+        );
+        // Far from every preset, or just outside the tolerance
+        assert_eq!(nearest_preset_ratio(2.4, 0.01), None);
+        assert_eq!(nearest_preset_ratio(1.8, 0.001), None);
+    }
+
+    #[test]
+    fn side_by_side_places_original_and_crop() {
+        let original = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            40,
+            20,
+            image::Rgba([0, 0, 255, 255]),
+        ));
+        let cropped = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            10,
+            10,
+            image::Rgba([0, 255, 0, 255]),
+        ));
+        let background = image::Rgba([255, 255, 255, 255]);
+        let corners = [(10.0, 5.0), (19.0, 5.0), (19.0, 14.0), (10.0, 14.0)];
+        let composed =
+            export::compose_side_by_side(&original, &cropped, Some(corners), 4, background)
+                .to_rgba8();
+
+        assert_eq!(composed.dimensions(), (40 + 10 + 12, 20 + 8));
+        assert_eq!(*composed.get_pixel(0, 0), background);
+        assert_eq!(composed.get_pixel(5, 5).0, [0, 0, 255, 255]);
+        assert_eq!(*composed.get_pixel(4 + 10, 4 + 5), export::OUTLINE_COLOR);
+        // The crop is vertically centered beside the original
+        assert_eq!(composed.get_pixel(48, 9).0, [0, 255, 0, 255]);
+        assert_eq!(*composed.get_pixel(48, 8), background);
+    }
+
+    #[test]
+    fn toggle_free_ratio_restores_last_constraint() {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.aspect_ratio_mode = AspectRatioMode::R9_16;
+        cropper.apply_aspect_ratio();
+
+        cropper.toggle_free_ratio();
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::Free);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::Pos2::ZERO,
+            egui::pos2(1.0, 1.0),
+        ));
+
+        cropper.toggle_free_ratio();
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::R9_16);
+        assert!(cropper.is_portrait);
+        let rect = cropper.crop_rect.unwrap();
+        let ratio = rect.width() * 200.0 / (rect.height() * 100.0);
+        assert!((ratio - 9.0 / 16.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn face_crop_frames_headroom_and_fits_image() {
+        let image_size = egui::vec2(1000.0, 1000.0);
+        let face = egui::Rect::from_min_size(egui::pos2(0.45, 0.3), egui::vec2(0.1, 0.1));
+
+        // Square around a 100 px face: 50 px headroom, 100 px chin room
+        let rect = ImageCropper::face_crop(face, image_size, 1.0, 0.5);
+        assert_rect_near(
+            rect,
+            egui::Rect::from_min_max(egui::pos2(0.375, 0.25), egui::pos2(0.625, 0.5)),
+        );
+
+        // A face filling most of a small image shrinks the framing to fit
+        let face = egui::Rect::from_min_size(egui::pos2(0.2, 0.1), egui::vec2(0.6, 0.6));
+        let rect = ImageCropper::face_crop(face, image_size, 4.0 / 5.0, 0.5);
+        assert!(rect.min.x >= 0.0 && rect.min.y >= 0.0);
+        assert!(rect.max.x <= 1.0 + 1e-6 && rect.max.y <= 1.0 + 1e-6);
+        assert!((rect.width() / rect.height() - 0.8).abs() < 1e-4);
+        assert!((rect.height() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn draw_crop_follows_ratio_from_anchor_and_stays_inside() {
+        let image_size = egui::vec2(200.0, 100.0);
+        let anchor = egui::pos2(0.5, 0.5);
+
+        // Free: anchor to pointer, clamped to the image
+        assert_rect_near(
+            ImageCropper::draw_crop(anchor, egui::pos2(1.4, 0.2), image_size, None),
+            egui::Rect::from_min_max(egui::pos2(0.5, 0.2), egui::pos2(1.0, 0.5)),
+        );
+
+        // Square, dragged up and left: anchored bottom-right, 1:1 in pixels
+        let rect = ImageCropper::draw_crop(anchor, egui::pos2(0.3, 0.1), image_size, Some(1.0));
+        assert_eq!(rect.max, anchor);
+        assert!((rect.width() * 200.0 - rect.height() * 100.0).abs() < 1e-3);
+
+        // Past the edge, the ratio is kept and the rect shrinks to fit
+        let rect = ImageCropper::draw_crop(
+            egui::pos2(0.1, 0.1),
+            egui::pos2(3.0, 3.0),
+            image_size,
+            Some(16.0 / 9.0),
+        );
+        assert_eq!(rect.min, egui::pos2(0.1, 0.1));
+        assert!((rect.max.y - 1.0).abs() < 1e-5 && rect.max.x <= 1.0);
+        assert!((rect.width() * 200.0 / (rect.height() * 100.0) - 16.0 / 9.0).abs() < 1e-3);
+
+        // No movement yet gives an empty rect rather than NaN
+        let rect = ImageCropper::draw_crop(anchor, anchor, image_size, Some(1.5));
+        assert_eq!(rect, egui::Rect::from_two_pos(anchor, anchor));
+    }
+
+    #[test]
+    fn icc_profile_survives_load_and_export() {
+        use image::ImageEncoder;
+
+        let profile = b"not a real profile, but carried through verbatim".to_vec();
+        let source = temp_path("icc_source.png");
+        let mut encoder =
+            image::codecs::png::PngEncoder::new(std::fs::File::create(&source).unwrap());
+        encoder.set_icc_profile(profile.clone()).unwrap();
+        DynamicImage::new_rgb8(8, 8)
+            .write_with_encoder(encoder)
+            .unwrap();
+
+        let (image, loaded, _) = ImageCropper::load_image(&source).unwrap();
+        assert_eq!(loaded.as_deref(), Some(profile.as_slice()));
+
+        let read_profile = |path: &Path| {
+            let mut decoder = image::ImageReader::open(path)
+                .unwrap()
+                .with_guessed_format()
+                .unwrap()
+                .into_decoder()
+                .unwrap();
+            decoder.icc_profile().unwrap()
+        };
+        for name in ["icc_out.png", "icc_out.jpg", "icc_out.webp"] {
+            let path = temp_path(name);
+            export::save_image_with_profile(&image, &path, loaded.as_deref(), 90).unwrap();
+            assert_eq!(read_profile(&path), Some(profile.clone()), "{}", name);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // Without a profile the export has none either
+        let path = temp_path("icc_none.png");
+        export::save_image_with_profile(&image, &path, None, 90).unwrap();
+        assert_eq!(read_profile(&path), None);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&source).unwrap();
+    }
+
+    #[test]
+    fn snap_and_maximize_locks_nearest_preset_around_center() {
+        let mut cropper = cropper_with_image(400, 200);
+        // Roughly 16:9 (170x100 px), centered left of the middle
+        cropper.crop_rect = Some(egui::Rect::from_center_size(
+            egui::pos2(0.45, 0.5),
+            egui::vec2(170.0 / 400.0, 100.0 / 200.0),
+        ));
+        cropper.snap_and_maximize();
+
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::R16_9);
+        let rect = cropper.crop_rect.unwrap();
+        assert!((rect.center() - egui::pos2(0.45, 0.5)).length() < 1e-5);
+        // Height is the limit: full 200 px tall, 16:9 wide
+        assert!((rect.height() - 1.0).abs() < 1e-5);
+        assert!((rect.width() * 400.0 - 200.0 * 16.0 / 9.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn data_uri_is_base64_of_encoded_image() {
+        assert_eq!(export::base64_encode(b""), "");
+        assert_eq!(export::base64_encode(b"f"), "Zg==");
+        assert_eq!(export::base64_encode(b"fo"), "Zm8=");
+        assert_eq!(export::base64_encode(b"foo"), "Zm9v");
+        assert_eq!(export::base64_encode(&[0xfb, 0xff, 0xbf]), "+/+/");
+
+        let uri = export::data_uri(&DynamicImage::new_rgb8(2, 2), image::ImageFormat::Png).unwrap();
+        // Base64 of the PNG signature
+        assert!(uri.starts_with("data:image/png;base64,iVBORw0KGgo"));
+    }
+
+    #[test]
+    fn carry_over_crop_clamps_and_skips_empty() {
+        let mut cropper = cropper_with_image(100, 100);
+        cropper.carry_over_crop(egui::Rect::from_min_max(
+            egui::pos2(0.5, -0.2),
+            egui::pos2(1.3, 0.6),
+        ));
+        assert_eq!(
+            cropper.crop_rect,
+            Some(egui::Rect::from_min_max(
+                egui::pos2(0.5, 0.0),
+                egui::pos2(1.0, 0.6)
+            ))
+        );
+
+        let before = cropper.crop_rect;
+        cropper.carry_over_crop(egui::Rect::from_min_max(
+            egui::pos2(1.2, 0.2),
+            egui::pos2(1.5, 0.4),
+        ));
+        assert_eq!(cropper.crop_rect, before);
+    }
+
+    #[test]
+    fn covers_full_image_allows_sub_pixel_slack() {
+        let full = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        assert!(covers_full_image(640, 480, full));
+        assert!(covers_full_image(
+            640,
+            480,
+            egui::Rect::from_min_max(egui::pos2(0.0004, 0.0), egui::pos2(0.9996, 1.0))
+        ));
+        assert!(!covers_full_image(
+            640,
+            480,
+            egui::Rect::from_min_max(egui::pos2(0.01, 0.0), egui::pos2(1.0, 1.0))
+        ));
+    }
+
+    #[test]
+    fn regions_json_is_clamped_and_validated() {
+        let json = r#"[
+            {"name": "left", "x": 0, "y": 0, "width": 50, "height": 100},
+            {"name": "spill", "x": 150, "y": -20, "width": 100, "height": 60, "note": null}
+        ]"#;
+        let regions = regions::parse_regions(json, 200, 100).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].name, "left");
+        assert_rect_near(
+            regions[0].rect,
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.25, 1.0)),
+        );
+        assert_rect_near(
+            regions[1].rect,
+            egui::Rect::from_min_max(egui::pos2(0.75, 0.0), egui::pos2(1.0, 0.4)),
+        );
+
+        let invalid = |json: &str| {
+            matches!(
+                regions::parse_regions(json, 200, 100),
+                Err(CropperError::InvalidRegions(_))
+            )
+        };
+        assert!(invalid(
+            r#"[{"name": "a", "x": 0, "y": 0, "width": 1, "height": 1},
+                {"name": "a", "x": 5, "y": 5, "width": 1, "height": 1}]"#
+        ));
+        assert!(invalid(
+            r#"[{"name": "gone", "x": 300, "y": 0, "width": 10, "height": 10}]"#
+        ));
+        assert!(invalid(
+            r#"[{"name": "", "x": 0, "y": 0, "width": 1, "height": 1}]"#
+        ));
+        assert!(invalid(
+            r#"[{"name": "a", "x": "0", "y": 0, "width": 1, "height": 1}]"#
+        ));
+        assert!(invalid(r#"{"name": "a"}"#));
+        assert!(invalid(r#"[{"name": "a", "x": 0"#));
+    }
+
+    #[test]
+    fn crop_readout_names_preset_or_shows_decimal_ratio() {
+        let full = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        let half = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.5, 0.5));
+        let readout = |w, h, rect, style| crop_readout(w, h, rect, 0.01, style);
+        assert_eq!(
+            readout(1920, 1080, full, ReadoutStyle::Corner),
+            "1920×1080 px · 16:9"
+        );
+        assert_eq!(
+            readout(1000, 420, full, ReadoutStyle::Corner),
+            "1000×420 px · 2.38:1"
+        );
+        assert_eq!(
+            readout(2560, 1440, half, ReadoutStyle::Drag),
+            "1280 × 720 (16:9)"
+        );
+        assert_eq!(
+            readout(1000, 420, half, ReadoutStyle::Drag),
+            "500 × 210 (2.38:1)"
+        );
+    }
+
+    #[test]
+    fn avif_export_matches_build_features() {
+        let path = temp_path("out.avif");
+        let result = export::save_avif(&DynamicImage::new_rgba8(8, 8), &path, 60);
+        #[cfg(feature = "avif")]
+        {
+            result.unwrap();
+            #[cfg(feature = "avif-decode")]
+            {
+                assert!(IMAGE_EXTENSIONS.contains(&"avif"));
+                assert_eq!(image::image_dimensions(&path).unwrap(), (8, 8));
+            }
+            std::fs::remove_file(&path).unwrap();
+        }
+        #[cfg(not(feature = "avif"))]
+        {
+            assert!(matches!(result, Err(CropperError::FormatDisabled("AVIF"))));
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn drag_threshold_swallows_small_slips_then_catches_up() {
+        let mut engaged = false;
+        let step = |engaged: &mut bool, total: egui::Vec2, frame: egui::Vec2| {
+            ImageCropper::thresholded_drag_delta(engaged, total, frame)
+        };
+        assert_eq!(
+            step(&mut engaged, egui::vec2(1.0, 1.0), egui::vec2(1.0, 1.0)),
+            egui::Vec2::ZERO
+        );
+        assert_eq!(
+            step(&mut engaged, egui::vec2(2.0, 1.0), egui::vec2(1.0, 0.0)),
+            egui::Vec2::ZERO
+        );
+        // Crossing the threshold applies everything since the press
+        assert_eq!(
+            step(&mut engaged, egui::vec2(4.0, 1.0), egui::vec2(2.0, 0.0)),
+            egui::vec2(4.0, 1.0)
+        );
+        assert!(engaged);
+        // Afterwards movement is applied as it comes, even back near the origin
+        assert_eq!(
+            step(&mut engaged, egui::vec2(0.0, 1.0), egui::vec2(-4.0, 0.0)),
+            egui::vec2(-4.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn crop_outside_image_pans_past_edge_and_pads_export() {
+        let start = egui::Rect::from_min_max(egui::pos2(0.6, 0.2), egui::pos2(1.0, 0.8));
+        let mut cropper = cropper_with_image(100, 50);
+        cropper.settings.allow_outside_crop = true;
+        cropper.crop_rect = Some(start);
+        for _ in 0..3 {
+            cropper.step_crop(ResizeHandle::Center, egui::vec2(1.0, 0.0));
+        }
+        let moved = cropper.crop_rect.unwrap();
+        assert!(moved.max.x > 1.0, "crop stopped at the edge: {:?}", moved);
+
+        let cropped = cropper.cropped_image().unwrap().to_rgba8();
+        let (width, height) = cropped.dimensions();
+        assert_eq!(height, 30);
+        assert_eq!(width, 40);
+        assert_eq!(cropped.get_pixel(0, 0).0[3], 255);
+        assert_eq!(cropped.get_pixel(width - 1, 0).0[3], 0);
+
+        // Without the option the same step stops at the edge
+        cropper.settings.allow_outside_crop = false;
+        cropper.crop_rect = Some(start);
+        cropper.step_crop(ResizeHandle::Center, egui::vec2(1.0, 0.0));
+        assert_eq!(cropper.crop_rect.unwrap(), start);
+    }
+
+    #[test]
+    fn drag_within_unit_bounds_matches_drag_crop() {
+        let rect = egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.75));
+        let unit = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        let size = egui::vec2(200.0, 100.0);
+        let delta = egui::vec2(0.1, -0.05);
+        for handle in [
+            ResizeHandle::TopLeft,
+            ResizeHandle::Right,
+            ResizeHandle::Center,
+        ] {
+            for ratio in [None, Some(1.5)] {
+                let (mut within, mut plain) = (rect, rect);
+                ImageCropper::drag_crop_within(&mut within, handle, delta, size, ratio, None, unit);
+                ImageCropper::drag_crop(&mut plain, handle, delta, size, ratio);
+                assert_rect_near(within, plain);
             }
         }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if ui.button("Open Image").clicked() {
-                if let Some(path) = rfd::FileDialog::new()
-                    .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
-                    .pick_file()
-                {
-                    if let Ok(img) = image::open(&path) {
-                        self.image = Some(img);
-                        self.load_texture(ctx);
-                        self.selected_handle = None;
+    #[test]
+    fn zoom_to_crop_fills_canvas_with_crop_pixels() {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.5, 0.0),
+            egui::pos2(0.75, 0.25),
+        ));
+        cropper.zoom_to_crop();
+        // 50x25 px crop: both sides limit equally at 4x
+        assert_eq!(cropper.zoom, 4.0);
+        assert_eq!(cropper.view_center, egui::pos2(0.625, 0.125));
+
+        // A tall crop is limited by its height and centered on its pixels
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.1, 0.2),
+            egui::pos2(0.2, 0.7),
+        ));
+        cropper.zoom_to_crop();
+        assert_eq!(cropper.zoom, 2.0);
+        assert_eq!(cropper.view_center, egui::pos2(0.25, 0.45));
+
+        cropper.fit_scale = 0.5;
+        cropper.zoom_actual_size();
+        assert_eq!(cropper.zoom, 2.0);
+        assert!(cropper.actual_pixels);
+        cropper.set_zoom(1.0);
+        assert!(!cropper.actual_pixels);
+
+        // Large images zoom in; small ones shrink the canvas to their size
+        assert_eq!(ImageCropper::actual_size_view(0.5), (0.5, 2.0));
+        assert_eq!(ImageCropper::actual_size_view(4.0), (1.0, 1.0));
+    }
+
+    #[test]
+    fn export_ratios_writes_a_maximized_crop_per_ratio() {
+        let folder = temp_path("export_ratios");
+        std::fs::create_dir_all(&folder).unwrap();
+        let image = DynamicImage::new_rgb8(400, 200);
+        let ratios = [
+            AspectRatioMode::Square,
+            AspectRatioMode::R16_9,
+            AspectRatioMode::R9_16,
+        ];
+        let mut cropper = cropper_with_image(400, 200);
+        cropper.image = Some(image);
+        cropper.image_path = Some(folder.join("photo.jpg"));
+        cropper.settings.multi_ratios = ratios.to_vec();
+        cropper.settings.filename_template = "{stem}-{w}.{ext}".to_owned();
+        cropper.export_ratio_crops(&folder).unwrap();
+        assert_eq!(
+            cropper.export_status.as_deref(),
+            Some("Saved 3 ratio crops")
+        );
+
+        let size = |name: &str| image::image_dimensions(folder.join(name)).unwrap();
+        assert_eq!(size("photo_1x1-200.png"), (200, 200));
+        assert_eq!(size("photo_16x9-356.png"), (356, 200));
+        assert_eq!(size("photo_9x16-112.png"), (112, 200));
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn session_is_stored_while_editing_and_cleared_on_close() {
+        let mut cropper = cropper_with_image(10, 10);
+        cropper.image_path = Some(PathBuf::from("/photos/cat.png"));
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.1, 0.2),
+            egui::pos2(0.7, 0.9),
+        ));
+        cropper.aspect_ratio_mode = AspectRatioMode::R3_4;
+        cropper.is_portrait = true;
+        cropper.settings.autosave_secs = 30;
+        let mut storage = MemoryStorage::default();
+        eframe::App::save(&mut cropper, &mut storage);
+        let session = eframe::get_value::<Option<Session>>(&storage, Session::STORAGE_KEY)
+            .flatten()
+            .unwrap();
+        assert_eq!(session.image_path, PathBuf::from("/photos/cat.png"));
+        assert_eq!(session.crop_rect, cropper.crop_rect.unwrap());
+        assert_eq!(session.aspect_ratio, AspectRatioMode::R3_4);
+        assert!(session.is_portrait && session.has_usable_crop());
+
+        cropper.closing = true;
+        eframe::App::save(&mut cropper, &mut storage);
+        assert_eq!(
+            eframe::get_value::<Option<Session>>(&storage, Session::STORAGE_KEY),
+            Some(None)
+        );
+
+        cropper.closing = false;
+        cropper.settings.autosave_secs = 0;
+        assert_eq!(cropper.session(), None);
+
+        let mut damaged = session;
+        damaged.crop_rect = egui::Rect::from_min_max(egui::pos2(0.5, 0.5), egui::pos2(0.2, 0.2));
+        assert!(!damaged.has_usable_crop());
+        damaged.crop_rect.max.x = f32::INFINITY;
+        assert!(!damaged.has_usable_crop());
+    }
+
+    #[test]
+    fn expand_tilde_only_rewrites_a_leading_home_component() {
+        let Some(home) = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        else {
+            return;
+        };
+        let home = PathBuf::from(home);
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(expand_tilde("~/photos/a.png"), home.join("photos/a.png"));
+        assert_eq!(expand_tilde("~bob/a.png"), PathBuf::from("~bob/a.png"));
+        assert_eq!(expand_tilde("/tmp/~/a.png"), PathBuf::from("/tmp/~/a.png"));
+    }
+
+    #[test]
+    fn match_monitor_sets_reduced_custom_ratio_and_maximizes() {
+        let mut cropper = cropper_with_image(400, 400);
+        cropper.match_monitor(2560, 1440);
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::Custom);
+        assert_eq!((cropper.custom_w, cropper.custom_h), (16, 9));
+        assert!(!cropper.is_portrait);
+        // Full width of the square image, 16:9 tall, still centered
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_center_size(egui::pos2(0.5, 0.5), egui::vec2(1.0, 0.5625)),
+        );
+
+        cropper.match_monitor(1080, 1920);
+        assert_eq!((cropper.custom_w, cropper.custom_h), (9, 16));
+        assert!(cropper.is_portrait);
+    }
+
+    #[test]
+    fn constrained_drag_stays_finite_and_ordered_near_zero_size() {
+        let image_size = egui::vec2(300.0, 200.0);
+        let handles = [
+            ResizeHandle::TopLeft,
+            ResizeHandle::TopRight,
+            ResizeHandle::BottomLeft,
+            ResizeHandle::BottomRight,
+            ResizeHandle::Left,
+            ResizeHandle::Right,
+            ResizeHandle::Top,
+            ResizeHandle::Bottom,
+        ];
+        let ratios = AspectRatioMode::ALL.iter().filter_map(|mode| mode.ratio());
+        for ratio in ratios.chain([0.01, 100.0]) {
+            for handle in handles {
+                let mut rect = egui::Rect::from_min_max(egui::pos2(0.4, 0.4), egui::pos2(0.6, 0.6));
+                // Shrink inwards in ever smaller steps, then overshoot past the anchor
+                for step in [0.05, 0.01, 0.001, 1e-6, 0.0, 0.3] {
+                    let inward = match handle {
+                        ResizeHandle::TopLeft => egui::vec2(step, step),
+                        ResizeHandle::TopRight => egui::vec2(-step, step),
+                        ResizeHandle::BottomLeft => egui::vec2(step, -step),
+                        ResizeHandle::BottomRight => egui::vec2(-step, -step),
+                        ResizeHandle::Left => egui::vec2(step, 0.0),
+                        ResizeHandle::Right => egui::vec2(-step, 0.0),
+                        ResizeHandle::Top => egui::vec2(0.0, step),
+                        _ => egui::vec2(0.0, -step),
+                    };
+                    for _ in 0..10 {
+                        ImageCropper::drag_crop(&mut rect, handle, inward, image_size, Some(ratio));
+                        let context = format!("{:?} at ratio {}: {:?}", handle, ratio, rect);
+                        assert!(
+                            [rect.min.x, rect.min.y, rect.max.x, rect.max.y]
+                                .iter()
+                                .all(|v| v.is_finite()),
+                            "{}",
+                            context
+                        );
+                        assert!(
+                            rect.min.x < rect.max.x && rect.min.y < rect.max.y,
+                            "{}",
+                            context
+                        );
+                        assert!(
+                            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0))
+                                .contains_rect(rect),
+                            "{}",
+                            context
+                        );
                     }
                 }
             }
+        }
+    }
 
-            if self.texture.is_some() {
-                ui.horizontal(|ui| {
-                    ui.label("Aspect Ratio:");
-                    let mut changed = false;
-                    egui::ComboBox::from_id_salt("params_aspect_ratio")
-                        .selected_text(format!("{}", self.aspect_ratio_mode))
-                        .show_ui(ui, |ui| {
-                            changed |= ui
-                                .selectable_value(
-                                    &mut self.aspect_ratio_mode,
-                                    AspectRatioMode::Free,
-                                    "Free",
-                                )
-                                .changed();
-                            changed |= ui
-                                .selectable_value(
-                                    &mut self.aspect_ratio_mode,
-                                    AspectRatioMode::Original,
-                                    "Original",
-                                )
-                                .changed();
-                            changed |= ui
-                                .selectable_value(
-                                    &mut self.aspect_ratio_mode,
-                                    AspectRatioMode::Square,
-                                    "1:1",
-                                )
-                                .changed();
+    #[test]
+    fn image_path_from_text_accepts_only_existing_image_files() {
+        let image = temp_path("clipboard.PNG");
+        DynamicImage::new_rgb8(1, 1)
+            .save_with_format(&image, image::ImageFormat::Png)
+            .unwrap();
+        let other = temp_path("clipboard.txt");
+        std::fs::write(&other, "hi").unwrap();
 
-                            ui.separator();
-                            if !self.is_portrait {
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R3_2,
-                                        "3:2",
-                                    )
-                                    .changed();
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R4_3,
-                                        "4:3",
-                                    )
-                                    .changed();
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R16_9,
-                                        "16:9",
-                                    )
-                                    .changed();
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R16_10,
-                                        "16:10",
-                                    )
-                                    .changed();
-                            } else {
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R2_3,
-                                        "2:3",
-                                    )
-                                    .changed();
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R3_4,
-                                        "3:4",
-                                    )
-                                    .changed();
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R9_16,
-                                        "9:16",
-                                    )
-                                    .changed();
-                                changed |= ui
-                                    .selectable_value(
-                                        &mut self.aspect_ratio_mode,
-                                        AspectRatioMode::R10_16,
-                                        "10:16",
-                                    )
-                                    .changed();
-                            }
+        let text = format!("  {}\n", image.display());
+        assert_eq!(image_path_from_text(&text), Some(image.clone()));
+        let url = format!("file://{}", image.display());
+        assert_eq!(image_path_from_text(&url), Some(image.clone()));
+        assert_eq!(image_path_from_text(&other.display().to_string()), None);
+        assert_eq!(image_path_from_text("/no/such/file.png"), None);
+        assert_eq!(image_path_from_text("some copied sentence"), None);
+        let two = format!("{}\n{}", image.display(), image.display());
+        assert_eq!(image_path_from_text(&two), None);
+
+        std::fs::remove_file(image).unwrap();
+        std::fs::remove_file(other).unwrap();
+    }
+
+    #[test]
+    fn bake_crop_replaces_image_and_undo_restores_it() {
+        let ctx = egui::Context::default();
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.5),
+            egui::pos2(0.75, 1.0),
+        ));
+        cropper.bake_crop(&ctx).unwrap();
+        let image = cropper.image.as_ref().unwrap();
+        assert_eq!((image.width(), image.height()), (100, 50));
+        assert_eq!(
+            cropper.crop_rect,
+            Some(egui::Rect::from_min_max(
+                egui::Pos2::ZERO,
+                egui::pos2(1.0, 1.0)
+            ))
+        );
+
+        // Crop again, then step back twice to the original
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.0, 0.0),
+            egui::pos2(0.5, 0.5),
+        ));
+        cropper.bake_crop(&ctx).unwrap();
+        assert_eq!(cropper.image.as_ref().unwrap().width(), 50);
+        cropper.undo_transform(&ctx);
+        assert_eq!(cropper.image.as_ref().unwrap().width(), 100);
+        cropper.undo_transform(&ctx);
+        assert_eq!(cropper.image.as_ref().unwrap().width(), 200);
+        assert_eq!(
+            cropper.crop_rect,
+            Some(egui::Rect::from_min_max(
+                egui::pos2(0.25, 0.5),
+                egui::pos2(0.75, 1.0)
+            ))
+        );
+        assert!(cropper.transform_history.is_empty());
+    }
+
+    #[test]
+    fn filename_template_expands_tokens_and_rejects_bad_input() {
+        let parts = filename::NameParts {
+            stem: "cat",
+            width: 1920,
+            height: 1080,
+            ratio: "16x9",
+            extension: "jpg",
+        };
+        assert_eq!(
+            filename::expand("{stem}_{w}x{h}_{ratio}.{ext}", &parts).unwrap(),
+            "cat_1920x1080_16x9.jpg"
+        );
+        assert_eq!(
+            filename::expand("{{{stem}}}.{ext}", &parts).unwrap(),
+            "{cat}.jpg"
+        );
+        for bad in ["{stem", "{size}.png", "a}.png", "out/{stem}.png", "   "] {
+            assert!(filename::validate(bad).is_err(), "{:?} accepted", bad);
+        }
+    }
+
+    #[test]
+    fn suggested_file_name_uses_crop_size_and_ratio() {
+        let mut cropper = cropper_with_image(400, 200);
+        cropper.image_path = Some(PathBuf::from("/photos/Beach.JPG"));
+        cropper.settings.filename_template = "{stem}_{w}x{h}_{ratio}.{ext}".to_owned();
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.0, 0.0),
+            egui::pos2(0.5, 1.0),
+        ));
+        assert_eq!(
+            cropper.suggested_file_name().unwrap().as_deref(),
+            Some("Beach_200x200_1x1.jpg")
+        );
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.0, 0.0),
+            egui::pos2(0.3, 0.5),
+        ));
+        assert_eq!(
+            cropper.suggested_file_name().unwrap().as_deref(),
+            Some("Beach_120x100_1.20.jpg")
+        );
+    }
+
+    #[test]
+    fn protect_source_refuses_to_overwrite_the_open_image() {
+        let source = temp_path("protect_source.png");
+        DynamicImage::new_rgb8(4, 4).save(&source).unwrap();
+        let mut cropper = cropper_with_image(4, 4);
+        cropper.image_path = Some(source.clone());
+        cropper.settings.protect_source = true;
+
+        assert!(matches!(
+            cropper.check_not_source(&source),
+            Err(CropperError::OverwriteSource(_))
+        ));
+        // The same file reached through a different spelling is still caught
+        let dotted = source
+            .parent()
+            .unwrap()
+            .join(".")
+            .join(source.file_name().unwrap());
+        assert!(cropper.check_not_source(&dotted).is_err());
+        assert!(
+            cropper
+                .check_not_source(&temp_path("protect_other.png"))
+                .is_ok()
+        );
+
+        cropper.settings.protect_source = false;
+        assert!(cropper.check_not_source(&source).is_ok());
+        std::fs::remove_file(source).unwrap();
+    }
+    /// 4×2 image whose pixel at (x, y) has red = x and green = y.
+    fn coordinate_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(4, 2, |x, y| {
+            image::Rgba([x as u8, y as u8, 0, 255])
+        }))
+    }
+
+    #[test]
+    fn compute_crop_selects_the_expected_pixels() {
+        let image = coordinate_image();
+        let pixel = |image: &DynamicImage, x, y| image.to_rgba8().get_pixel(x, y).0;
+
+        let full = compute_crop(
+            &image,
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+        );
+        assert_eq!((full.width(), full.height()), (4, 2));
+
+        // Right half, bottom row: starts at pixel (2, 1)
+        let quarter = compute_crop(
+            &image,
+            egui::Rect::from_min_max(egui::pos2(0.5, 0.5), egui::pos2(1.0, 1.0)),
+        );
+        assert_eq!((quarter.width(), quarter.height()), (2, 1));
+        assert_eq!(pixel(&quarter, 0, 0), [2, 1, 0, 255]);
+        assert_eq!(pixel(&quarter, 1, 0), [3, 1, 0, 255]);
+
+        // A sliver thinner than a pixel still yields one pixel
+        let sliver = compute_crop(
+            &image,
+            egui::Rect::from_min_max(egui::pos2(0.26, 0.0), egui::pos2(0.27, 1.0)),
+        );
+        assert_eq!((sliver.width(), sliver.height()), (1, 2));
+        assert_eq!(pixel(&sliver, 0, 1), [1, 1, 0, 255]);
+
+        // Past the left edge: one transparent column, then the image
+        let padded = compute_crop(
+            &image,
+            egui::Rect::from_min_max(egui::pos2(-0.25, 0.0), egui::pos2(0.5, 1.0)),
+        );
+        assert_eq!((padded.width(), padded.height()), (3, 2));
+        assert_eq!(pixel(&padded, 0, 0)[3], 0);
+        assert_eq!(pixel(&padded, 1, 1), [0, 1, 0, 255]);
+    }
+
+    #[test]
+    fn crop_history_undoes_drags_and_ratio_changes() {
+        let mut cropper = centered_quarter_crop();
+        let start = cropper.crop_rect.unwrap();
+        cropper.record_crop();
+
+        drag_math(
+            &mut cropper,
+            egui::pos2(320.0, 170.0),
+            egui::pos2(360.0, 190.0),
+        );
+        cropper.record_crop(); // as on drag_stopped
+        let dragged = cropper.crop_rect.unwrap();
+        assert_ne!(dragged, start);
+
+        cropper.aspect_ratio_mode = AspectRatioMode::Square;
+        cropper.apply_aspect_ratio();
+        let squared = cropper.crop_rect.unwrap();
+        assert_ne!(squared, dragged);
+
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, Some(dragged));
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::Free);
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, Some(start));
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, Some(start));
+
+        cropper.redo_crop();
+        cropper.redo_crop();
+        assert_eq!(cropper.crop_rect, Some(squared));
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::Square);
+        assert!(!cropper.crop_history.can_redo());
+
+        // A new edit after undoing drops the redo branch
+        cropper.undo_crop();
+        cropper.crop_rect = Some(start);
+        cropper.record_crop();
+        assert!(!cropper.crop_history.can_redo());
+    }
+
+    #[test]
+    fn crop_history_is_capped() {
+        let mut history = history::CropHistory::default();
+        for i in 0..history::CROP_HISTORY + 10 {
+            history.record(CropState {
+                rect: egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1.0, i as f32)),
+                aspect_ratio: AspectRatioMode::Free,
+                is_portrait: false,
+            });
+        }
+        let mut undos = 0;
+        while history.undo().is_some() {
+            undos += 1;
+        }
+        assert_eq!(undos, history::CROP_HISTORY - 1);
+    }
+
+    #[test]
+    fn rotate_turns_image_and_ratio_and_round_trips() {
+        let ctx = egui::Context::default();
+        let mut cropper = ImageCropper {
+            image: Some(coordinate_image()),
+            aspect_ratio_mode: AspectRatioMode::R4_3,
+            ..Default::default()
+        };
+        cropper.rotate_image(&ctx, true);
+        let image = cropper.image.as_ref().unwrap();
+        assert_eq!((image.width(), image.height()), (2, 4));
+        // Clockwise: the bottom-left pixel (0, 1) moves to the top-left
+        assert_eq!(image.to_rgba8().get_pixel(0, 0).0, [0, 1, 0, 255]);
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::R3_4);
+        assert!(cropper.is_portrait);
+        // The crop is reset to a 3:4 rect of the rotated image
+        let rect = cropper.crop_rect.unwrap();
+        assert!((rect.width() * 2.0 / (rect.height() * 4.0) - 0.75).abs() < 1e-4);
+        assert_eq!(cropper.output_image().unwrap().width(), 2);
+
+        cropper.rotate_image(&ctx, false);
+        assert_eq!(
+            cropper.image.as_ref().unwrap().to_rgba8(),
+            coordinate_image().to_rgba8()
+        );
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::R4_3);
+
+        // Two turns the same way flip the image upside down
+        cropper.rotate_image(&ctx, true);
+        cropper.rotate_image(&ctx, true);
+        assert_eq!(
+            cropper.image.as_ref().unwrap().to_rgba8().get_pixel(0, 0).0,
+            [3, 1, 0, 255]
+        );
+    }
+
+    #[test]
+    fn pixel_crop_rect_round_trips_and_clamps() {
+        let rect = pixel_crop_rect(3840, 2160, (100, 50, 1920, 1080), None);
+        assert_eq!(crop_pixel_bounds(3840, 2160, rect), (100, 50, 1920, 1080));
+
+        // Too wide for the room right of x: clamped to the edge
+        let rect = pixel_crop_rect(400, 200, (300, 0, 500, 100), None);
+        assert_eq!(crop_pixel_bounds(400, 200, rect), (300, 0, 100, 100));
+        // Position past the image and zero sizes are pulled back in
+        let rect = pixel_crop_rect(400, 200, (999, 999, 0, 0), None);
+        assert_eq!(crop_pixel_bounds(400, 200, rect), (399, 199, 1, 1));
+
+        // A locked 2:1 crop that overflows shrinks in both directions
+        let rect = pixel_crop_rect(400, 200, (200, 0, 400, 200), Some(2.0));
+        assert_eq!(crop_pixel_bounds(400, 200, rect), (200, 0, 200, 100));
+    }
+
+    #[test]
+    fn jpeg_saves_honor_quality_and_png_is_unaffected() {
+        // Noise compresses poorly, so quality shows up clearly in the size
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 37 + y * 91) as u8, (x * y) as u8, (x ^ y) as u8 * 4])
+        }));
+        let size_at = |quality| {
+            let path = temp_path(&format!("quality_{}.jpg", quality));
+            export::save_image_with_profile(&image, &path, None, quality).unwrap();
+            let size = std::fs::metadata(&path).unwrap().len();
+            assert_eq!(
+                image::ImageFormat::from_path(&path).unwrap(),
+                image::ImageFormat::Jpeg
+            );
+            std::fs::remove_file(path).unwrap();
+            size
+        };
+        assert!(size_at(95) > size_at(20));
+
+        let png = temp_path("quality.png");
+        export::save_image_with_profile(&image, &png, None, 1).unwrap();
+        assert_eq!(image::open(&png).unwrap().to_rgb8(), image.to_rgb8());
+        std::fs::remove_file(png).unwrap();
+    }
+
+    #[test]
+    fn full_rect_saves_the_exact_source_dimensions() {
+        for (w, h) in [(1, 1), (3, 7), (641, 479), (1920, 1081)] {
+            let cropper = cropper_with_image(w, h);
+            let path = temp_path(&format!("full_{}x{}.png", w, h));
+            export::save_image_with_profile(&cropper.output_image().unwrap(), &path, None, 90)
+                .unwrap();
+            assert_eq!(image::image_dimensions(&path).unwrap(), (w, h));
+            std::fs::remove_file(path).unwrap();
+        }
+
+        // A crop dragged against the right and bottom edges keeps the last column and row
+        let mut cropper = ImageCropper {
+            image: Some(coordinate_image()),
+            crop_rect: Some(egui::Rect::from_min_max(
+                egui::pos2(0.5, 0.5),
+                egui::pos2(0.9, 0.9),
+            )),
+            ..Default::default()
+        };
+        cropper.step_crop(ResizeHandle::BottomRight, egui::vec2(1.0, 1.0));
+        let output = cropper.output_image().unwrap().to_rgba8();
+        assert_eq!(output.dimensions(), (2, 1));
+        assert_eq!(output.get_pixel(1, 0).0, [3, 1, 0, 255]);
+    }
+
+    #[test]
+    fn zoom_at_keeps_the_anchor_under_the_pointer() {
+        let canvas = egui::vec2(200.0, 100.0);
+        let screen = |zoom: f32, center: egui::Pos2, p: egui::Pos2| (p - center) * canvas * zoom;
+
+        let anchor = egui::pos2(0.7, 0.6);
+        let center = egui::pos2(0.5, 0.5);
+        let (zoom, zoomed_center) = ImageCropper::zoom_at(1.0, center, 2.0, anchor);
+        assert_eq!(zoom, 2.0);
+        let (before, after) = (
+            screen(1.0, center, anchor),
+            screen(zoom, zoomed_center, anchor),
+        );
+        assert!((before - after).length() < 1e-4, "{before:?} vs {after:?}");
+
+        // Panning stays inside the image
+        let panned =
+            ImageCropper::pan_view(zoomed_center, zoom, egui::vec2(-1000.0, 0.0), canvas * zoom);
+        assert_eq!(panned.x, 0.75);
+
+        // Zooming back out recenters within the clamp
+        let (zoom, center) = ImageCropper::zoom_at(zoom, panned, 0.5, anchor);
+        assert_eq!(zoom, 1.0);
+        assert_eq!(center, egui::pos2(0.5, 0.5));
+    }
+
+    #[test]
+    fn flip_mirrors_image_and_keeps_the_crop_on_the_same_content() {
+        let ctx = egui::Context::default();
+        let mut cropper = ImageCropper {
+            image: Some(coordinate_image()),
+            ..Default::default()
+        };
+        cropper.load_texture(&ctx);
+        // Columns 0..1 of row 1
+        let crop = egui::Rect::from_min_max(egui::pos2(0.0, 0.5), egui::pos2(0.25, 1.0));
+        cropper.crop_rect = Some(crop);
+        let before = cropper.output_image().unwrap().to_rgba8();
+
+        cropper.flip_image(&ctx, true);
+        assert_eq!(
+            cropper.image.as_ref().unwrap().to_rgba8().get_pixel(0, 0).0,
+            [3, 0, 0, 255]
+        );
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.75, 0.5), egui::pos2(1.0, 1.0)),
+        );
+        assert_eq!(cropper.output_image().unwrap().to_rgba8(), before);
+
+        cropper.flip_image(&ctx, false);
+        assert_eq!(
+            cropper.image.as_ref().unwrap().to_rgba8().get_pixel(0, 0).0,
+            [3, 1, 0, 255]
+        );
+        assert_eq!(cropper.output_image().unwrap().to_rgba8(), before);
+
+        // Each flip can be reverted
+        cropper.undo_transform(&ctx);
+        cropper.undo_transform(&ctx);
+        assert_eq!(
+            cropper.image.as_ref().unwrap().to_rgba8(),
+            coordinate_image().to_rgba8()
+        );
+        assert_eq!(cropper.crop_rect, Some(crop));
+    }
+
+    #[test]
+    fn move_crop_nudges_by_whole_pixels_and_stops_at_the_edge() {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.25),
+            egui::pos2(0.75, 0.75),
+        ));
+        cropper.move_crop(ResizeHandle::Center, egui::vec2(1.0, 0.0));
+        assert_eq!(
+            crop_pixel_bounds(200, 100, cropper.crop_rect.unwrap()),
+            (51, 25, 100, 50)
+        );
+        cropper.move_crop(ResizeHandle::Center, egui::vec2(0.0, -10.0));
+        assert_eq!(
+            crop_pixel_bounds(200, 100, cropper.crop_rect.unwrap()),
+            (51, 15, 100, 50)
+        );
+
+        // Nudging past the edge keeps the size and stops at the border
+        cropper.move_crop(ResizeHandle::Center, egui::vec2(-500.0, 0.0));
+        assert_eq!(
+            crop_pixel_bounds(200, 100, cropper.crop_rect.unwrap()),
+            (0, 15, 100, 50)
+        );
+    }
+
+    #[test]
+    fn resizing_stops_at_the_minimum_crop_size() {
+        let image_size = egui::vec2(400.0, 200.0);
+        let px = |rect: egui::Rect| rect.size() * image_size;
+        let start = egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.75));
+
+        // Free: each dragged edge stops MIN_CROP_PX short of the opposite one
+        for (handle, inward) in [
+            (ResizeHandle::TopLeft, egui::vec2(2.0, 2.0)),
+            (ResizeHandle::BottomRight, egui::vec2(-2.0, -2.0)),
+            (ResizeHandle::Left, egui::vec2(2.0, 0.0)),
+            (ResizeHandle::Bottom, egui::vec2(0.0, -2.0)),
+        ] {
+            let mut rect = start;
+            ImageCropper::drag_crop(&mut rect, handle, inward, image_size, None);
+            let size = px(rect);
+            assert!(size.x >= MIN_CROP_PX - 1e-3 && size.y >= MIN_CROP_PX - 1e-3);
+            assert!(start.contains_rect(rect), "{handle:?} flipped to {rect:?}");
+        }
+        let mut rect = start;
+        ImageCropper::drag_crop(
+            &mut rect,
+            ResizeHandle::BottomRight,
+            egui::vec2(-2.0, -2.0),
+            image_size,
+            None,
+        );
+        assert_rect_near(
+            rect,
+            egui::Rect::from_min_size(start.min, egui::Vec2::splat(MIN_CROP_PX) / image_size),
+        );
+
+        // Locked 16:9: the shorter side is the one held at the minimum
+        let mut rect = start;
+        ImageCropper::drag_crop(
+            &mut rect,
+            ResizeHandle::BottomRight,
+            egui::vec2(-2.0, -2.0),
+            image_size,
+            Some(16.0 / 9.0),
+        );
+        let size = px(rect);
+        assert!((size.y - MIN_CROP_PX).abs() < 1e-3, "{size:?}");
+        assert!((size.x / size.y - 16.0 / 9.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn select_whole_image_resets_to_the_largest_crop_of_the_ratio() {
+        let mut cropper = cropper_with_image(400, 200);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.1, 0.2),
+            egui::pos2(0.3, 0.4),
+        ));
+        cropper.selected_handle = Some(ResizeHandle::Center);
+        cropper.select_whole_image();
+        assert_eq!(
+            cropper.crop_rect,
+            Some(egui::Rect::from_min_max(
+                egui::Pos2::ZERO,
+                egui::pos2(1.0, 1.0)
+            ))
+        );
+        assert_eq!(cropper.selected_handle, None);
+
+        cropper.aspect_ratio_mode = AspectRatioMode::Square;
+        cropper.select_whole_image();
+        assert_eq!(
+            crop_pixel_bounds(400, 200, cropper.crop_rect.unwrap()),
+            (100, 0, 200, 200)
+        );
+        // One undo goes back to the full frame
+        cropper.undo_crop();
+        assert_eq!(
+            crop_pixel_bounds(400, 200, cropper.crop_rect.unwrap()),
+            (0, 0, 400, 200)
+        );
+    }
+
+    #[test]
+    fn clipboard_image_holds_the_cropped_rgba_pixels() {
+        let cropper = ImageCropper {
+            image: Some(coordinate_image()),
+            crop_rect: Some(egui::Rect::from_min_max(
+                egui::pos2(0.5, 0.5),
+                egui::pos2(1.0, 1.0),
+            )),
+            ..Default::default()
+        };
+        let data = clipboard_image(&cropper.output_image().unwrap());
+        assert_eq!((data.width, data.height), (2, 1));
+        assert_eq!(&data.bytes[..], &[2, 1, 0, 255, 3, 1, 0, 255]);
+    }
+
+    #[test]
+    fn clipboard_pixels_round_trip_through_image_conversion() {
+        let data = clipboard_image(&coordinate_image());
+        let image = image_from_clipboard(data).unwrap();
+        assert_eq!(image.to_rgba8(), coordinate_image().to_rgba8());
+
+        // A buffer that does not match its stated size is rejected
+        let short = arboard::ImageData {
+            width: 4,
+            height: 2,
+            bytes: vec![0; 4].into(),
+        };
+        assert!(image_from_clipboard(short).is_none());
+    }
+
+    #[test]
+    fn webp_tiff_and_gif_round_trip_through_open_and_save() {
+        let mut cropper = ImageCropper {
+            image: Some(coordinate_image()),
+            crop_rect: Some(egui::Rect::from_min_max(
+                egui::pos2(0.25, 0.0),
+                egui::pos2(0.75, 1.0),
+            )),
+            ..Default::default()
+        };
+        let output = cropper.output_image().unwrap();
+        for extension in ["webp", "tiff", "tif", "gif"] {
+            assert!(IMAGE_EXTENSIONS.contains(&extension));
+            let path = temp_path(&format!("round_trip.{}", extension));
+            export::save_image_with_profile(&output, &path, None, 90).unwrap();
+            let expected = image::ImageFormat::from_extension(extension).unwrap();
+            assert_eq!(image::ImageFormat::from_path(&path).unwrap(), expected);
+
+            let ctx = egui::Context::default();
+            cropper.open_image(&ctx, &path).unwrap();
+            let image = cropper.image.as_ref().unwrap();
+            assert_eq!((image.width(), image.height()), (2, 2), "{extension}");
+            // GIF quantizes to a palette, the others are lossless
+            if extension != "gif" {
+                assert_eq!(image.to_rgba8(), output.to_rgba8(), "{extension}");
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+
+        // 16-bit images are converted for formats limited to 8 bits
+        let deep = DynamicImage::ImageRgba16(image::ImageBuffer::new(3, 2));
+        for extension in ["webp", "gif"] {
+            let path = temp_path(&format!("deep.{}", extension));
+            export::save_image_with_profile(&deep, &path, None, 90).unwrap();
+            assert_eq!(image::image_dimensions(&path).unwrap(), (3, 2));
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn recent_files_keep_the_newest_unique_paths() {
+        let mut recent = RecentFiles::default();
+        for i in 0..recent::RECENT_FILES + 2 {
+            recent.push(PathBuf::from(format!("/photos/{}.jpg", i)));
+        }
+        recent.push(PathBuf::from("/photos/5.jpg"));
+        let paths: Vec<&Path> = recent.paths().collect();
+        assert_eq!(paths.len(), recent::RECENT_FILES);
+        assert_eq!(paths[0], Path::new("/photos/5.jpg"));
+        assert_eq!(paths[1], Path::new("/photos/11.jpg"));
+        assert!(!paths.contains(&Path::new("/photos/1.jpg")));
+        assert_eq!(
+            paths
+                .iter()
+                .filter(|p| **p == Path::new("/photos/5.jpg"))
+                .count(),
+            1
+        );
+
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, RecentFiles::STORAGE_KEY, &recent);
+        assert_eq!(
+            eframe::get_value::<RecentFiles>(&storage, RecentFiles::STORAGE_KEY),
+            Some(recent.clone())
+        );
+
+        recent.remove(Path::new("/photos/5.jpg"));
+        assert_eq!(recent.paths().next(), Some(Path::new("/photos/11.jpg")));
+    }
+
+    #[test]
+    fn opening_a_file_adds_it_to_recent_files() {
+        let path = temp_path("recent_open.png");
+        coordinate_image().save(&path).unwrap();
+        let mut cropper = ImageCropper::default();
+        cropper
+            .open_image(&egui::Context::default(), &path)
+            .unwrap();
+        assert_eq!(cropper.recent_files.paths().next(), Some(path.as_path()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn circular_crop_clears_the_corners_and_suggests_png() {
+        let mut cropper = cropper_with_image(40, 20);
+        cropper.image_path = Some(PathBuf::from("/photos/face.jpg"));
+        cropper.circular = true;
+        let output = cropper.output_image().unwrap().to_rgba8();
+        assert_eq!(output.dimensions(), (40, 20));
+        // Corners are cleared, the center and edge midpoints are kept
+        for (x, y) in [(0, 0), (39, 0), (0, 19), (39, 19), (3, 2)] {
+            assert_eq!(output.get_pixel(x, y).0, [0; 4], "({x}, {y})");
+        }
+        for (x, y) in [(20, 10), (0, 10), (20, 0), (39, 10)] {
+            assert!(output.get_pixel(x, y).0[3] > 0, "({x}, {y})");
+        }
+        assert!(
+            cropper
+                .suggested_file_name()
+                .unwrap()
+                .is_some_and(|name| name.ends_with(".png"))
+        );
+        assert!(export::drops_alpha(Path::new("face.jpg")));
+        assert!(!export::drops_alpha(Path::new("face.png")));
+
+        // Off, the crop is untouched
+        cropper.circular = false;
+        assert_eq!(
+            cropper.output_image().unwrap().to_rgba8().get_pixel(0, 0).0[3],
+            255
+        );
+    }
+
+    #[test]
+    fn status_line_shows_path_size_and_zoom() {
+        let mut cropper = ImageCropper::default();
+        assert_eq!(cropper.status_line(), "No image");
+
+        cropper = cropper_with_image(640, 480);
+        cropper.zoom = 1.5;
+        assert_eq!(
+            cropper.status_line(),
+            "Pasted image  ·  640 × 480 px  ·  150%"
+        );
+        cropper.image_path = Some(PathBuf::from("/photos/beach.jpg"));
+        assert_eq!(
+            cropper.status_line(),
+            "/photos/beach.jpg  ·  640 × 480 px  ·  150%"
+        );
+    }
+
+    #[test]
+    fn golden_preset_turns_with_the_orientation() {
+        let mut cropper = cropper_with_image(1000, 1000);
+        cropper.aspect_ratio_mode = AspectRatioMode::Golden;
+        cropper.apply_aspect_ratio();
+        assert_eq!(
+            crop_pixel_bounds(1000, 1000, cropper.crop_rect.unwrap()),
+            (0, 191, 1000, 618)
+        );
+
+        cropper.swap_ratio_orientation();
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::GoldenPortrait);
+        cropper.apply_aspect_ratio();
+        assert_eq!(
+            crop_pixel_bounds(1000, 1000, cropper.crop_rect.unwrap()),
+            (191, 0, 618, 1000)
+        );
+        assert_eq!(
+            AspectRatioMode::from_key("golden_portrait"),
+            Some(AspectRatioMode::GoldenPortrait)
+        );
+    }
+
+    #[test]
+    fn handles_map_to_matching_resize_cursors() {
+        let rect = egui::Rect::from_min_max(egui::pos2(100.0, 100.0), egui::pos2(300.0, 200.0));
+        let cursor = |pos| ImageCropper::hit_test(pos, rect).map(|h| h.cursor_icon(false));
+        assert_eq!(cursor(rect.left_top()), Some(egui::CursorIcon::ResizeNwSe));
+        assert_eq!(
+            cursor(rect.right_bottom()),
+            Some(egui::CursorIcon::ResizeNwSe)
+        );
+        assert_eq!(cursor(rect.right_top()), Some(egui::CursorIcon::ResizeNeSw));
+        assert_eq!(
+            cursor(rect.left_bottom()),
+            Some(egui::CursorIcon::ResizeNeSw)
+        );
+        assert_eq!(
+            cursor(rect.left_center()),
+            Some(egui::CursorIcon::ResizeHorizontal)
+        );
+        assert_eq!(
+            cursor(rect.center_bottom()),
+            Some(egui::CursorIcon::ResizeVertical)
+        );
+        assert_eq!(cursor(rect.center()), Some(egui::CursorIcon::Grab));
+        assert_eq!(
+            ResizeHandle::Center.cursor_icon(true),
+            egui::CursorIcon::Grabbing
+        );
+    }
+
+    #[test]
+    fn custom_output_size_scales_the_crop() {
+        assert_eq!(scaled_size((400, 300), (0, 0)), (400, 300));
+        assert_eq!(scaled_size((400, 300), (200, 0)), (200, 150));
+        assert_eq!(scaled_size((400, 300), (0, 90)), (120, 90));
+        assert_eq!(scaled_size((400, 300), (64, 64)), (64, 64));
+        assert_eq!(scaled_size((1000, 1), (10, 0)), (10, 1));
+
+        let mut cropper = cropper_with_image(400, 300);
+        cropper.size_preset = SizePreset::Custom;
+        assert_eq!(cropper.output_image().unwrap().width(), 400);
+        cropper.output_size = (100, 0);
+        cropper.resize_filter = ResizeFilter::Nearest;
+        let output = cropper.output_image().unwrap();
+        assert_eq!((output.width(), output.height()), (100, 75));
+        cropper.settings.filename_template = "{w}x{h}.{ext}".to_owned();
+        assert_eq!(
+            cropper.suggested_file_name().unwrap().as_deref(),
+            Some("100x75.png")
+        );
+    }
+
+    #[test]
+    fn side_handles_keep_the_ratio_against_the_edges() {
+        let image_size = egui::vec2(1600.0, 900.0);
+        let ratio = 16.0 / 9.0;
+        let pixel_ratio = |rect: egui::Rect| {
+            let size = rect.size() * image_size;
+            size.x / size.y
+        };
+        let inside = |rect: egui::Rect| {
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0))
+                .expand(1e-5)
+                .contains_rect(rect)
+        };
+
+        // A 16:9 crop near the top edge, its top handle dragged well past it
+        let start = egui::Rect::from_min_max(egui::pos2(0.3, 0.05), egui::pos2(0.6, 0.35));
+        let mut rect = start;
+        ImageCropper::drag_crop(
+            &mut rect,
+            ResizeHandle::Top,
+            egui::vec2(0.0, -0.3),
+            image_size,
+            Some(ratio),
+        );
+        assert!(inside(rect), "{rect:?}");
+        assert!(
+            (pixel_ratio(rect) - ratio).abs() < 1e-3,
+            "{}",
+            pixel_ratio(rect)
+        );
+        assert!((rect.min.y - 0.0).abs() < 1e-5 && (rect.max.y - start.max.y).abs() < 1e-5);
+
+        // Same for a left handle dragged past the left edge
+        let mut rect = start;
+        ImageCropper::drag_crop(
+            &mut rect,
+            ResizeHandle::Left,
+            egui::vec2(-0.5, 0.0),
+            image_size,
+            Some(ratio),
+        );
+        assert!(inside(rect), "{rect:?}");
+        assert!(
+            (pixel_ratio(rect) - ratio).abs() < 1e-3,
+            "{}",
+            pixel_ratio(rect)
+        );
+        assert!((rect.max.x - start.max.x).abs() < 1e-5);
+    }
+
+    #[test]
+    fn straighten_turns_the_image_and_crops_out_the_corners() {
+        let ctx = egui::Context::default();
+        let mut cropper = ImageCropper {
+            image: Some(DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                200,
+                100,
+                image::Rgba([10, 20, 30, 255]),
+            ))),
+            ..Default::default()
+        };
+        cropper.load_texture(&ctx);
+        cropper.straighten_deg = 10.0;
+        cropper.apply_straighten(&ctx);
+
+        let image = cropper.image.as_ref().unwrap();
+        // 200·cos10° + 100·sin10° by 200·sin10° + 100·cos10°
+        assert_eq!((image.width(), image.height()), (214, 133));
+        assert_eq!(image.to_rgba8().get_pixel(0, 0).0[3], 0);
+
+        // The crop keeps the photo's 2:1 shape and holds only image pixels
+        let output = cropper.output_image().unwrap().to_rgba8();
+        let ratio = output.width() as f32 / output.height() as f32;
+        assert!((ratio - 2.0).abs() < 0.05, "{ratio}");
+        assert!((145..200).contains(&output.width()), "{}", output.width());
+        assert!(output.pixels().all(|p| p.0[3] == 255));
+        assert_eq!(
+            cropper.target_ratio(),
+            None,
+            "Free stays free after straightening"
+        );
+        cropper.aspect_ratio_mode = AspectRatioMode::Original;
+        assert_eq!(cropper.target_ratio(), Some(2.0));
+
+        // Back to zero restores the untouched image and the full frame
+        cropper.straighten_deg = 0.0;
+        cropper.apply_straighten(&ctx);
+        let image = cropper.image.as_ref().unwrap();
+        assert_eq!((image.width(), image.height()), (200, 100));
+        assert!(cropper.straighten_base.is_none());
+        assert_eq!(
+            crop_pixel_bounds(200, 100, cropper.crop_rect.unwrap()),
+            (0, 0, 200, 100)
+        );
+    }
+
+    #[test]
+    fn exif_orientation_is_applied_on_open_unless_turned_off() {
+        // A minimal little-endian EXIF block: one IFD entry, Orientation = 6
+        // (stored turned a quarter turn counter-clockwise)
+        let exif = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, // TIFF header, IFD at offset 8
+            1, 0, // one entry
+            0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, // Orientation, SHORT, 1, 6
+            0, 0, 0, 0, // no next IFD
+        ];
+        let path = temp_path("exif_rotated.png");
+        let mut encoder =
+            image::codecs::png::PngEncoder::new(std::fs::File::create(&path).unwrap());
+        image::ImageEncoder::set_exif_metadata(&mut encoder, exif.to_vec()).unwrap();
+        coordinate_image().write_with_encoder(encoder).unwrap();
+
+        let ctx = egui::Context::default();
+        let mut cropper = ImageCropper::default();
+        cropper.settings.auto_orient = true;
+        cropper.open_image(&ctx, &path).unwrap();
+        let image = cropper.image.as_ref().unwrap();
+        assert_eq!((image.width(), image.height()), (2, 4));
+        assert_eq!(cropper.auto_rotated, Some(Orientation::Rotate90));
+        assert!(cropper.status_line().ends_with("rotated upright (EXIF)"));
+
+        cropper.settings.auto_orient = false;
+        cropper.open_image(&ctx, &path).unwrap();
+        let image = cropper.image.as_ref().unwrap();
+        assert_eq!((image.width(), image.height()), (4, 2));
+        assert_eq!(cropper.auto_rotated, None);
+        std::fs::remove_file(path).unwrap();
+    }
 
-                            ui.separator();
-                            changed |= ui
-                                .selectable_value(
-                                    &mut self.aspect_ratio_mode,
-                                    AspectRatioMode::Custom,
-                                    "Custom",
-                                )
-                                .changed();
-                        });
+    #[test]
+    fn picked_files_batch_writes_to_the_output_folder() {
+        let folder = temp_path("files_batch");
+        let out_dir = folder.join("out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let (a, broken) = (folder.join("a.png"), folder.join("broken.png"));
+        DynamicImage::new_rgb8(80, 40).save(&a).unwrap();
+        std::fs::write(&broken, b"not an image").unwrap();
 
-                    if ui.button("🔄").clicked() {
-                        self.is_portrait = !self.is_portrait;
-                        if self.aspect_ratio_mode == AspectRatioMode::Custom {
-                            std::mem::swap(&mut self.custom_w, &mut self.custom_h);
-                        } else {
-                            self.aspect_ratio_mode = self.aspect_ratio_mode.counterpart();
-                        }
-                        changed = true;
-                    }
+        let template = egui::Rect::from_min_max(egui::pos2(0.5, 0.0), egui::pos2(1.0, 0.5));
+        let progress = AtomicUsize::new(0);
+        let summary = batch::crop_files(
+            &[a, broken],
+            &out_dir,
+            template,
+            &Settings::default(),
+            &progress,
+        );
+        let cropped = image::open(out_dir.join("a_cropped.png")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
 
-                    if self.aspect_ratio_mode == AspectRatioMode::Custom {
-                        changed |= ui
-                            .add(
-                                egui::DragValue::new(&mut self.custom_w)
-                                    .speed(0.1)
-                                    .range(1..=10000),
-                            )
-                            .changed();
-                        ui.label(":");
-                        changed |= ui
-                            .add(
-                                egui::DragValue::new(&mut self.custom_h)
-                                    .speed(0.1)
-                                    .range(1..=10000),
-                            )
-                            .changed();
-                    }
+        assert_eq!(progress.load(Ordering::Relaxed), 2);
+        assert_eq!(summary.saved, 1);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!((cropped.width(), cropped.height()), (40, 20));
+        let message = CropperError::BatchFailed(summary.failed).to_string();
+        assert!(message.starts_with("1 file(s) could not be cropped:\n"));
+        assert!(message.contains("broken.png: Failed to open image"));
+    }
 
-                    if changed {
-                        self.apply_aspect_ratio();
-                    }
+    #[test]
+    fn lock_current_ratio_reduces_the_crop_shape() {
+        let mut cropper = cropper_with_image(3840, 2160);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.25),
+            egui::pos2(0.75, 0.75),
+        ));
+        cropper.lock_current_ratio();
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::Custom);
+        assert_eq!((cropper.custom_w, cropper.custom_h), (16, 9));
+        assert!(!cropper.is_portrait);
+        assert_eq!(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.75))
+        );
 
-                    if ui.button("Save Cropped Image").clicked() {
-                        if let (Some(image), Some(crop_rect)) = (&self.image, self.crop_rect) {
-                            if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
-                                .save_file()
-                            {
-                                let w = image.width() as f32;
-                                let h = image.height() as f32;
-
-                                let x = (crop_rect.min.x * w).max(0.0) as u32;
-                                let y = (crop_rect.min.y * h).max(0.0) as u32;
-                                let width = (crop_rect.width() * w).max(1.0) as u32;
-                                let height = (crop_rect.height() * h).max(1.0) as u32;
-
-                                // Ensure bounds
-                                let x = x.min(image.width() - 1);
-                                let y = y.min(image.height() - 1);
-                                let width = width.min(image.width() - x);
-                                let height = height.min(image.height() - y);
-
-                                let cropped = image.crop_imm(x, y, width, height);
-                                if let Err(e) = cropped.save(path) {
-                                    eprintln!("Failed to save image: {}", e);
-                                }
-                            }
-                        }
-                    }
-                });
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::Pos2::ZERO,
+            egui::pos2(0.1, 0.5),
+        ));
+        cropper.lock_current_ratio();
+        assert_eq!((cropper.custom_w, cropper.custom_h), (16, 45));
+        assert!(cropper.is_portrait);
+    }
 
-                ui.separator();
-            }
+    #[test]
+    fn custom_ratio_is_always_finite() {
+        for (w, h) in [
+            (0, 0),
+            (1, 0),
+            (0, 1),
+            (16, 9),
+            (u32::MAX, 1),
+            (1, u32::MAX),
+        ] {
+            let ratio = custom_ratio(w, h);
+            assert!(
+                ratio.is_finite() && ratio > 0.0,
+                "{}:{} gave {}",
+                w,
+                h,
+                ratio
+            );
+        }
+        let mut cropper = cropper_with_image(100, 100);
+        cropper.aspect_ratio_mode = AspectRatioMode::Custom;
+        (cropper.custom_w, cropper.custom_h) = (4, 0);
+        assert_eq!(cropper.target_ratio(), Some(4.0));
+    }
 
-            if let (Some(texture), Some(crop_rect)) = (&self.texture, &mut self.crop_rect) {
-                const PADDING: f32 = 20.0;
-                let available_size = ui.available_size();
-                let max_size = available_size - egui::vec2(PADDING * 2.0, PADDING * 2.0);
-                let image_size = texture.size_vec2();
+    #[test]
+    fn preview_follows_the_crop_at_a_limited_rate() {
+        let ctx = egui::Context::default();
+        let mut cropper = cropper_with_image(800, 400);
+        cropper.load_texture(&ctx);
+        cropper.update_preview(&ctx);
+        let (_, _, texture) = cropper.preview.as_ref().unwrap();
+        assert_eq!(texture.size(), [200, 100]);
 
-                // Calculate size to fit within available space while maintaining aspect ratio
-                let scale = (max_size.x / image_size.x).min(max_size.y / image_size.y);
-                let display_size = image_size * scale;
+        // A change right after a rebuild waits for the interval to pass
+        let half = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.25, 1.0));
+        cropper.crop_rect = Some(half);
+        cropper.update_preview(&ctx);
+        assert_eq!(cropper.preview.as_ref().unwrap().2.size(), [200, 100]);
 
-                let total_display_size = display_size + egui::vec2(PADDING * 2.0, PADDING * 2.0);
+        cropper.preview.as_mut().unwrap().1 -= PREVIEW_INTERVAL_SECS;
+        cropper.update_preview(&ctx);
+        let (key, _, texture) = cropper.preview.as_ref().unwrap();
+        assert_eq!(key.1, half);
+        assert_eq!(texture.size(), [100, 200]);
 
-                // Manual centering
-                let x_offset = (available_size.x - total_display_size.x) / 2.0;
-                let y_offset = (available_size.y - total_display_size.y) / 2.0;
-                let start_pos = ui.cursor().min + egui::vec2(x_offset.max(0.0), y_offset.max(0.0));
+        // The output size and round mask are part of what is shown
+        cropper.size_preset = SizePreset::Square(512);
+        cropper.preview.as_mut().unwrap().1 -= PREVIEW_INTERVAL_SECS;
+        cropper.update_preview(&ctx);
+        assert_eq!(cropper.preview.as_ref().unwrap().2.size(), [200, 200]);
+        cropper.circular = true;
+        cropper.preview.as_mut().unwrap().1 -= PREVIEW_INTERVAL_SECS;
+        cropper.update_preview(&ctx);
+        assert!(cropper.preview.as_ref().unwrap().0.5);
+    }
 
-                let target_rect = egui::Rect::from_min_size(start_pos, total_display_size);
+    #[test]
+    fn print_presets_pair_up_by_orientation() {
+        let pairs = [
+            (AspectRatioMode::R7_5, AspectRatioMode::R5_7),
+            (AspectRatioMode::R10_8, AspectRatioMode::R8_10),
+            (AspectRatioMode::IsoA, AspectRatioMode::IsoAPortrait),
+        ];
+        for (landscape, portrait) in pairs {
+            assert_eq!(landscape.counterpart(), portrait);
+            assert_eq!(portrait.counterpart(), landscape);
+            assert!(!landscape.is_portrait() && portrait.is_portrait());
+            let (l, p) = (landscape.ratio().unwrap(), portrait.ratio().unwrap());
+            assert!((l * p - 1.0).abs() < 1e-6);
+            assert_eq!(AspectRatioMode::from_key(&portrait.key()), Some(portrait));
+        }
+        assert_eq!(AspectRatioMode::R8_10.key(), "8:10");
+        assert_eq!(AspectRatioMode::IsoA.key(), "a_paper");
 
-                let response = ui.allocate_rect(target_rect, egui::Sense::drag());
-                let painter = ui.painter_at(target_rect);
+        // A 210×297 sheet is recognised as A-series, not 5:7
+        assert_eq!(
+            nearest_preset_ratio(210.0 / 297.0, 0.01),
+            Some(AspectRatioMode::IsoAPortrait)
+        );
+    }
 
-                // Center the image rect within the response rect (which includes padding)
-                let image_rect = egui::Rect::from_min_size(
-                    target_rect.min + egui::vec2(PADDING, PADDING),
-                    display_size,
-                );
+    #[test]
+    fn dragged_edges_snap_to_the_nearby_border() {
+        let tolerance = egui::Vec2::splat(0.02);
+        let rect = egui::Rect::from_min_max(egui::pos2(0.01, 0.3), egui::pos2(0.5, 0.985));
 
-                // Draw image
-                painter.image(
-                    texture.id(),
-                    image_rect,
-                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                    egui::Color32::WHITE,
-                );
+        // Only the edges the handle moves are pulled in
+        assert_eq!(
+            ImageCropper::edge_snap(rect, ResizeHandle::Left, tolerance),
+            egui::vec2(-0.01, 0.0)
+        );
+        assert_eq!(
+            ImageCropper::edge_snap(rect, ResizeHandle::Right, tolerance),
+            egui::Vec2::ZERO
+        );
+        let snap = ImageCropper::edge_snap(rect, ResizeHandle::Center, tolerance);
+        assert!((snap - egui::vec2(-0.01, 0.015)).length() < 1e-6);
 
-                // Convert normalized crop rect to screen coordinates
-                let mut screen_crop_rect = egui::Rect::from_min_max(
-                    image_rect.lerp_inside(crop_rect.min.to_vec2()),
-                    image_rect.lerp_inside(crop_rect.max.to_vec2()),
-                );
+        // Moving the whole crop by the snap keeps its size
+        let mut moved = rect;
+        ImageCropper::drag_crop_within(
+            &mut moved,
+            ResizeHandle::Center,
+            snap,
+            egui::vec2(100.0, 100.0),
+            None,
+            None,
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+        );
+        assert_rect_near(
+            moved,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.315), egui::pos2(0.49, 1.0)),
+        );
 
-                // Handle Input
-                if response.drag_started() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        self.selected_handle = Self::hit_test(pos, screen_crop_rect);
-                    }
-                }
-
-                if response.dragged() {
-                    if let Some(handle) = self.selected_handle {
-                        let delta = response.drag_delta();
-                        let delta_norm = delta / display_size; // Normalize delta
-
-                        // Determine target aspect ratio
-                        let target_ratio = match self.aspect_ratio_mode {
-                            AspectRatioMode::Free => None,
-                            AspectRatioMode::Original => Some(image_size.x / image_size.y),
-                            AspectRatioMode::Square => Some(1.0),
-                            AspectRatioMode::R3_2 => Some(3.0 / 2.0),
-                            AspectRatioMode::R4_3 => Some(4.0 / 3.0),
-                            AspectRatioMode::R16_9 => Some(16.0 / 9.0),
-                            AspectRatioMode::R16_10 => Some(16.0 / 10.0),
-                            AspectRatioMode::R2_3 => Some(2.0 / 3.0),
-                            AspectRatioMode::R3_4 => Some(3.0 / 4.0),
-                            AspectRatioMode::R9_16 => Some(9.0 / 16.0),
-                            AspectRatioMode::R10_16 => Some(10.0 / 16.0),
-                            AspectRatioMode::Custom => {
-                                Some(self.custom_w as f32 / self.custom_h as f32)
-                            }
-                        };
+        // Past the tolerance nothing happens
+        let inner = egui::Rect::from_min_max(egui::pos2(0.1, 0.1), egui::pos2(0.9, 0.9));
+        assert_eq!(
+            ImageCropper::edge_snap(inner, ResizeHandle::Center, tolerance),
+            egui::Vec2::ZERO
+        );
+    }
 
-                        let norm_aspect = target_ratio.map(|r| r * (image_size.y / image_size.x));
+    #[test]
+    fn swapping_orientation_in_free_mode_turns_the_crop() {
+        let mut cropper = cropper_with_image(400, 200);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.1, 0.25),
+            egui::pos2(0.4, 0.75),
+        ));
+        // 120×100 px near the left edge becomes 100×120 px, same center
+        cropper.flip_orientation();
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_center_size(egui::pos2(0.25, 0.5), egui::vec2(0.25, 0.6)),
+        );
+        assert!(cropper.is_portrait);
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::Free);
 
-                        if let (Some(ratio), Some(norm_aspect)) = (target_ratio, norm_aspect) {
-                            // Constrained resize
-                            // Helper to convert normalized width/height to screen space
-                            let to_screen = |w_norm: f32, h_norm: f32| -> egui::Vec2 {
-                                egui::vec2(w_norm * display_size.x, h_norm * display_size.y)
-                            };
-                            // Helper to convert screen dimensions back to normalized space dimensions
-                            let to_norm = |w_screen: f32, h_screen: f32| -> egui::Vec2 {
-                                egui::vec2(w_screen / display_size.x, h_screen / display_size.y)
-                            };
+        // A full-width crop is too wide to stand up, so it shrinks to fit
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::Pos2::ZERO,
+            egui::pos2(1.0, 0.5),
+        ));
+        cropper.flip_orientation();
+        let (_, _, w, h) = crop_pixel_bounds(400, 200, cropper.crop_rect.unwrap());
+        assert_eq!((w, h), (50, 200));
 
-                            match handle {
-                                ResizeHandle::Center => {
-                                    // Safe Panning: constrain delta to stay within bounds
-                                    let mut final_delta = delta_norm;
-                                    if crop_rect.min.x + final_delta.x < 0.0 {
-                                        final_delta.x = -crop_rect.min.x;
-                                    }
-                                    if crop_rect.max.x + final_delta.x > 1.0 {
-                                        final_delta.x = 1.0 - crop_rect.max.x;
-                                    }
-                                    if crop_rect.min.y + final_delta.y < 0.0 {
-                                        final_delta.y = -crop_rect.min.y;
-                                    }
-                                    if crop_rect.max.y + final_delta.y > 1.0 {
-                                        final_delta.y = 1.0 - crop_rect.max.y;
-                                    }
-
-                                    *crop_rect = crop_rect.translate(final_delta);
-                                }
-                                // Corner Handles: Use projection logic for smooth interactions
-                                ResizeHandle::TopLeft
-                                | ResizeHandle::TopRight
-                                | ResizeHandle::BottomLeft
-                                | ResizeHandle::BottomRight => {
-                                    // 1. Identify Anchor (Fixed Point) and current Corner
-                                    let (anchor, mut corner) = match handle {
-                                        ResizeHandle::TopLeft => (crop_rect.max, crop_rect.min),
-                                        ResizeHandle::TopRight => (
-                                            egui::pos2(crop_rect.min.x, crop_rect.max.y),
-                                            egui::pos2(crop_rect.max.x, crop_rect.min.y),
-                                        ),
-                                        ResizeHandle::BottomLeft => (
-                                            egui::pos2(crop_rect.max.x, crop_rect.min.y),
-                                            egui::pos2(crop_rect.min.x, crop_rect.max.y),
-                                        ),
-                                        ResizeHandle::BottomRight => (crop_rect.min, crop_rect.max),
-                                        _ => (egui::Pos2::ZERO, egui::Pos2::ZERO), // Unreachable
-                                    };
-
-                                    // 2. Calculate suggested new dimensions in screen space
-                                    // Apply delta to corner
-                                    match handle {
-                                        ResizeHandle::TopLeft => corner += delta_norm,
-                                        ResizeHandle::TopRight => {
-                                            corner.y += delta_norm.y;
-                                            corner.x += delta_norm.x;
-                                        }
-                                        ResizeHandle::BottomLeft => {
-                                            corner.x += delta_norm.x;
-                                            corner.y += delta_norm.y;
-                                        }
-                                        ResizeHandle::BottomRight => corner += delta_norm,
-                                        _ => {}
-                                    }
-
-                                    // Calculate raw new width/height (absolute)
-                                    let raw_w_norm = (corner.x - anchor.x).abs();
-                                    let raw_h_norm = (corner.y - anchor.y).abs();
-                                    let raw_screen = to_screen(raw_w_norm, raw_h_norm);
-
-                                    // 3. Project onto aspect ratio vector
-                                    // Vector direction U = (ratio, 1.0)
-                                    let u = egui::vec2(ratio, 1.0);
-                                    let p = raw_screen; // Computed target vector
-                                    // Projection: (P . U) / (U . U) * U
-                                    let lambda = p.dot(u) / u.length_sq();
-                                    let constrained_screen = u * lambda;
-
-                                    // 4. Convert back to normalized
-                                    let mut final_dim =
-                                        to_norm(constrained_screen.x, constrained_screen.y);
-
-                                    // 5. Constrain to Image Bounds BEFORE applying
-                                    // Calculate max available dimensions from anchor
-                                    let (max_w, max_h) = match handle {
-                                        ResizeHandle::TopLeft => (anchor.x, anchor.y),
-                                        ResizeHandle::TopRight => (1.0 - anchor.x, anchor.y),
-                                        ResizeHandle::BottomLeft => (anchor.x, 1.0 - anchor.y),
-                                        ResizeHandle::BottomRight => {
-                                            (1.0 - anchor.x, 1.0 - anchor.y)
-                                        }
-                                        _ => (1.0, 1.0),
-                                    };
-
-                                    // Scale down if exceeding bounds
-                                    let mut scale_factor = 1.0f32;
-                                    if final_dim.x > max_w {
-                                        scale_factor = scale_factor.min(max_w / final_dim.x);
-                                    }
-                                    if final_dim.y > max_h {
-                                        scale_factor = scale_factor.min(max_h / final_dim.y);
-                                    }
-                                    final_dim *= scale_factor;
-
-                                    // Reconstruct rect from Anchor
-                                    let (new_min, new_max) = match handle {
-                                        ResizeHandle::TopLeft => (anchor - final_dim, anchor),
-                                        ResizeHandle::TopRight => (
-                                            egui::pos2(anchor.x, anchor.y - final_dim.y),
-                                            egui::pos2(anchor.x + final_dim.x, anchor.y),
-                                        ),
-                                        ResizeHandle::BottomLeft => (
-                                            egui::pos2(anchor.x - final_dim.x, anchor.y),
-                                            egui::pos2(anchor.x, anchor.y + final_dim.y),
-                                        ),
-                                        ResizeHandle::BottomRight => (anchor, anchor + final_dim),
-                                        _ => (egui::Pos2::ZERO, egui::Pos2::ZERO),
-                                    };
-
-                                    *crop_rect = egui::Rect::from_min_max(new_min, new_max);
-                                }
+        cropper.aspect_ratio_mode = AspectRatioMode::Original;
+        assert!(!cropper.can_swap_orientation());
+        let before = (cropper.crop_rect, cropper.is_portrait);
+        cropper.flip_orientation();
+        assert_eq!((cropper.crop_rect, cropper.is_portrait), before);
+    }
 
-                                // Side Handles: Drive one dimension, center the other
-                                ResizeHandle::Left | ResizeHandle::Right => {
-                                    // Drive Width
-                                    match handle {
-                                        ResizeHandle::Left => {
-                                            crop_rect.min.x += delta_norm.x;
-                                        }
-                                        ResizeHandle::Right => {
-                                            crop_rect.max.x += delta_norm.x;
-                                        }
-                                        _ => {}
-                                    }
-
-                                    // Use absolute width (in case of crossover, though UI usually prevents)
-                                    let mut new_w = (crop_rect.max.x - crop_rect.min.x).abs();
-
-                                    // Constrain Width
-                                    // 1. If width > 1.0, clamp to 1.0
-                                    if new_w > 1.0 {
-                                        new_w = 1.0;
-                                    }
-
-                                    // 2. Calc Height
-                                    let mut new_h = new_w / norm_aspect;
-
-                                    // 3. If Height > 1.0, clamp Height => clamp Width
-                                    if new_h > 1.0 {
-                                        new_h = 1.0;
-                                        new_w = new_h * norm_aspect;
-                                    }
-
-                                    // 4. Center Height
-                                    let old_center_y = crop_rect.center().y;
-                                    let mut min_y = old_center_y - new_h * 0.5;
-                                    let mut max_y = old_center_y + new_h * 0.5;
-
-                                    // 5. Bounds Check & Slide
-                                    if min_y < 0.0 {
-                                        let offset = -min_y;
-                                        min_y += offset;
-                                        max_y += offset;
-                                    }
-                                    if max_y > 1.0 {
-                                        let offset = 1.0 - max_y;
-                                        min_y += offset;
-                                        max_y += offset;
-                                    }
-
-                                    // Apply
-                                    crop_rect.min.y = min_y;
-                                    crop_rect.max.y = max_y;
-                                    // Re-apply width to handled side (keeping center if possible? No, we moved one side)
-                                    // Actually we just updated crop_rect.min/max.x above directly.
-                                    // We need to re-sync them to the valid new_w
-                                    match handle {
-                                        ResizeHandle::Left => {
-                                            crop_rect.min.x = crop_rect.max.x - new_w
-                                        }
-                                        ResizeHandle::Right => {
-                                            crop_rect.max.x = crop_rect.min.x + new_w
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                                ResizeHandle::Top | ResizeHandle::Bottom => {
-                                    // Drive Height
-                                    match handle {
-                                        ResizeHandle::Top => {
-                                            crop_rect.min.y += delta_norm.y;
-                                        }
-                                        ResizeHandle::Bottom => {
-                                            crop_rect.max.y += delta_norm.y;
-                                        }
-                                        _ => {}
-                                    }
-
-                                    let mut new_h = (crop_rect.max.y - crop_rect.min.y).abs();
-
-                                    // Constrain
-                                    if new_h > 1.0 {
-                                        new_h = 1.0;
-                                    }
-
-                                    let mut new_w = new_h * norm_aspect;
-
-                                    if new_w > 1.0 {
-                                        new_w = 1.0;
-                                        new_h = new_w / norm_aspect;
-                                    }
-
-                                    let old_center_x = crop_rect.center().x;
-                                    let mut min_x = old_center_x - new_w * 0.5;
-                                    let mut max_x = old_center_x + new_w * 0.5;
-
-                                    if min_x < 0.0 {
-                                        let offset = -min_x;
-                                        min_x += offset;
-                                        max_x += offset;
-                                    }
-                                    if max_x > 1.0 {
-                                        let offset = 1.0 - max_x;
-                                        min_x += offset;
-                                        max_x += offset;
-                                    }
-
-                                    crop_rect.min.x = min_x;
-                                    crop_rect.max.x = max_x;
-
-                                    match handle {
-                                        ResizeHandle::Top => {
-                                            crop_rect.min.y = crop_rect.max.y - new_h
-                                        }
-                                        ResizeHandle::Bottom => {
-                                            crop_rect.max.y = crop_rect.min.y + new_h
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            }
-                        } else {
-                            // Free resize
-                            match handle {
-                                ResizeHandle::Center => {
-                                    // Safe Panning: constrain delta to stay within bounds
-                                    let mut final_delta = delta_norm;
-                                    if crop_rect.min.x + final_delta.x < 0.0 {
-                                        final_delta.x = -crop_rect.min.x;
-                                    }
-                                    if crop_rect.max.x + final_delta.x > 1.0 {
-                                        final_delta.x = 1.0 - crop_rect.max.x;
-                                    }
-                                    if crop_rect.min.y + final_delta.y < 0.0 {
-                                        final_delta.y = -crop_rect.min.y;
-                                    }
-                                    if crop_rect.max.y + final_delta.y > 1.0 {
-                                        final_delta.y = 1.0 - crop_rect.max.y;
-                                    }
-
-                                    *crop_rect = crop_rect.translate(final_delta);
-                                }
-                                ResizeHandle::TopLeft => {
-                                    crop_rect.min += delta_norm;
-                                }
-                                ResizeHandle::TopRight => {
-                                    crop_rect.min.y += delta_norm.y;
-                                    crop_rect.max.x += delta_norm.x;
-                                }
-                                ResizeHandle::BottomLeft => {
-                                    crop_rect.min.x += delta_norm.x;
-                                    crop_rect.max.y += delta_norm.y;
-                                }
-                                ResizeHandle::BottomRight => {
-                                    crop_rect.max += delta_norm;
-                                }
-                                ResizeHandle::Top => {
-                                    crop_rect.min.y += delta_norm.y;
-                                }
-                                ResizeHandle::Bottom => {
-                                    crop_rect.max.y += delta_norm.y;
-                                }
-                                ResizeHandle::Left => {
-                                    crop_rect.min.x += delta_norm.x;
-                                }
-                                ResizeHandle::Right => {
-                                    crop_rect.max.x += delta_norm.x;
-                                }
-                            }
-                        }
+    #[test]
+    fn grid_modes_place_their_guides() {
+        let rect = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(300.0, 100.0));
+        assert!(GridMode::None.lines(rect).is_empty());
+        assert_eq!(
+            GridMode::Thirds.lines(rect)[0],
+            [egui::pos2(100.0, 0.0), egui::pos2(100.0, 100.0)]
+        );
+        let golden = GridMode::Golden.lines(rect);
+        assert_eq!(golden.len(), 4);
+        assert!((golden[0][0].x - 114.59).abs() < 0.01);
+        assert!((golden[3][0].y - 61.80).abs() < 0.01);
+        assert_eq!(
+            GridMode::Diagonals.lines(rect),
+            vec![
+                [rect.left_top(), rect.right_bottom()],
+                [rect.right_top(), rect.left_bottom()],
+            ]
+        );
+        assert_eq!(GridMode::Fine.lines(rect).len(), 14);
+    }
 
-                        // Clamp and ensure min < max
-                        if crop_rect.min.x < 0.0 {
-                            crop_rect.min.x = 0.0;
-                        }
-                        if crop_rect.min.y < 0.0 {
-                            crop_rect.min.y = 0.0;
-                        }
-                        if crop_rect.max.x > 1.0 {
-                            crop_rect.max.x = 1.0;
-                        }
-                        if crop_rect.max.y > 1.0 {
-                            crop_rect.max.y = 1.0;
-                        }
-                        // TODO: Ensure min < max
-                        if crop_rect.min.x > crop_rect.max.x {
-                            std::mem::swap(&mut crop_rect.min.x, &mut crop_rect.max.x);
-                        }
-                        if crop_rect.min.y > crop_rect.max.y {
-                            std::mem::swap(&mut crop_rect.min.y, &mut crop_rect.max.y);
-                        }
+    #[test]
+    fn crop_data_round_trips_through_json() {
+        let mut cropper = cropper_with_image(4000, 3000);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.1, 0.2),
+            egui::pos2(0.9, 0.8),
+        ));
+        cropper.aspect_ratio_mode = AspectRatioMode::Custom;
+        (cropper.custom_w, cropper.custom_h) = (5, 4);
+        let data = cropper.crop_data().unwrap();
+        let json = data.to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["pixels"],
+            serde_json::json!({ "x": 400, "y": 600, "width": 3200, "height": 1800 })
+        );
+        assert_eq!(value["aspect_ratio"], "custom");
+        assert_eq!(crop_data::CropData::parse(&json).unwrap(), data);
 
-                        // Re-calculate screen rect for display after modification
-                        screen_crop_rect = egui::Rect::from_min_max(
-                            image_rect.lerp_inside(crop_rect.min.to_vec2()),
-                            image_rect.lerp_inside(crop_rect.max.to_vec2()),
-                        );
-                    }
-                }
+        // Another image takes the same normalized crop and ratio
+        let mut other = cropper_with_image(100, 100);
+        other
+            .apply_crop_data(crop_data::CropData::parse(&json).unwrap())
+            .unwrap();
+        assert_eq!(other.crop_rect, cropper.crop_rect);
+        assert_eq!(other.aspect_ratio_mode, AspectRatioMode::Custom);
+        assert_eq!((other.custom_w, other.custom_h), (5, 4));
 
-                if response.drag_stopped() {
-                    self.selected_handle = None;
-                }
+        // Only the normalized rect is required
+        let minimal = r#"{"normalized": {"min_x": 0, "min_y": 0, "max_x": 0.5, "max_y": 1}}"#;
+        let data = crop_data::CropData::parse(minimal).unwrap();
+        assert_eq!(data.aspect_ratio, AspectRatioMode::Free);
+        assert!(matches!(
+            crop_data::CropData::parse(r#"{"normalized": {"min_x": 0.5}}"#),
+            Err(CropperError::InvalidCropData(_))
+        ));
+        assert!(matches!(
+            crop_data::CropData::parse("[1, 2"),
+            Err(CropperError::InvalidCropData(_))
+        ));
+        // Deep nesting is refused rather than overflowing the stack
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(matches!(
+            crop_data::CropData::parse(&nested),
+            Err(CropperError::InvalidCropData(_))
+        ));
+    }
 
-                // Draw overlay (dimmed area outside crop)
-                let overlay_color = egui::Color32::from_black_alpha(150);
+    #[test]
+    fn save_format_appends_a_missing_extension() {
+        use export::SaveFormat;
+        let path = |name| PathBuf::from("/out").join(name);
+        assert_eq!(SaveFormat::Auto.apply_to(&path("crop")), path("crop.png"));
+        assert_eq!(
+            SaveFormat::Auto.apply_to(&path("crop.JPG")),
+            path("crop.JPG")
+        );
+        assert_eq!(
+            SaveFormat::Auto.apply_to(&path("crop.avif")),
+            path("crop.avif")
+        );
+        assert_eq!(SaveFormat::Jpeg.apply_to(&path("crop")), path("crop.jpg"));
+        assert_eq!(
+            SaveFormat::Jpeg.apply_to(&path("crop.jpeg")),
+            path("crop.jpeg")
+        );
+        // A different image extension is kept as part of the name
+        assert_eq!(
+            SaveFormat::WebP.apply_to(&path("crop.png")),
+            path("crop.png.webp")
+        );
+        assert_eq!(SaveFormat::Bmp.apply_to(&path("v1.2")), path("v1.2.bmp"));
 
-                // Top
-                painter.rect_filled(
-                    egui::Rect::from_min_max(
-                        image_rect.min,
-                        egui::pos2(image_rect.max.x, screen_crop_rect.min.y),
-                    ),
-                    0.0,
-                    overlay_color,
-                );
-                // Bottom
-                painter.rect_filled(
-                    egui::Rect::from_min_max(
-                        egui::pos2(image_rect.min.x, screen_crop_rect.max.y),
-                        image_rect.max,
-                    ),
-                    0.0,
-                    overlay_color,
-                );
-                // Left
-                painter.rect_filled(
-                    egui::Rect::from_min_max(
-                        egui::pos2(image_rect.min.x, screen_crop_rect.min.y),
-                        egui::pos2(screen_crop_rect.min.x, screen_crop_rect.max.y),
-                    ),
-                    0.0,
-                    overlay_color,
-                );
-                // Right
-                painter.rect_filled(
-                    egui::Rect::from_min_max(
-                        egui::pos2(screen_crop_rect.max.x, screen_crop_rect.min.y),
-                        egui::pos2(image_rect.max.x, screen_crop_rect.max.y),
-                    ),
-                    0.0,
-                    overlay_color,
-                );
+        let mut cropper = cropper_with_image(100, 100);
+        cropper.image_path = Some(PathBuf::from("/photos/cat.png"));
+        cropper.save_format = SaveFormat::Jpeg;
+        assert!(
+            cropper
+                .suggested_file_name()
+                .unwrap()
+                .unwrap()
+                .ends_with(".jpg")
+        );
+    }
 
-                // Draw crop border
-                painter.rect_stroke(
-                    screen_crop_rect,
-                    0.0,
-                    egui::Stroke::new(1.0, egui::Color32::WHITE),
-                );
+    #[test]
+    fn size_estimate_waits_for_the_crop_to_settle() {
+        let ctx = egui::Context::default();
+        let mut cropper = cropper_with_image(64, 64);
+        cropper.load_texture(&ctx);
+        cropper.save_format = export::SaveFormat::Jpeg;
+        cropper.settings.jpeg_quality = 90;
 
-                // Draw handles
-                let handle_radius = 6.0;
-                let handle_stroke = egui::Stroke::new(1.0, egui::Color32::BLACK);
-                let handle_fill = egui::Color32::WHITE;
+        // The first call only notes the change; nothing is encoded yet
+        cropper.update_size_estimate(&ctx);
+        assert!(cropper.estimate_job.is_none());
+        cropper.estimate_pending.as_mut().unwrap().1 -= ESTIMATE_DELAY_SECS;
+        cropper.update_size_estimate(&ctx);
+        let (_, rx) = cropper.estimate_job.take().unwrap();
+        let size = rx.recv().unwrap().unwrap();
+        assert!(size > 0);
 
-                let handles = [
-                    screen_crop_rect.min,
-                    screen_crop_rect.max,
-                    egui::pos2(screen_crop_rect.min.x, screen_crop_rect.max.y),
-                    egui::pos2(screen_crop_rect.max.x, screen_crop_rect.min.y),
-                    screen_crop_rect.center_top(),
-                    screen_crop_rect.center_bottom(),
-                    screen_crop_rect.left_center(),
-                    screen_crop_rect.right_center(),
-                ];
+        let key = cropper.estimate_key().unwrap();
+        cropper.size_estimate = Some((key, Some(size)));
+        let label = cropper.size_estimate_label().unwrap();
+        assert!(
+            label.starts_with("≈ ") && label.ends_with(" as JPG q90"),
+            "{}",
+            label
+        );
+        assert_eq!(
+            export::encoded_size(
+                &cropper.output_image().unwrap(),
+                image::ImageFormat::Jpeg,
+                90
+            )
+            .unwrap(),
+            size
+        );
+    }
 
-                for pos in handles {
-                    painter.circle(pos, handle_radius, handle_fill, handle_stroke);
-                }
-            }
+    #[test]
+    fn start_open_decodes_on_a_worker_and_poll_applies_it() {
+        let path = temp_path("start_open.png");
+        DynamicImage::new_rgb8(30, 20).save(&path).unwrap();
+        let ctx = egui::Context::default();
+        let mut cropper = ImageCropper::default();
+        cropper.start_open(&ctx, path.clone());
+        assert!(cropper.image.is_none());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while cropper.loading.is_some() && std::time::Instant::now() < deadline {
+            cropper.poll_loading(&ctx);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert!(cropper.loading.is_none());
+        assert_eq!(cropper.image.as_ref().map(|i| i.width()), Some(30));
+        assert_eq!(cropper.image_path.as_deref(), Some(path.as_path()));
+        assert!(cropper.texture.is_some());
+
+        cropper.start_open(&ctx, temp_path("start_open_missing.png"));
+        while cropper.loading.is_some() && std::time::Instant::now() < deadline {
+            cropper.poll_loading(&ctx);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert!(matches!(cropper.error, Some(CropperError::Load(_))));
+        assert_eq!(cropper.image_path.as_deref(), Some(path.as_path()));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn huge_images_get_a_downscaled_texture_but_keep_their_pixels() {
+        let ctx = egui::Context::default();
+        let mut cropper = cropper_with_image(5000, 100);
+        cropper.load_texture(&ctx);
+        // A default context reports a 2048 px GPU limit, under MAX_TEXTURE_SIDE
+        assert_eq!(cropper.texture.as_ref().unwrap().size(), [2048, 41]);
+        assert_eq!(cropper.image.as_ref().unwrap().width(), 5000);
+        assert_eq!(cropper.cropped_image().unwrap().width(), 5000);
+
+        let mut cropper = cropper_with_image(300, 200);
+        cropper.load_texture(&ctx);
+        assert_eq!(cropper.texture.as_ref().unwrap().size(), [300, 200]);
+    }
+
+    #[test]
+    fn mask_out_clears_the_crop_and_keeps_the_rest() {
+        let mut cropper = cropper_with_image(40, 20);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.0),
+            egui::pos2(0.75, 1.0),
+        ));
+        cropper.mask_out = true;
+        cropper.settings.filename_template = "{w}x{h}.{ext}".to_owned();
+        let output = cropper.output_image().unwrap().to_rgba8();
+        assert_eq!(output.dimensions(), (40, 20));
+        for (x, y) in [(10, 0), (29, 19), (20, 10)] {
+            assert_eq!(output.get_pixel(x, y).0, [0; 4], "({x}, {y})");
+        }
+        for (x, y) in [(9, 10), (30, 10), (0, 0)] {
+            assert_eq!(output.get_pixel(x, y).0[3], 255, "({x}, {y})");
+        }
+        assert!(
+            cropper
+                .suggested_file_name()
+                .unwrap()
+                .is_some_and(|name| name.starts_with("40x20."))
+        );
+
+        // With a round crop only the ellipse goes; its bounding corners stay
+        cropper.circular = true;
+        let output = cropper.output_image().unwrap().to_rgba8();
+        assert_eq!(output.get_pixel(20, 10).0, [0; 4]);
+        assert_eq!(output.get_pixel(10, 0).0[3], 255);
+    }
+
+    #[test]
+    fn redacting_changes_only_the_pixels_under_the_crop() {
+        let ctx = egui::Context::default();
+        let striped = image::RgbaImage::from_fn(40, 20, |x, _| {
+            image::Rgba([if x % 2 == 0 { 255 } else { 0 }, 0, 0, 255])
         });
+        let mut cropper = cropper_with_image(40, 20);
+        cropper.image = Some(DynamicImage::ImageRgba8(striped.clone()));
+        let crop = egui::Rect::from_min_max(egui::pos2(0.5, 0.0), egui::pos2(1.0, 1.0));
+        cropper.crop_rect = Some(crop);
+        cropper.redact_strength = 4;
+
+        cropper.redact_crop(&ctx, true);
+        let pixelated = cropper.image.as_ref().unwrap().to_rgba8();
+        assert_eq!(pixelated.get_pixel(0, 0), striped.get_pixel(0, 0));
+        assert_eq!(pixelated.get_pixel(19, 5), striped.get_pixel(19, 5));
+        // Each 4 px block is one colour, somewhere between the stripes
+        let block = pixelated.get_pixel(20, 0).0;
+        assert!((1..255).contains(&block[0]), "{block:?}");
+        for (x, y) in [(21, 0), (23, 3), (20, 2)] {
+            assert_eq!(pixelated.get_pixel(x, y).0, block, "({x}, {y})");
+        }
+        assert_eq!(cropper.crop_rect, Some(crop));
+        assert_eq!(cropper.transform_history.len(), 1);
+
+        cropper.undo_transform(&ctx);
+        cropper.redact_crop(&ctx, false);
+        let blurred = cropper.image.as_ref().unwrap().to_rgba8();
+        assert_eq!(blurred.get_pixel(19, 10), striped.get_pixel(19, 10));
+        let (left, right) = (
+            blurred.get_pixel(30, 10).0[0],
+            blurred.get_pixel(31, 10).0[0],
+        );
+        assert!(left.abs_diff(right) < 40, "{left} vs {right}");
+
+        // Saving keeps the whole image, redaction included, until reverted
+        assert_eq!(cropper.output_image().unwrap().to_rgba8(), blurred);
+        assert!(cropper.status_line().ends_with("saving the whole image"));
+        cropper.undo_transform(&ctx);
+        assert_eq!(cropper.output_image().unwrap().width(), 20);
     }
-}
 
-fn main() -> eframe::Result {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([800.0, 600.0]),
-        ..Default::default()
-    };
-    eframe::run_native(
-        "Image Cropper",
-        options,
-        Box::new(|cc| Ok(Box::new(ImageCropper::new(cc)))),
-    )
+    #[test]
+    fn crop_presets_replace_by_name_and_round_trip() {
+        let banner = egui::Rect::from_min_max(egui::pos2(0.0, 0.25), egui::pos2(1.0, 0.5));
+        let thumbnail = egui::Rect::from_min_max(egui::pos2(0.1, 0.1), egui::pos2(0.4, 0.4));
+        let mut presets = CropPresets::default();
+        presets.set("banner", thumbnail);
+        presets.set("my thumbnail", thumbnail);
+        presets.set(" banner \nextra", banner);
+        presets.set("", banner);
+        presets.set(
+            "outside",
+            egui::Rect::from_min_max(egui::pos2(1.5, 0.0), egui::pos2(2.0, 1.0)),
+        );
+        assert_eq!(
+            presets.iter().collect::<Vec<_>>(),
+            [("banner", banner), ("my thumbnail", thumbnail)]
+        );
+
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, CropPresets::STORAGE_KEY, &presets);
+        assert_eq!(
+            eframe::get_value::<CropPresets>(&storage, CropPresets::STORAGE_KEY),
+            Some(presets.clone())
+        );
+        let mut stored: Vec<(String, egui::Rect)> = presets.clone().into();
+        stored.push(("odd".to_owned(), egui::Rect::NAN));
+        stored.push(("".to_owned(), banner));
+        assert_eq!(CropPresets::from(stored), presets);
+
+        presets.remove("banner");
+        assert_eq!(presets.iter().next(), Some(("my thumbnail", thumbnail)));
+        presets.remove("my thumbnail");
+        assert_eq!(presets.iter().count(), 0);
+    }
+
+    #[test]
+    fn aspect_lock_holds_the_current_shape_on_every_handle() {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.aspect_ratio_mode = AspectRatioMode::Free;
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.25),
+            egui::pos2(0.6, 0.75),
+        ));
+        cropper.set_aspect_lock(true);
+        assert_eq!(cropper.aspect_lock, Some(70.0 / 50.0));
+        assert_eq!(cropper.target_ratio(), Some(1.4));
+
+        let pixel_ratio = |cropper: &ImageCropper| {
+            let size = cropper.crop_rect.unwrap().size() * egui::vec2(200.0, 100.0);
+            size.x / size.y
+        };
+        // Right edge, then the bottom-right corner
+        let handle = drag_math(
+            &mut cropper,
+            egui::pos2(260.0, 120.0),
+            egui::pos2(300.0, 120.0),
+        );
+        assert_eq!(handle, Some(ResizeHandle::Right));
+        assert!((pixel_ratio(&cropper) - 1.4).abs() < 1e-3);
+        let corner = DRAG_CANVAS.lerp_inside(cropper.crop_rect.unwrap().max.to_vec2());
+        drag_math(&mut cropper, corner, corner - egui::vec2(30.0, 5.0));
+        assert!((pixel_ratio(&cropper) - 1.4).abs() < 1e-3);
+
+        // Turning the image turns the lock with it, and off frees the crop
+        cropper.swap_ratio_orientation();
+        assert_eq!(cropper.aspect_lock, Some(1.0 / 1.4));
+        cropper.set_aspect_lock(false);
+        assert_eq!(cropper.target_ratio(), None);
+    }
+
+    #[test]
+    fn flipping_keeps_an_inverted_aspect_lock() {
+        let mut cropper = cropper_with_image(200, 100);
+        cropper.crop_rect = Some(egui::Rect::from_min_max(
+            egui::pos2(0.25, 0.25),
+            egui::pos2(0.6, 0.75),
+        ));
+        cropper.set_aspect_lock(true);
+        cropper.flip_orientation();
+        assert_eq!(cropper.aspect_lock, Some(1.0 / 1.4));
+        let size = cropper.crop_rect.unwrap().size() * egui::vec2(200.0, 100.0);
+        assert!((size.x / size.y - 1.0 / 1.4).abs() < 1e-3);
+
+        cropper.aspect_ratio_mode = AspectRatioMode::R4_3;
+        cropper.flip_orientation();
+        assert_eq!(cropper.aspect_ratio_mode, AspectRatioMode::R3_4);
+        assert_eq!(cropper.aspect_lock, Some(1.4));
+    }
+
+    #[test]
+    fn drag_math_past_the_left_edge_keeps_the_bottom_right_anchor() {
+        for ratio in [None, Some(2.0)] {
+            let mut cropper = cropper_with_image(200, 100);
+            cropper.aspect_lock = ratio;
+            let start = egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.75));
+            cropper.crop_rect = Some(start);
+            let corner = DRAG_CANVAS.lerp_inside(start.max.to_vec2());
+            let handle = drag_math(&mut cropper, corner, corner - egui::vec2(300.0, 0.0));
+            assert_eq!(handle, Some(ResizeHandle::BottomRight));
+            let rect = cropper.crop_rect.unwrap();
+            // The crop stops at its minimum size, so no axis flips and the
+            // top-left corner stays where it was
+            assert!(
+                rect.min.x < rect.max.x && rect.min.y < rect.max.y,
+                "{ratio:?}: {rect:?}"
+            );
+            assert_rect_near(
+                egui::Rect::from_min_size(rect.min, egui::Vec2::ZERO),
+                egui::Rect::from_min_size(start.min, egui::Vec2::ZERO),
+            );
+
+            // Dragging back the other way grows the same corner again
+            let corner = DRAG_CANVAS.lerp_inside(rect.max.to_vec2());
+            let handle = drag_math(&mut cropper, corner, corner + egui::vec2(40.0, 20.0));
+            assert_eq!(handle, Some(ResizeHandle::BottomRight));
+            let grown = cropper.crop_rect.unwrap();
+            assert_rect_near(
+                egui::Rect::from_min_size(grown.min, egui::Vec2::ZERO),
+                egui::Rect::from_min_size(start.min, egui::Vec2::ZERO),
+            );
+            assert!(grown.max.x > rect.max.x, "{ratio:?}: {grown:?}");
+        }
+    }
+
+    #[test]
+    fn imported_crop_data_is_kept_inside_the_image() {
+        let mut cropper = cropper_with_image(400, 200);
+        let before = egui::Rect::from_min_max(egui::pos2(0.1, 0.1), egui::pos2(0.5, 0.5));
+        cropper.crop_rect = Some(before);
+        cropper.record_crop();
+        let parse = |json: &str| crop_data::CropData::parse(json).unwrap();
+
+        let huge = parse(r#"{"normalized": {"min_x": 0.5, "min_y": 0, "max_x": 1e6, "max_y": 1}}"#);
+        cropper.apply_crop_data(huge).unwrap();
+        assert_rect_near(
+            cropper.crop_rect.unwrap(),
+            egui::Rect::from_min_max(egui::pos2(0.5, 0.0), egui::pos2(1.0, 1.0)),
+        );
+        // The import is an undoable step
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, Some(before));
+
+        // A sliver grows to the minimum size; a crop off the image is refused
+        let sliver =
+            parse(r#"{"normalized": {"min_x": 0.2, "min_y": 0.2, "max_x": 0.2001, "max_y": 0.5}}"#);
+        cropper.apply_crop_data(sliver).unwrap();
+        let size = cropper.crop_rect.unwrap().size() * egui::vec2(400.0, 200.0);
+        assert!((size.x - MIN_CROP_PX).abs() < 1e-3, "{size:?}");
+        let outside = parse(r#"{"normalized": {"min_x": 2, "min_y": 2, "max_x": 3, "max_y": 3}}"#);
+        assert!(matches!(
+            cropper.apply_crop_data(outside),
+            Err(CropperError::InvalidCropData(_))
+        ));
+        assert!(
+            crop_data::CropData::parse(
+                r#"{"normalized": {"min_x": 0, "min_y": 0, "max_x": 1e999, "max_y": 1}}"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn undo_after_one_click_crop_actions_returns_to_the_previous_crop() {
+        let mut cropper = cropper_with_image(400, 300);
+        cropper.aspect_ratio_mode = AspectRatioMode::Free;
+        cropper.crop_history.reset(cropper.crop_state().unwrap());
+        // A drag result, recorded as on drag_stopped
+        let dragged = egui::Rect::from_min_max(egui::pos2(0.4, 0.3), egui::pos2(0.6, 0.5));
+        cropper.crop_rect = Some(dragged);
+        cropper.record_crop();
+
+        cropper.frame_face();
+        assert_ne!(cropper.crop_rect, Some(dragged));
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, Some(dragged));
+
+        cropper.redo_crop();
+        let framed = cropper.crop_rect;
+        cropper.snap_and_maximize();
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, framed);
+        cropper.match_monitor(1920, 1080);
+        cropper.undo_crop();
+        assert_eq!(cropper.crop_rect, framed);
+    }
+
+    #[cfg(feature = "face-detect")]
+    #[test]
+    fn detect_face_reports_a_missing_model() {
+        let image = DynamicImage::new_rgb8(64, 64);
+        let result = face::detect_largest(&image, Path::new("/no/such/model.bin"));
+        assert!(matches!(result, Err(CropperError::FaceModel(_))));
+    }
+
+    #[test]
+    fn picked_files_batch_orients_and_numbers_clashing_names() {
+        // Orientation = 6, as in exif_orientation_is_applied_on_open_unless_turned_off
+        let exif = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0,
+            0,
+        ];
+        let folder = temp_path("files_batch_clash");
+        let (first, second, out_dir) = (folder.join("1"), folder.join("2"), folder.join("out"));
+        for dir in [&first, &second, &out_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let rotated = first.join("a.png");
+        let mut encoder =
+            image::codecs::png::PngEncoder::new(std::fs::File::create(&rotated).unwrap());
+        image::ImageEncoder::set_exif_metadata(&mut encoder, exif.to_vec()).unwrap();
+        DynamicImage::new_rgb8(80, 40)
+            .write_with_encoder(encoder)
+            .unwrap();
+        DynamicImage::new_rgb8(60, 60)
+            .save(second.join("a.png"))
+            .unwrap();
+
+        let template = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.5, 1.0));
+        let summary = batch::crop_files(
+            &[rotated, second.join("a.png")],
+            &out_dir,
+            template,
+            &Settings::default(),
+            &AtomicUsize::new(0),
+        );
+        let upright = image::open(out_dir.join("a_cropped.png")).unwrap();
+        let numbered = image::open(out_dir.join("a_cropped-1.png")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(summary.saved, 2);
+        assert_eq!((upright.width(), upright.height()), (20, 80));
+        assert_eq!((numbered.width(), numbered.height()), (30, 60));
+    }
+
+    #[test]
+    fn paste_shortcut_fires_once_per_ctrl_v() {
+        let v = |pressed, command| egui::Event::Key {
+            key: egui::Key::V,
+            physical_key: None,
+            pressed,
+            repeat: false,
+            modifiers: if command {
+                egui::Modifiers::COMMAND
+            } else {
+                egui::Modifiers::NONE
+            },
+        };
+        let mut seen = false;
+
+        // Pixels only: the press is swallowed, so the release counts while
+        // Ctrl/Cmd is still held
+        assert!(ImageCropper::paste_shortcut(&[v(false, true)], &mut seen));
+        assert!(!ImageCropper::paste_shortcut(&[v(false, false)], &mut seen));
+
+        // Text too: the paste event counts and its release does not
+        let paste = egui::Event::Paste("photo.png".to_owned());
+        assert!(ImageCropper::paste_shortcut(&[paste], &mut seen));
+        assert!(!ImageCropper::paste_shortcut(&[v(false, true)], &mut seen));
+
+        // A plain V is not a paste
+        assert!(!ImageCropper::paste_shortcut(&[v(true, false)], &mut seen));
+        assert!(!ImageCropper::paste_shortcut(&[v(false, false)], &mut seen));
+    }
+
+    #[test]
+    fn advancing_keeps_the_crop_and_uses_the_cached_listing() {
+        let folder = temp_path("advance");
+        std::fs::create_dir_all(&folder).unwrap();
+        let (a, b) = (folder.join("a.png"), folder.join("b.png"));
+        DynamicImage::new_rgb8(40, 20).save(&a).unwrap();
+        DynamicImage::new_rgb8(40, 20).save(&b).unwrap();
+
+        let ctx = egui::Context::default();
+        let mut cropper = ImageCropper::default();
+        cropper.open_image(&ctx, &a).unwrap();
+        // A file added later is not seen until the folder is listed again
+        DynamicImage::new_rgb8(40, 20)
+            .save(folder.join("0.png"))
+            .unwrap();
+        assert_eq!(cropper.sibling_path(-1), None);
+        cropper.refresh_folder_images();
+        assert_eq!(cropper.sibling_path(-1), Some(folder.join("0.png")));
+
+        let crop = egui::Rect::from_min_max(egui::pos2(0.25, 0.0), egui::pos2(0.75, 1.0));
+        cropper.crop_rect = Some(crop);
+        cropper.advance_keeping_crop(&ctx);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while cropper.loading.is_some() && std::time::Instant::now() < deadline {
+            cropper.poll_loading(&ctx);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(cropper.image_path.as_deref(), Some(b.as_path()));
+        assert_eq!(cropper.crop_rect, Some(crop));
+        assert_eq!(cropper.sibling_path(1), None);
+    }
+
+    #[test]
+    fn region_export_uses_the_save_encoding_and_safe_names() {
+        let folder = temp_path("region_export");
+        std::fs::create_dir_all(&folder).unwrap();
+        let mut cropper = cropper_with_image(100, 50);
+        cropper.image_path = Some(folder.join("photo.jpg"));
+        cropper.regions = vec![regions::Region {
+            name: "a*b?<c>|\"d".to_owned(),
+            rect: egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.5, 1.0)),
+        }];
+
+        cropper.export_regions(&folder).unwrap();
+        let jpeg = folder.join("photo_a_b__c___d_cropped.jpg");
+        assert!(image::open(&jpeg).is_ok());
+
+        // Round output needs transparency, so it switches to PNG
+        cropper.circular = true;
+        cropper.export_regions(&folder).unwrap();
+        let round = image::open(folder.join("photo_a_b__c___d_cropped.png")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(cropper.export_status.as_deref(), Some("Saved 1 regions"));
+        assert_eq!((round.width(), round.height()), (50, 50));
+        assert_eq!(round.to_rgba8().get_pixel(0, 0).0[3], 0);
+    }
+
+    #[test]
+    fn batch_names_outputs_with_the_filename_template() {
+        let folder = temp_path("batch_template");
+        std::fs::create_dir_all(&folder).unwrap();
+        DynamicImage::new_rgb8(160, 90)
+            .save(folder.join("a.png"))
+            .unwrap();
+        let settings = Settings {
+            filename_template: "{stem}-{ratio}-{w}.{ext}".to_owned(),
+            ..Settings::default()
+        };
+
+        let template = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(0.5, 0.5));
+        let summary = batch::apply_template(&folder, template, &settings).unwrap();
+        let named = image::image_dimensions(folder.join("a-16x9-80.png"));
+
+        // A template that names the source itself must not replace it
+        let settings = Settings {
+            filename_template: "{stem}.{ext}".to_owned(),
+            ..Settings::default()
+        };
+        let kept = batch::crop_files(
+            &[folder.join("a.png")],
+            &folder,
+            template,
+            &settings,
+            &AtomicUsize::new(0),
+        );
+        let source = image::image_dimensions(folder.join("a.png"));
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(summary.saved, 1);
+        assert_eq!(named.unwrap(), (80, 45));
+        assert!(matches!(
+            kept.failed[..],
+            [(_, CropperError::OverwriteSource(_))]
+        ));
+        assert_eq!(source.unwrap(), (160, 90));
+    }
+
+    #[test]
+    fn last_ratio_is_stored_and_restored_when_remembered() {
+        let mut cropper = cropper_with_image(10, 10);
+        cropper.aspect_ratio_mode = AspectRatioMode::Custom;
+        (cropper.custom_w, cropper.custom_h) = (5, 7);
+        cropper.is_portrait = true;
+        let mut storage = MemoryStorage::default();
+        eframe::App::save(&mut cropper, &mut storage);
+        let stored = eframe::get_value::<LastRatio>(&storage, LastRatio::STORAGE_KEY);
+
+        let mut settings = Settings::default();
+        let restored = LastRatio::at_startup(stored, &settings);
+        assert_eq!(restored.mode, AspectRatioMode::Custom);
+        assert_eq!((restored.custom_w, restored.custom_h), (5, 7));
+        assert!(restored.is_portrait);
+
+        settings.remember_ratio = false;
+        settings.default_aspect_ratio = AspectRatioMode::R16_9;
+        let default = LastRatio::at_startup(stored, &settings);
+        assert_eq!(default.mode, AspectRatioMode::R16_9);
+        assert_eq!((default.custom_w, default.custom_h), (5, 7));
+        assert!(!default.is_portrait);
+
+        let first_run = LastRatio::at_startup(None, &Settings::default());
+        assert_eq!(first_run.mode, AspectRatioMode::Free);
+        assert_eq!((first_run.custom_w, first_run.custom_h), (4, 3));
+    }
+
+    #[test]
+    fn canvas_drags_resize_through_pointer_input() {
+        let quarter = egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.75));
+        let (mut ui, mut cropper) = UiHarness::new(200, 100, quarter);
+        let image_rect = cropper.image_screen_rect.unwrap();
+        let right_edge = UiHarness::screen(&cropper, egui::pos2(0.75, 0.5));
+
+        // The right edge follows the pointer, and is let go on release
+        let to = right_edge + egui::vec2(image_rect.width() * 0.1, 0.0);
+        let held = ui.drag(&mut cropper, right_edge, to);
+        assert_eq!(held, Some(ResizeHandle::Right));
+        assert_eq!(cropper.selected_handle, None);
+        let crop = cropper.crop_rect.unwrap();
+        assert!((crop.max.x - 0.85).abs() < 1e-3, "{crop:?}");
+        assert_eq!((crop.min, crop.max.y), (quarter.min, quarter.max.y));
+
+        // A slip shorter than the drag threshold leaves the crop alone
+        let right_edge = UiHarness::screen(&cropper, egui::pos2(crop.max.x, 0.5));
+        ui.drag(&mut cropper, right_edge, right_edge + egui::vec2(2.0, 0.0));
+        assert_eq!(cropper.crop_rect, Some(crop));
+
+        // Stopping just short of the image border snaps onto it
+        let near_border = UiHarness::screen(&cropper, egui::pos2(1.0, 0.5))
+            - egui::vec2(SNAP_DISTANCE / 2.0, 0.0);
+        ui.drag(&mut cropper, right_edge, near_border);
+        assert_eq!(cropper.crop_rect.unwrap().max.x, 1.0);
+    }
+
+    #[test]
+    fn canvas_double_click_selects_the_whole_image() {
+        let quarter = egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.75));
+        let (mut ui, mut cropper) = UiHarness::new(200, 100, quarter);
+        let inside = UiHarness::screen(&cropper, egui::pos2(0.1, 0.1));
+        ui.frame(&mut cropper, vec![egui::Event::PointerMoved(inside)]);
+        for pressed in [true, false, true, false] {
+            ui.button(&mut cropper, inside, pressed);
+        }
+        assert_eq!(
+            cropper.crop_rect,
+            Some(egui::Rect::from_min_max(
+                egui::Pos2::ZERO,
+                egui::pos2(1.0, 1.0)
+            ))
+        );
+    }
 }
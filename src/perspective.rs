@@ -0,0 +1,30 @@
+use image::{DynamicImage, ImageBuffer, Rgba};
+use imageproc::geometric_transformations::{Interpolation, Projection, warp_into};
+
+/// Warps the quadrilateral `corners` of `image` into an upright rectangle.
+///
+/// `corners` are in pixel coordinates, ordered top-left, top-right,
+/// bottom-right, bottom-left. The output size is taken from the longer of
+/// each pair of opposite edges so no detail is lost. Returns `None` if the
+/// quad is degenerate (e.g. three corners on a line).
+pub fn rectify(image: &DynamicImage, corners: [(f32, f32); 4]) -> Option<DynamicImage> {
+    let dist = |a: (f32, f32), b: (f32, f32)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+    let [tl, tr, br, bl] = corners;
+
+    let width = dist(tl, tr).max(dist(bl, br)).round().max(1.0);
+    let height = dist(tl, bl).max(dist(tr, br)).round().max(1.0);
+
+    let target = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+    let projection = Projection::from_control_points(corners, target)?;
+
+    let source = image.to_rgba8();
+    let mut out = ImageBuffer::new(width as u32, height as u32);
+    warp_into(
+        &source,
+        &projection,
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+        &mut out,
+    );
+    Some(DynamicImage::ImageRgba8(out))
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Number of recently opened files offered in the Recent menu.
+pub const RECENT_FILES: usize = 10;
+
+/// Recently opened image files, newest first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentFiles {
+    paths: VecDeque<PathBuf>,
+}
+
+impl RecentFiles {
+    /// Key the list is kept under in eframe's storage.
+    pub const STORAGE_KEY: &str = "recent_files";
+
+    /// Moves `path` to the front, dropping the oldest entry when full.
+    pub fn push(&mut self, path: PathBuf) {
+        self.paths.retain(|p| *p != path);
+        self.paths.push_front(path);
+        self.paths.truncate(RECENT_FILES);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.paths.retain(|p| p != path);
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(PathBuf::as_path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
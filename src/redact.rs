@@ -0,0 +1,29 @@
+use image::{DynamicImage, GenericImage, imageops::FilterType};
+
+/// Largest blur radius / pixel block the strength slider offers, in pixels.
+pub const MAX_STRENGTH: u32 = 64;
+
+/// Pixel bounds of a region: left, top, width and height.
+pub type Region = (u32, u32, u32, u32);
+
+/// Blurs the pixels in `region` with a Gaussian of `sigma` pixels, leaving
+/// the rest of the image alone. The region is blurred on its own, so
+/// nothing outside it bleeds in.
+pub fn blur(image: &mut DynamicImage, (x, y, width, height): Region, sigma: f32) {
+    let blurred = image.crop_imm(x, y, width, height).blur(sigma);
+    // The block came out of this image, so it always fits back in
+    let _ = image.copy_from(&blurred, x, y);
+}
+
+/// Replaces the pixels in `region` with blocks of about `block` pixels, each
+/// filled with the average of the pixels it covers.
+pub fn pixelate(image: &mut DynamicImage, (x, y, width, height): Region, block: u32) {
+    let block = block.max(1);
+    let small = image.crop_imm(x, y, width, height).resize_exact(
+        width.div_ceil(block),
+        height.div_ceil(block),
+        FilterType::Triangle,
+    );
+    let blocky = small.resize_exact(width, height, FilterType::Nearest);
+    let _ = image.copy_from(&blocky, x, y);
+}
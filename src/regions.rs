@@ -0,0 +1,109 @@
+use crate::error::CropperError;
+use eframe::egui;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// A named crop in multi-crop mode, in normalized coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub rect: egui::Rect,
+}
+
+/// One region as written in a regions file, in pixels.
+#[derive(Deserialize)]
+struct RegionEntry {
+    name: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Parses a JSON array of named pixel regions for a `width`×`height` image:
+///
+/// ```json
+/// [{ "name": "avatar", "x": 10, "y": 20, "width": 64, "height": 64 }]
+/// ```
+///
+/// Regions partly outside the image are clamped to it. Duplicate names and
+/// regions entirely outside the image are rejected.
+pub fn parse_regions(json: &str, width: u32, height: u32) -> Result<Vec<Region>, CropperError> {
+    let invalid = |message: String| CropperError::InvalidRegions(message);
+    let entries: Vec<RegionEntry> =
+        serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+
+    let image =
+        egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(width as f32, height as f32));
+    let mut names = HashSet::new();
+    let mut regions = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let name = entry.name.as_str();
+        if name.is_empty() {
+            return Err(invalid(format!(
+                "region {}: name must be a non-empty string",
+                index
+            )));
+        }
+        if !names.insert(name) {
+            return Err(invalid(format!("duplicate region name \"{}\"", name)));
+        }
+        if ![entry.x, entry.y, entry.width, entry.height]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            return Err(invalid(format!("region \"{}\" is not finite", name)));
+        }
+
+        let rect = egui::Rect::from_min_size(
+            egui::pos2(entry.x, entry.y),
+            egui::vec2(entry.width, entry.height),
+        )
+        .intersect(image);
+        if !rect.is_positive() {
+            return Err(invalid(format!("region \"{}\" is outside the image", name)));
+        }
+
+        regions.push(Region {
+            name: name.to_owned(),
+            rect: egui::Rect::from_min_max(
+                (rect.min.to_vec2() / image.size()).to_pos2(),
+                (rect.max.to_vec2() / image.size()).to_pos2(),
+            ),
+        });
+    }
+    Ok(regions)
+}
+
+/// Characters Windows refuses in file names; `/` and `\` also keep a name
+/// to a single path component elsewhere.
+const RESERVED: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// `name` with reserved and control characters replaced by `_`. Names come
+/// from user files, so they may hold anything.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if RESERVED.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Crops each region of `image` and hands it to `save` along with the stem
+/// `<stem>_<name>` to name it by. Returns how many were saved.
+pub fn export_all(
+    image: &image::DynamicImage,
+    regions: &[Region],
+    stem: &str,
+    mut save: impl FnMut(&str, image::DynamicImage) -> Result<(), CropperError>,
+) -> Result<usize, CropperError> {
+    for region in regions {
+        let stem = format!("{}_{}", stem, sanitize(&region.name));
+        save(&stem, crate::compute_crop(image, region.rect))?;
+    }
+    Ok(regions.len())
+}
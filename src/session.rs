@@ -0,0 +1,26 @@
+use crate::AspectRatioMode;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The image being edited and its crop, stored with each auto-save so a
+/// crash can be recovered from. Cleared on a clean exit, so finding one at
+/// startup means the last run ended unexpectedly.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub image_path: PathBuf,
+    pub crop_rect: egui::Rect,
+    pub aspect_ratio: AspectRatioMode,
+    pub is_portrait: bool,
+}
+
+impl Session {
+    /// Key the session is kept under in eframe's storage.
+    pub const STORAGE_KEY: &str = "session";
+
+    /// Whether the stored crop can be restored; a damaged store may hold
+    /// anything.
+    pub fn has_usable_crop(&self) -> bool {
+        self.crop_rect.is_finite() && self.crop_rect.is_positive()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{AspectRatioMode, GridMode, Tool};
+use eframe::egui::ThemePreference;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// User preferences persisted between runs in eframe's storage. Fields
+/// missing from the stored value take their defaults, so older and newer
+/// versions can share it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Startup
+    pub default_tool: Tool,
+    pub default_aspect_ratio: AspectRatioMode,
+    pub open_clipboard_path: bool, // Open an image whose path is on the clipboard at launch
+    pub auto_orient: bool,         // Turn photos upright from their EXIF orientation when opened
+    pub remember_ratio: bool,      // Start with the ratio in use when the app last closed
+
+    // Workflow
+    pub auto_advance: bool, // Open the next image in the folder after saving
+    pub apply_last_crop: bool, // Start new images with the previous image's crop
+    pub allow_outside_crop: bool, // Let the crop extend past the image edges
+    pub ratio_epsilon: f32, // Relative tolerance for naming a crop after a preset ratio
+    pub face_headroom: f32, // Space above the face for Frame Face, in face heights
+    pub face_model: Option<PathBuf>, // SeetaFace model file used by Detect Face
+    pub preserve_icc_profile: bool, // Embed the source colour profile in exports
+    pub protect_source: bool, // Refuse to save over the open image's file
+    pub autosave_secs: u32, // Session auto-save interval; 0 turns it off
+
+    // Export
+    pub jpeg_quality: u8,
+    pub avif_quality: u8,
+    pub comparison_gutter: u32,
+    pub comparison_background: [u8; 3],
+    pub comparison_outline: bool,  // Trace the crop on the original
+    pub filename_template: String, // Suggested save name, see `filename::TOKENS`
+    pub multi_ratios: Vec<AspectRatioMode>, // Presets written by Export Ratios
+
+    // Appearance
+    pub theme: ThemePreference,
+    pub overlay_alpha: u8,
+    pub handle_radius: f32,
+    pub handles_on_hover: bool, // Draw the handles only while the pointer is near the crop
+    pub canvas_padding: f32,
+    pub contrast_border: bool, // Draw a dark stroke under the white crop border
+    pub corner_readout: bool,  // Always show the crop size in a canvas corner
+    pub anti_alias: bool,      // Feather strokes; off snaps the border to whole pixels
+    pub marching_ants: bool,   // Animated dashed border; costs a repaint every frame
+    pub show_overlays: bool,   // Dim, badges and labels; off leaves just the border and handles
+    pub grid_mode: GridMode,   // Composition guides inside the crop
+    pub show_preview: bool,    // Side panel with a thumbnail of the cropped result
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_tool: Tool::Crop,
+            default_aspect_ratio: AspectRatioMode::Free,
+            open_clipboard_path: false,
+            auto_orient: true,
+            remember_ratio: true,
+            auto_advance: false,
+            apply_last_crop: false,
+            allow_outside_crop: false,
+            ratio_epsilon: 0.01,
+            face_headroom: 0.5,
+            face_model: None,
+            preserve_icc_profile: true,
+            protect_source: true,
+            autosave_secs: 30,
+            jpeg_quality: 90,
+            avif_quality: 70,
+            comparison_gutter: 24,
+            comparison_background: [255, 255, 255],
+            comparison_outline: true,
+            filename_template: "{stem}_cropped.{ext}".to_owned(),
+            multi_ratios: vec![
+                AspectRatioMode::Square,
+                AspectRatioMode::R16_9,
+                AspectRatioMode::R9_16,
+            ],
+            theme: ThemePreference::System,
+            overlay_alpha: 150,
+            handle_radius: 6.0,
+            handles_on_hover: false,
+            canvas_padding: 20.0,
+            contrast_border: true,
+            corner_readout: false,
+            anti_alias: true,
+            marching_ants: false,
+            show_overlays: true,
+            grid_mode: GridMode::None,
+            show_preview: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Key the settings are kept under in eframe's storage.
+    pub const STORAGE_KEY: &str = "settings";
+
+    pub fn reset_startup(&mut self) {
+        let defaults = Self::default();
+        self.default_tool = defaults.default_tool;
+        self.default_aspect_ratio = defaults.default_aspect_ratio;
+        self.open_clipboard_path = defaults.open_clipboard_path;
+        self.auto_orient = defaults.auto_orient;
+        self.remember_ratio = defaults.remember_ratio;
+    }
+
+    pub fn reset_workflow(&mut self) {
+        let defaults = Self::default();
+        self.auto_advance = defaults.auto_advance;
+        self.apply_last_crop = defaults.apply_last_crop;
+        self.allow_outside_crop = defaults.allow_outside_crop;
+        self.ratio_epsilon = defaults.ratio_epsilon;
+        self.face_headroom = defaults.face_headroom;
+        self.preserve_icc_profile = defaults.preserve_icc_profile;
+        self.protect_source = defaults.protect_source;
+        self.autosave_secs = defaults.autosave_secs;
+    }
+
+    pub fn reset_export(&mut self) {
+        let defaults = Self::default();
+        self.jpeg_quality = defaults.jpeg_quality;
+        self.avif_quality = defaults.avif_quality;
+        self.comparison_gutter = defaults.comparison_gutter;
+        self.comparison_background = defaults.comparison_background;
+        self.comparison_outline = defaults.comparison_outline;
+        self.filename_template = defaults.filename_template;
+        self.multi_ratios = defaults.multi_ratios;
+    }
+
+    pub fn reset_appearance(&mut self) {
+        let defaults = Self::default();
+        self.theme = defaults.theme;
+        self.overlay_alpha = defaults.overlay_alpha;
+        self.handle_radius = defaults.handle_radius;
+        self.handles_on_hover = defaults.handles_on_hover;
+        self.canvas_padding = defaults.canvas_padding;
+        self.contrast_border = defaults.contrast_border;
+        self.corner_readout = defaults.corner_readout;
+        self.anti_alias = defaults.anti_alias;
+        self.marching_ants = defaults.marching_ants;
+        self.show_overlays = defaults.show_overlays;
+        self.grid_mode = defaults.grid_mode;
+        self.show_preview = defaults.show_preview;
+    }
+}
@@ -0,0 +1,53 @@
+use eframe::egui;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use imageproc::geometric_transformations::{Interpolation, Projection, warp_into};
+
+/// Largest straightening angle either way, in degrees.
+pub const MAX_ANGLE: f32 = 45.0;
+
+/// Size of the canvas that holds a `width`×`height` image turned by `degrees`.
+fn rotated_size(width: u32, height: u32, degrees: f32) -> (u32, u32) {
+    let (s, c) = degrees.to_radians().sin_cos();
+    let (s, c) = (s.abs(), c.abs());
+    let (w, h) = (width as f32, height as f32);
+    (
+        (w * c + h * s).round().max(1.0) as u32,
+        (w * s + h * c).round().max(1.0) as u32,
+    )
+}
+
+/// Turns `image` clockwise by `degrees` onto a canvas grown to fit all of
+/// it. The corners the rotation uncovers are transparent.
+pub fn rotate(image: &DynamicImage, degrees: f32) -> DynamicImage {
+    let (width, height) = rotated_size(image.width(), image.height(), degrees);
+    let projection = Projection::translate(width as f32 / 2.0, height as f32 / 2.0)
+        * Projection::rotate(degrees.to_radians())
+        * Projection::translate(
+            -(image.width() as f32) / 2.0,
+            -(image.height() as f32) / 2.0,
+        );
+    let mut out = ImageBuffer::new(width, height);
+    warp_into(
+        &image.to_rgba8(),
+        &projection,
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+        &mut out,
+    );
+    DynamicImage::ImageRgba8(out)
+}
+
+/// The largest centered crop of the rotated canvas that holds only image
+/// pixels, as a normalized rect. `ratio` is the crop's width over height in
+/// pixels; `None` keeps the source image's shape.
+pub fn inscribed_crop(width: u32, height: u32, degrees: f32, ratio: Option<f32>) -> egui::Rect {
+    let (s, c) = degrees.to_radians().sin_cos();
+    let (s, c) = (s.abs(), c.abs());
+    let (w, h) = (width as f32, height as f32);
+    let ratio = ratio.unwrap_or(w / h);
+    // Every corner of a ratio×1 box scaled by `k` must stay inside the turned image
+    let k = (w / (ratio * c + s)).min(h / (ratio * s + c));
+    let (canvas_w, canvas_h) = rotated_size(width, height, degrees);
+    let half = egui::vec2(ratio * k / canvas_w as f32, k / canvas_h as f32) * 0.5;
+    egui::Rect::from_min_max(egui::pos2(0.5, 0.5) - half, egui::pos2(0.5, 0.5) + half)
+}